@@ -0,0 +1,225 @@
+//! Determines the timestamp that is recorded in built packages.
+//!
+//! Two builds of the same source should produce byte-identical packages, so
+//! the timestamp is derived from the source instead of the current time. It is
+//! taken from the first of:
+//!
+//! 1. the `SOURCE_DATE_EPOCH` environment variable, see
+//!    <https://reproducible-builds.org/specs/source-date-epoch/>,
+//! 2. the time of the last git commit that touched the source directory, if
+//!    the directory has no uncommitted changes that the commit does not
+//!    describe,
+//! 3. the newest modification time of the manifest and the files that match
+//!    the input globs of the build.
+//!
+//! Only when none of these is available the current time is used.
+
+use std::{collections::BTreeSet, path::Path, process::Command};
+
+use chrono::{DateTime, Utc};
+use fs_err as fs;
+use miette::{Diagnostic, IntoDiagnostic};
+use thiserror::Error;
+
+use crate::outputs_cache::find_input_files;
+
+/// The environment variable that overrides the timestamp of a build.
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("`{SOURCE_DATE_EPOCH}` must be a number of seconds since the Unix epoch, got `{0}`")]
+pub struct InvalidSourceDateEpoch(String);
+
+/// Where the timestamp of a build was derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampSource {
+    SourceDateEpoch,
+    GitCommit,
+    InputFiles,
+    Now,
+}
+
+/// The timestamp that is recorded in a built package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildTimestamp {
+    pub timestamp: DateTime<Utc>,
+    pub source: TimestampSource,
+}
+
+impl BuildTimestamp {
+    /// Determines the timestamp of a build of the package in `source_dir`.
+    pub fn determine(
+        source_dir: &Path,
+        manifest_path: &Path,
+        input_globs: &BTreeSet<String>,
+    ) -> miette::Result<Self> {
+        Self::determine_with_source_date_epoch(
+            std::env::var(SOURCE_DATE_EPOCH).ok().as_deref(),
+            source_dir,
+            manifest_path,
+            input_globs,
+        )
+    }
+
+    fn determine_with_source_date_epoch(
+        source_date_epoch: Option<&str>,
+        source_dir: &Path,
+        manifest_path: &Path,
+        input_globs: &BTreeSet<String>,
+    ) -> miette::Result<Self> {
+        let (timestamp, source) = if let Some(epoch) = source_date_epoch {
+            let timestamp = epoch
+                .trim()
+                .parse()
+                .ok()
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+                .ok_or_else(|| InvalidSourceDateEpoch(epoch.to_string()))?;
+            (timestamp, TimestampSource::SourceDateEpoch)
+        } else if let Some(timestamp) = last_commit_time(source_dir) {
+            (timestamp, TimestampSource::GitCommit)
+        } else if let Some(timestamp) = newest_input_mtime(source_dir, manifest_path, input_globs)?
+        {
+            (timestamp, TimestampSource::InputFiles)
+        } else {
+            (Utc::now(), TimestampSource::Now)
+        };
+
+        tracing::debug!("using build timestamp {timestamp} ({source:?})");
+        Ok(Self { timestamp, source })
+    }
+}
+
+/// Returns the time of the last git commit that touched `source_dir`, or
+/// `None` if the directory is not part of a git repository, git is not
+/// available or the directory has uncommitted changes, including untracked
+/// files that are not ignored.
+fn last_commit_time(source_dir: &Path) -> Option<DateTime<Utc>> {
+    let status = git(source_dir, &["status", "--porcelain", "--", "."])?;
+    if !status.trim().is_empty() {
+        return None;
+    }
+    let seconds = git(source_dir, &["log", "-1", "--format=%ct", "--", "."])?
+        .trim()
+        .parse()
+        .ok()?;
+    DateTime::from_timestamp(seconds, 0)
+}
+
+/// Runs git in `dir` and returns its output, or `None` if git is not
+/// available or fails.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Returns the newest modification time of the manifest and the files in
+/// `source_dir` that match the input globs.
+fn newest_input_mtime(
+    source_dir: &Path,
+    manifest_path: &Path,
+    input_globs: &BTreeSet<String>,
+) -> miette::Result<Option<DateTime<Utc>>> {
+    let mut newest = None;
+    let input_files = find_input_files(source_dir, input_globs)?
+        .into_iter()
+        .map(|path| source_dir.join(path));
+    for path in std::iter::once(manifest_path.to_path_buf()).chain(input_files) {
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        let modified = DateTime::<Utc>::from(metadata.modified().into_diagnostic()?);
+        newest = newest.max(Some(modified));
+    }
+    Ok(newest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=pixi",
+                "-c",
+                "user.email=pixi@example.com",
+                "-c",
+                "commit.gpgsign=false",
+            ])
+            .args(args)
+            .env("GIT_COMMITTER_DATE", "@1500000000 +0000")
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    fn set_mtime(path: &Path, seconds: u64) {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_determine_build_timestamp() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let manifest_path = source_dir.path().join("pixi.toml");
+        let source_path = source_dir.path().join("main.c");
+        let ignored_path = source_dir.path().join("README.md");
+        for path in [&manifest_path, &source_path, &ignored_path] {
+            fs::write(path, "").unwrap();
+        }
+        set_mtime(&manifest_path, 1_000);
+        set_mtime(&source_path, 2_000);
+        set_mtime(&ignored_path, 3_000);
+        let input_globs = BTreeSet::from(["*.c".to_string()]);
+
+        let determine = |epoch| {
+            BuildTimestamp::determine_with_source_date_epoch(
+                epoch,
+                source_dir.path(),
+                &manifest_path,
+                &input_globs,
+            )
+        };
+
+        // `SOURCE_DATE_EPOCH` takes precedence
+        let timestamp = determine(Some("1700000000")).unwrap();
+        assert_eq!(timestamp.source, TimestampSource::SourceDateEpoch);
+        assert_eq!(timestamp.timestamp.timestamp(), 1_700_000_000);
+        assert!(determine(Some("yesterday")).is_err());
+
+        // Without git the newest matching input file is used
+        let timestamp = determine(None).unwrap();
+        assert_eq!(timestamp.source, TimestampSource::InputFiles);
+        assert_eq!(timestamp.timestamp.timestamp(), 2_000);
+
+        // In a clean repository the time of the last commit is used
+        run_git(source_dir.path(), &["init", "-q"]);
+        run_git(source_dir.path(), &["add", "."]);
+        run_git(source_dir.path(), &["commit", "-q", "-m", "initial"]);
+        let timestamp = determine(None).unwrap();
+        assert_eq!(timestamp.source, TimestampSource::GitCommit);
+        assert_eq!(timestamp.timestamp.timestamp(), 1_500_000_000);
+
+        // The commit does not describe uncommitted changes
+        fs::write(&source_path, "int main() {}").unwrap();
+        set_mtime(&source_path, 2_500);
+        let timestamp = determine(None).unwrap();
+        assert_eq!(timestamp.source, TimestampSource::InputFiles);
+        assert_eq!(timestamp.timestamp.timestamp(), 2_500);
+    }
+}
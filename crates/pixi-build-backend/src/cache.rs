@@ -1,12 +1,16 @@
+//! Compiler caches that speed up repeated builds.
+
 use std::collections::HashMap;
 
+use indexmap::IndexMap;
 use itertools::Itertools;
+use miette::IntoDiagnostic;
+use pixi_build_types::SourcePackageName;
+use recipe_stage0::{matchspec::PackageDependency, recipe::Item};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-use crate::{ProjectModel, Targets, traits::Dependencies};
-
-pub fn sccache_tools() -> Vec<String> {
-    vec!["sccache".to_string()]
-}
+use crate::{env_passthrough::passthrough_secrets, traits::Dependencies};
 
 /// Return environment variables that are used by sccache.
 pub fn sccache_envs(env: &HashMap<String, String>) -> Option<Vec<&str>> {
@@ -18,14 +22,149 @@ pub fn sccache_envs(env: &HashMap<String, String>) -> Option<Vec<&str>> {
     if res.is_empty() { None } else { Some(res) }
 }
 
-pub fn add_sccache<'a, P: ProjectModel>(
-    dependencies: &mut Dependencies<'a, <P::Targets as Targets>::Spec>,
-    sccache_tools: &'a [String],
-    empty_spec: &'a <<P as ProjectModel>::Targets as Targets>::Spec,
-) {
-    for cache_tool in sccache_tools {
-        if !dependencies.build.contains_key(&cache_tool) {
-            dependencies.build.insert(cache_tool, empty_spec);
+/// A compiler cache that the compilers of a build are launched through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompilerCache {
+    /// [sccache](https://github.com/mozilla/sccache)
+    Sccache,
+    /// [ccache](https://ccache.dev)
+    Ccache,
+}
+
+impl CompilerCache {
+    /// The name of the executable, which is also the name of the package that
+    /// provides it.
+    pub fn tool(self) -> &'static str {
+        match self {
+            CompilerCache::Sccache => "sccache",
+            CompilerCache::Ccache => "ccache",
+        }
+    }
+
+    /// The prefix of the environment variables that configure the cache.
+    pub fn env_prefix(self) -> &'static str {
+        match self {
+            CompilerCache::Sccache => "SCCACHE",
+            CompilerCache::Ccache => "CCACHE",
+        }
+    }
+
+    /// Returns the names of the host environment variables that configure the
+    /// cache. They are forwarded to the build script as secrets, except for
+    /// the variables that are set explicitly in the `env` of the script.
+    pub fn secrets(
+        self,
+        script_env: &IndexMap<String, String>,
+        host_env_names: impl IntoIterator<Item = String>,
+    ) -> miette::Result<Vec<String>> {
+        passthrough_secrets(
+            &[format!("{}_*", self.env_prefix())],
+            script_env,
+            host_env_names,
+        )
+    }
+
+    /// Returns the names of the environment variables of the current process
+    /// that configure the cache, see [`CompilerCache::secrets`].
+    pub fn host_secrets(
+        self,
+        script_env: &IndexMap<String, String>,
+    ) -> miette::Result<Vec<String>> {
+        self.secrets(
+            script_env,
+            std::env::vars_os().filter_map(|(name, _)| name.into_string().ok()),
+        )
+    }
+
+    /// The wrapper that `cargo` launches `rustc` through to cache it, if the
+    /// cache supports Rust.
+    pub fn rustc_wrapper(self) -> Option<&'static str> {
+        match self {
+            CompilerCache::Sccache => Some(self.tool()),
+            CompilerCache::Ccache => None,
         }
     }
+
+    /// Adds the cache to the build requirements, unless the project already
+    /// depends on it.
+    pub fn add_to_requirements<S>(
+        self,
+        requirements: &mut Vec<Item<PackageDependency>>,
+        dependencies: &Dependencies<S>,
+    ) -> miette::Result<()> {
+        if !dependencies
+            .build
+            .contains_key(&SourcePackageName::from(self.tool()))
+        {
+            requirements.push(self.tool().parse().into_diagnostic()?);
+        }
+        Ok(())
+    }
+
+    /// Returns the CMake arguments that launch the compilers of the given
+    /// languages through the cache.
+    pub fn cmake_launcher_args(self, compilers: &[String]) -> Vec<String> {
+        let has_compiler = |language: &str| compilers.iter().any(|name| name == language);
+        let mut languages = Vec::new();
+        if has_compiler("c") || has_compiler("cxx") {
+            languages.extend(["C", "CXX"]);
+        }
+        if has_compiler("cuda") {
+            languages.push("CUDA");
+        }
+        languages
+            .into_iter()
+            .map(|language| format!("-DCMAKE_{language}_COMPILER_LAUNCHER={}", self.tool()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compiler_cache_secrets() {
+        let script_env = IndexMap::from([("CCACHE_DIR".to_string(), "/cache".to_string())]);
+        let host_env_names =
+            ["PATH", "CCACHE_MAXSIZE", "CCACHE_DIR", "SCCACHE_BUCKET"].map(String::from);
+
+        assert_eq!(
+            CompilerCache::Ccache
+                .secrets(&script_env, host_env_names.clone())
+                .unwrap(),
+            ["CCACHE_MAXSIZE"]
+        );
+        assert_eq!(
+            CompilerCache::Sccache
+                .secrets(&script_env, host_env_names)
+                .unwrap(),
+            ["SCCACHE_BUCKET"]
+        );
+    }
+
+    #[test]
+    fn test_cmake_launcher_args() {
+        assert_eq!(
+            CompilerCache::Sccache.cmake_launcher_args(&["cxx".to_string()]),
+            [
+                "-DCMAKE_C_COMPILER_LAUNCHER=sccache",
+                "-DCMAKE_CXX_COMPILER_LAUNCHER=sccache"
+            ]
+        );
+        assert_eq!(
+            CompilerCache::Ccache.cmake_launcher_args(&["c".to_string(), "cuda".to_string()]),
+            [
+                "-DCMAKE_C_COMPILER_LAUNCHER=ccache",
+                "-DCMAKE_CXX_COMPILER_LAUNCHER=ccache",
+                "-DCMAKE_CUDA_COMPILER_LAUNCHER=ccache"
+            ]
+        );
+        assert!(
+            CompilerCache::Ccache
+                .cmake_launcher_args(&["fortran".to_string()])
+                .is_empty()
+        );
+    }
 }
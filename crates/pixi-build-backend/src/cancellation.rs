@@ -0,0 +1,389 @@
+//! Cancellation of in-flight JSON-RPC requests.
+//!
+//! The frontend can send a `$/cancelRequest` notification with the id of a
+//! request that it is no longer interested in. The future that handles the
+//! request is aborted and the request is answered with an error that has the
+//! [`REQUEST_CANCELLED_ERROR_CODE`].
+//!
+//! A `$/cancelRequest` may arrive before the request it cancels has been
+//! registered, because requests are handled concurrently. Such a cancellation
+//! is remembered, and the request is answered as cancelled as soon as it
+//! arrives.
+//!
+//! Aborting the future does not stop the build script that rattler-build
+//! spawned, nor the compilers that were in turn spawned by the build script.
+//! On Unix the build script therefore runs in a process group of its own (see
+//! [`run_build_scripts_in_process_group`]), and a build registers its work
+//! directory with [`register_build_process_group`]. On cancellation the process
+//! groups of the builds of the cancelled request are terminated. Builds of
+//! other requests, which are handled concurrently, keep running.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use futures::future::{AbortHandle, Aborted, Either, abortable};
+use indexmap::IndexSet;
+use jsonrpc_core::{
+    BoxFuture, Call, Error, ErrorCode, Failure, Id, Metadata, Middleware, Output,
+    middleware::NoopFuture,
+};
+use parking_lot::Mutex;
+use recipe_stage0::recipe::{IntermediateRecipe, Script};
+use serde::Deserialize;
+
+/// The name of the notification that cancels an in-flight request.
+pub const CANCEL_REQUEST_METHOD_NAME: &str = "$/cancelRequest";
+
+/// The JSON-RPC error code of a request that was cancelled by the client.
+pub const REQUEST_CANCELLED_ERROR_CODE: i64 = -32800;
+
+/// The parameters of the `$/cancelRequest` notification.
+#[derive(Debug, Deserialize)]
+pub struct CancelRequestParams {
+    /// The id of the request to cancel.
+    pub id: Id,
+}
+
+/// The maximum number of cancellations of requests that have not arrived yet
+/// that are remembered. Cancellations of requests that already finished are
+/// indistinguishable from these, so the oldest ones are forgotten.
+const MAX_EARLY_CANCELLATIONS: usize = 1024;
+
+/// The file in the work directory of a build that contains the id of the
+/// process group of its build script.
+pub const PROCESS_GROUP_FILE: &str = ".pixi-build-process-group";
+
+/// Runs the build scripts of the recipe and of its outputs in a process group
+/// of their own, whose id is written to the [`PROCESS_GROUP_FILE`] in the work
+/// directory. Does nothing on Windows.
+pub fn run_build_scripts_in_process_group(recipe: &mut IntermediateRecipe) {
+    if !cfg!(unix) {
+        return;
+    }
+
+    run_in_process_group(&mut recipe.build.script);
+    for output in &mut recipe.outputs {
+        if let Some(build) = &mut output.build {
+            run_in_process_group(&mut build.script);
+        }
+    }
+}
+
+/// Makes the script run in a process group of its own. The script is started
+/// again as a background job with job control enabled, which puts it into a new
+/// process group, and the id of that group is written to the
+/// [`PROCESS_GROUP_FILE`] in the work directory (`$SRC_DIR`). Terminating the
+/// group terminates the script together with every process it spawned.
+///
+/// An empty script, which makes rattler-build run the default build script, is
+/// left alone.
+fn run_in_process_group(script: &mut Script) {
+    if script.content.is_empty() {
+        return;
+    }
+
+    script.content = format!(
+        r#"if [ -z "${{PIXI_BUILD_PROCESS_GROUP:-}}" ]; then
+  set -m
+  PIXI_BUILD_PROCESS_GROUP=1 "$BASH" -e "$0" "$@" &
+  echo "$!" > "$SRC_DIR/{PROCESS_GROUP_FILE}"
+  wait "$!"
+  exit
+fi
+unset PIXI_BUILD_PROCESS_GROUP
+{}"#,
+        script.content
+    );
+}
+
+tokio::task_local! {
+    /// The process group files of the builds of the request that is handled by
+    /// the current task.
+    static PROCESS_GROUP_FILES: ProcessGroupFiles;
+}
+
+type ProcessGroupFiles = Arc<Mutex<Vec<PathBuf>>>;
+
+/// Records that the request that is currently being handled builds in
+/// `work_dir`, so that the process group of its build script is terminated
+/// when the request is cancelled. A process group file that was left behind by
+/// a previous build in the same directory is removed. Does nothing outside of
+/// a request.
+pub fn register_build_process_group(work_dir: &Path) {
+    let file = work_dir.join(PROCESS_GROUP_FILE);
+    let _ = PROCESS_GROUP_FILES.try_with(|files| {
+        match fs_err::remove_file(&file) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => tracing::debug!("{err}"),
+            _ => {}
+        }
+        files.lock().push(file);
+    });
+}
+
+/// An in-flight request.
+struct InFlightRequest {
+    abort_handle: AbortHandle,
+    process_group_files: ProcessGroupFiles,
+}
+
+/// The requests that can be cancelled.
+#[derive(Default)]
+struct Requests {
+    in_flight: HashMap<Id, InFlightRequest>,
+    /// The ids of cancelled requests that were not in-flight yet.
+    cancelled: IndexSet<Id>,
+}
+
+/// A middleware that keeps track of all in-flight requests so that they can be
+/// cancelled.
+#[derive(Clone, Default)]
+pub struct RequestCancellation {
+    requests: Arc<Mutex<Requests>>,
+}
+
+impl RequestCancellation {
+    /// Aborts the in-flight request with the given id and terminates the
+    /// process groups of its builds. Returns `false` if there is no such
+    /// request, in which case the request is answered as cancelled if it
+    /// arrives later on.
+    pub fn cancel(&self, id: &Id) -> bool {
+        let request = {
+            let mut requests = self.requests.lock();
+            let request = requests.in_flight.remove(id);
+            if request.is_none() {
+                if requests.cancelled.len() == MAX_EARLY_CANCELLATIONS {
+                    requests.cancelled.shift_remove_index(0);
+                }
+                requests.cancelled.insert(id.clone());
+            }
+            request
+        };
+        let Some(request) = request else {
+            return false;
+        };
+        request.abort_handle.abort();
+        let process_group_files = std::mem::take(&mut *request.process_group_files.lock());
+        terminate_process_groups(&process_group_files);
+        true
+    }
+}
+
+impl<M: Metadata> Middleware<M> for RequestCancellation {
+    type Future = NoopFuture;
+    type CallFuture = BoxFuture<Option<Output>>;
+
+    fn on_call<F, X>(&self, call: Call, meta: M, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, M) -> X + Send + Sync,
+        X: Future<Output = Option<Output>> + Send + 'static,
+    {
+        // Notifications do not have an id and can therefore not be cancelled.
+        let Call::MethodCall(method_call) = &call else {
+            return Either::Right(next(call, meta));
+        };
+        let id = method_call.id.clone();
+        let jsonrpc = method_call.jsonrpc;
+
+        let mut requests = self.requests.lock();
+        if requests.cancelled.shift_remove(&id) {
+            return Either::Left(Box::pin(async move {
+                Some(Output::Failure(Failure {
+                    jsonrpc,
+                    error: request_cancelled_error(),
+                    id,
+                }))
+            }));
+        }
+
+        let process_group_files = ProcessGroupFiles::default();
+        let (future, abort_handle) =
+            abortable(PROCESS_GROUP_FILES.scope(process_group_files.clone(), next(call, meta)));
+        requests.in_flight.insert(
+            id.clone(),
+            InFlightRequest {
+                abort_handle,
+                process_group_files,
+            },
+        );
+        drop(requests);
+
+        let requests = self.requests.clone();
+        Either::Left(Box::pin(async move {
+            let result = future.await;
+            requests.lock().in_flight.remove(&id);
+            match result {
+                Ok(output) => output,
+                Err(Aborted) => Some(Output::Failure(Failure {
+                    jsonrpc,
+                    error: request_cancelled_error(),
+                    id,
+                })),
+            }
+        }))
+    }
+}
+
+/// Returns the error that is sent in response to a cancelled request.
+pub fn request_cancelled_error() -> Error {
+    Error {
+        code: ErrorCode::ServerError(REQUEST_CANCELLED_ERROR_CODE),
+        message: String::from("request cancelled"),
+        data: None,
+    }
+}
+
+/// Terminates the process groups whose ids are written to the given files.
+/// Builds that did not start their build script yet have no such file.
+fn terminate_process_groups(process_group_files: &[PathBuf]) {
+    for file in process_group_files {
+        let Ok(contents) = fs_err::read_to_string(file) else {
+            continue;
+        };
+        let Ok(process_group) = contents.trim().parse::<u32>() else {
+            tracing::debug!("invalid process group in {}", file.display());
+            continue;
+        };
+        tracing::debug!("terminating build process group {process_group}");
+        terminate_process_group(process_group);
+    }
+}
+
+#[cfg(unix)]
+fn terminate_process_group(process_group: u32) {
+    let Ok(process_group) = libc::pid_t::try_from(process_group) else {
+        return;
+    };
+    // SAFETY: `killpg` has no memory safety requirements, it fails if the
+    // process group no longer exists.
+    unsafe {
+        libc::killpg(process_group, libc::SIGTERM);
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate_process_group(_process_group: u32) {}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_core::{MetaIoHandler, Value};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancel_in_flight_request() {
+        let cancellation = RequestCancellation::default();
+        let mut io = MetaIoHandler::with_middleware(cancellation.clone());
+        io.add_method("wait", |_params| {
+            futures::future::pending::<jsonrpc_core::Result<Value>>()
+        });
+
+        let response = io.handle_request(r#"{"jsonrpc":"2.0","method":"wait","id":1}"#, ());
+        assert!(cancellation.cancel(&Id::Num(1)));
+
+        let response: Value = serde_json::from_str(&response.await.unwrap()).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], REQUEST_CANCELLED_ERROR_CODE);
+
+        // The request is no longer in-flight
+        assert!(!cancellation.cancel(&Id::Num(1)));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_before_the_request_arrives() {
+        let cancellation = RequestCancellation::default();
+        let mut io = MetaIoHandler::with_middleware(cancellation.clone());
+        io.add_method("ready", |_params| async { Ok(Value::Null) });
+
+        // The cancellation is read before the request it cancels.
+        assert!(!cancellation.cancel(&Id::Num(1)));
+
+        let response = io
+            .handle_request(r#"{"jsonrpc":"2.0","method":"ready","id":1}"#, ())
+            .await
+            .unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["error"]["code"], REQUEST_CANCELLED_ERROR_CODE);
+
+        // The cancellation only applies to a single request.
+        let response = io
+            .handle_request(r#"{"jsonrpc":"2.0","method":"ready","id":1}"#, ())
+            .await
+            .unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["result"], Value::Null);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_only_terminates_the_process_group_of_the_request() {
+        let cancelled_dir = tempfile::tempdir().unwrap();
+        let other_dir = tempfile::tempdir().unwrap();
+
+        // Like rattler-build, which writes the build script to the work
+        // directory and runs it with bash.
+        let mut script = Script {
+            content: String::from("sleep 30 &\nsleep 30"),
+            ..Script::default()
+        };
+        run_in_process_group(&mut script);
+        let spawn_build = |dir: &Path| {
+            let script_path = dir.join("conda_build.sh");
+            fs_err::write(&script_path, &script.content).unwrap();
+            std::process::Command::new("bash")
+                .arg("-e")
+                .arg(&script_path)
+                .env("SRC_DIR", dir)
+                .current_dir(dir)
+                .spawn()
+                .unwrap()
+        };
+        let wait_for_process_group = |dir: &Path| {
+            let file = dir.join(PROCESS_GROUP_FILE);
+            while !fs_err::read_to_string(&file).is_ok_and(|pid| pid.ends_with('\n')) {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        };
+
+        let cancellation = RequestCancellation::default();
+        let mut io = MetaIoHandler::with_middleware(cancellation.clone());
+        io.add_method("build", |params: jsonrpc_core::Params| async move {
+            let (dir,): (PathBuf,) = params.parse()?;
+            register_build_process_group(&dir);
+            futures::future::pending::<jsonrpc_core::Result<Value>>().await
+        });
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "build",
+            "params": [cancelled_dir.path()],
+            "id": 1,
+        });
+        let response = io.handle_request(&request.to_string(), ());
+        let (response, (mut cancelled_build, mut other_build)) = futures::join!(response, async {
+            // Let the request register its build before starting it.
+            tokio::task::yield_now().await;
+            let cancelled_build = spawn_build(cancelled_dir.path());
+            let other_build = spawn_build(other_dir.path());
+            wait_for_process_group(cancelled_dir.path());
+            wait_for_process_group(other_dir.path());
+            assert!(cancellation.cancel(&Id::Num(1)));
+            (cancelled_build, other_build)
+        });
+        let response: Value = serde_json::from_str(&response.unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], REQUEST_CANCELLED_ERROR_CODE);
+
+        // Only the build of the cancelled request is terminated.
+        assert!(!cancelled_build.wait().unwrap().success());
+        assert!(other_build.try_wait().unwrap().is_none());
+        let other_process_group = fs_err::read_to_string(other_dir.path().join(PROCESS_GROUP_FILE))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        terminate_process_group(other_process_group);
+        other_build.wait().unwrap();
+    }
+}
@@ -1,14 +1,25 @@
-use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
-use miette::IntoDiagnostic;
+use miette::{Context, IntoDiagnostic};
 use pixi_build_types::{
-    BackendCapabilities, FrontendCapabilities,
-    procedures::negotiate_capabilities::NegotiateCapabilitiesParams,
+    BackendCapabilities, FrontendCapabilities, ProjectModelV1, VersionedProjectModel,
+    procedures::{
+        initialize::InitializeParams, negotiate_capabilities::NegotiateCapabilitiesParams,
+    },
 };
 use rattler_build::console_utils::{LoggingOutputHandler, get_default_env_filter};
+use rattler_conda_types::{ChannelUrl, Platform};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::{protocol::ProtocolInstantiator, server::Server};
+use crate::{
+    notifications::NotificationSink,
+    protocol::{GenerateRecipeParams, ProtocolInstantiator},
+    replay::{self, Recording, ReplayProcedure},
+    sandbox::SandboxLogLayer,
+    server::Server,
+};
 
 #[allow(missing_docs)]
 #[derive(Parser)]
@@ -19,9 +30,15 @@ pub struct App {
 
     /// The port to expose the json-rpc server on. If not specified will
     /// communicate with stdin/stdout.
-    #[clap(long)]
+    #[clap(long, conflicts_with = "socket")]
     http_port: Option<u16>,
 
+    /// The path of a Unix domain socket to expose the json-rpc server on. The
+    /// server keeps running when a client disconnects so that it can be
+    /// reused.
+    #[clap(long)]
+    socket: Option<PathBuf>,
+
     /// Enable verbose logging.
     #[command(flatten)]
     verbose: Verbosity<InfoLevel>,
@@ -31,13 +48,87 @@ pub struct App {
 pub enum Commands {
     /// Get the capabilities of the backend.
     Capabilities,
+    /// Generate the recipe of a package without building it, and print it
+    /// together with the outputs that are discovered by expanding the
+    /// variants.
+    GenerateRecipe(GenerateRecipeArgs),
+    /// Replay a request that was recorded in a debug directory.
+    Replay(ReplayArgs),
+    /// Print the JSON Schema of the backend configuration.
+    ConfigSchema,
+}
+
+#[derive(Args, Debug)]
+pub struct GenerateRecipeArgs {
+    /// The path to the manifest of the package.
+    #[clap(long)]
+    manifest: PathBuf,
+
+    /// The project model, either as JSON or as the path to a JSON file.
+    #[clap(long)]
+    project_model: String,
+
+    /// The platform to generate the recipe for.
+    #[clap(long, default_value_t = Platform::current())]
+    platform: Platform,
+
+    /// The backend configuration, either as JSON or as the path to a JSON
+    /// file.
+    #[clap(long)]
+    config: Option<String>,
+
+    /// The channels that are passed to the generator.
+    #[clap(long = "channel")]
+    channels: Vec<url::Url>,
+
+    /// Variant files to use when expanding the variants.
+    #[clap(long = "variant-file")]
+    variant_files: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// The debug directory that contains the recorded request.
+    debug_dir: PathBuf,
+
+    /// The procedure to replay. Defaults to `conda-build-v1` if it was
+    /// recorded and `conda-outputs` otherwise.
+    #[clap(long, value_enum)]
+    procedure: Option<ReplayProcedure>,
+
+    /// The path to the manifest of the package. Overrides the recorded
+    /// manifest path.
+    #[clap(long)]
+    manifest: Option<PathBuf>,
+
+    /// Compare the response with the recorded response instead of printing
+    /// it.
+    #[clap(long)]
+    diff: bool,
 }
 
-/// Run the sever on the specified port or over stdin/stdout.
-async fn run_server<T: ProtocolInstantiator>(port: Option<u16>, protocol: T) -> miette::Result<()> {
-    let server = Server::new(protocol);
+/// Run the sever on the specified port, socket or over stdin/stdout.
+async fn run_server<T: ProtocolInstantiator>(
+    port: Option<u16>,
+    socket: Option<PathBuf>,
+    protocol: T,
+    notifications: NotificationSink,
+) -> miette::Result<()> {
+    let server = Server::new(protocol).with_notifications(notifications);
     if let Some(port) = port {
         server.run_over_http(port)
+    } else if let Some(socket) = socket {
+        #[cfg(unix)]
+        {
+            server.run_over_socket(&socket).await
+        }
+        #[cfg(not(unix))]
+        {
+            miette::bail!(
+                "cannot listen on {}, unix domain sockets are not supported on this platform",
+                socket.display()
+            )
+        }
     } else {
         // running over stdin/stdout
         server.run().await
@@ -51,16 +142,21 @@ pub(crate) async fn main_impl<T: ProtocolInstantiator, F: FnOnce(LoggingOutputHa
 ) -> miette::Result<()> {
     // Setup logging
     let log_handler = LoggingOutputHandler::default();
+    let notifications = NotificationSink::default();
 
     let registry = tracing_subscriber::registry()
         .with(get_default_env_filter(args.verbose.log_level_filter()).into_diagnostic()?);
 
-    registry.with(log_handler.clone()).init();
+    registry
+        .with(log_handler.clone())
+        .with(notifications.layer())
+        .with(SandboxLogLayer)
+        .init();
 
     let factory = factory(log_handler);
 
     match args.command {
-        None => run_server(args.http_port, factory).await,
+        None => run_server(args.http_port, args.socket, factory, notifications).await,
         Some(Commands::Capabilities) => {
             let backend_capabilities = capabilities::<T>().await?;
             eprintln!(
@@ -79,7 +175,125 @@ pub(crate) async fn main_impl<T: ProtocolInstantiator, F: FnOnce(LoggingOutputHa
             );
             Ok(())
         }
+        Some(Commands::GenerateRecipe(args)) => generate_recipe(factory, args).await,
+        Some(Commands::Replay(args)) => replay(factory, args).await,
+        Some(Commands::ConfigSchema) => {
+            let schema = T::config_schema().ok_or_else(|| {
+                miette::miette!("this backend does not provide a configuration schema")
+            })?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&schema).into_diagnostic()?
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Re-initializes the backend from a debug directory and re-issues the
+/// recorded procedure.
+async fn replay<T: ProtocolInstantiator>(factory: T, args: ReplayArgs) -> miette::Result<()> {
+    let recording = Recording::load(&args.debug_dir, args.procedure, args.manifest)?;
+    let procedure = recording.procedure.kind();
+
+    // Replay in a fresh work directory so that the recorded debug directory is
+    // not overwritten. It is kept so that the artifacts can be inspected.
+    let work_directory = tempfile::Builder::new()
+        .prefix("pixi-build-replay-")
+        .tempdir()
+        .into_diagnostic()
+        .wrap_err("failed to create a work directory to replay in")?
+        .keep();
+    eprintln!("Replaying in {}", work_directory.display());
+
+    let (protocol, _) = factory.initialize(recording.initialize).await?;
+    let result = recording
+        .procedure
+        .replay(protocol.as_ref(), &work_directory)
+        .await;
+
+    if !args.diff {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&result?).into_diagnostic()?
+        );
+        return Ok(());
+    }
+
+    let recorded = replay::recorded_response(&args.debug_dir, procedure)?;
+    let replayed = replay::response_to_json(result);
+    let differences = replay::diff_json(&recorded, &replayed);
+    if differences.is_empty() {
+        eprintln!("The response is identical to the recorded response");
+        return Ok(());
     }
+
+    for difference in &differences {
+        println!("{difference}");
+    }
+    miette::bail!(
+        "the response differs from the recorded response in {} place(s)",
+        differences.len()
+    )
+}
+
+/// Generates the recipe and prints it followed by the discovered outputs, as
+/// two YAML documents.
+async fn generate_recipe<T: ProtocolInstantiator>(
+    factory: T,
+    args: GenerateRecipeArgs,
+) -> miette::Result<()> {
+    let project_model = read_json_arg(&args.project_model)?;
+    let project_model = match serde_json::from_value::<VersionedProjectModel>(project_model.clone())
+    {
+        Ok(project_model) => project_model,
+        Err(_) => serde_json::from_value::<ProjectModelV1>(project_model)
+            .into_diagnostic()
+            .wrap_err("failed to parse the project model")?
+            .into(),
+    };
+    let configuration = args.config.as_deref().map(read_json_arg).transpose()?;
+
+    let (protocol, _) = factory
+        .initialize(InitializeParams {
+            workspace_root: None,
+            source_dir: None,
+            manifest_path: args.manifest,
+            project_model: Some(project_model),
+            configuration,
+            target_configuration: None,
+            cache_directory: None,
+        })
+        .await?;
+
+    let result = protocol
+        .generate_recipe(GenerateRecipeParams {
+            host_platform: args.platform,
+            channels: args.channels.into_iter().map(ChannelUrl::from).collect(),
+            variant_configuration: None,
+            variant_files: Some(args.variant_files),
+        })
+        .await?;
+
+    print!("{}", result.recipe);
+    println!("---");
+    print!(
+        "{}",
+        serde_yaml::to_string(&result.outputs).into_diagnostic()?
+    );
+    Ok(())
+}
+
+/// Parses an argument that is either JSON or the path to a JSON file.
+fn read_json_arg(value: &str) -> miette::Result<serde_json::Value> {
+    let contents = if value.trim_start().starts_with(['{', '[']) {
+        value.to_string()
+    } else {
+        fs_err::read_to_string(Path::new(value)).into_diagnostic()?
+    };
+    serde_json::from_str(&contents)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to parse {value} as JSON"))
 }
 
 /// The entry point for the CLI which should be called from the backends implementation.
@@ -1,12 +1,12 @@
 use std::collections::BTreeMap;
 
-use miette::IntoDiagnostic;
+use miette::{Context, IntoDiagnostic};
 use pixi_build_types::PlatformAndVirtualPackages;
 use rattler_build::{
     NormalizedKey, metadata::PlatformWithVirtualPackages, recipe::variable::Variable,
     types::Directories,
 };
-use rattler_conda_types::ChannelUrl;
+use rattler_conda_types::{ChannelUrl, GenericVirtualPackage, PackageName, Platform, Version};
 use rattler_virtual_packages::VirtualPackageOverrides;
 use url::Url;
 
@@ -18,15 +18,12 @@ pub fn build_configuration(
     variant: BTreeMap<NormalizedKey, Variable>,
     directories: Directories,
 ) -> miette::Result<BuildConfigurationParams> {
-    let build_platform = build_platform.map(|p| PlatformWithVirtualPackages {
-        platform: p.platform,
-        virtual_packages: p.virtual_packages.unwrap_or_default(),
-    });
-
-    let host_platform = host_platform.map(|p| PlatformWithVirtualPackages {
-        platform: p.platform,
-        virtual_packages: p.virtual_packages.unwrap_or_default(),
-    });
+    let build_platform = build_platform
+        .map(platform_with_virtual_packages)
+        .transpose()?;
+    let host_platform = host_platform
+        .map(platform_with_virtual_packages)
+        .transpose()?;
 
     let (build_platform, host_platform) = match (build_platform, host_platform) {
         (Some(build_platform), Some(host_platform)) => (build_platform, host_platform),
@@ -55,6 +52,64 @@ pub fn build_configuration(
     Ok(params)
 }
 
+/// Returns the platform together with its virtual packages.
+///
+/// The virtual packages supplied by the frontend take precedence. If there are
+/// none, they are detected when the platform is the current platform. The
+/// virtual packages of other platforms cannot be detected.
+pub fn platform_with_virtual_packages(
+    platform: PlatformAndVirtualPackages,
+) -> miette::Result<PlatformWithVirtualPackages> {
+    if let Some(virtual_packages) = platform.virtual_packages {
+        return Ok(PlatformWithVirtualPackages {
+            platform: platform.platform,
+            virtual_packages,
+        });
+    }
+
+    if platform.platform == Platform::current() {
+        return PlatformWithVirtualPackages::detect(&VirtualPackageOverrides::from_env())
+            .into_diagnostic();
+    }
+
+    tracing::debug!(
+        "cannot detect the virtual packages of {}, assuming there are none",
+        platform.platform
+    );
+    Ok(PlatformWithVirtualPackages {
+        platform: platform.platform,
+        virtual_packages: Vec::new(),
+    })
+}
+
+/// Returns the virtual packages that the frontend requested through the
+/// variant, e.g. `__glibc: 2.17`, or `None` if it requested none so that they
+/// are detected instead.
+///
+/// A variant only carries the version of a virtual package, the build string
+/// is always `0`.
+pub fn requested_virtual_packages<'a>(
+    variant: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> miette::Result<Option<Vec<GenericVirtualPackage>>> {
+    let virtual_packages = variant
+        .into_iter()
+        .filter(|(name, _)| name.starts_with("__"))
+        .map(|(name, version)| {
+            Ok(GenericVirtualPackage {
+                name: name.parse::<PackageName>().into_diagnostic()?,
+                version: version
+                    .parse::<Version>()
+                    .into_diagnostic()
+                    .with_context(|| {
+                        format!("invalid version `{version}` of the virtual package `{name}`")
+                    })?,
+                build_string: String::from("0"),
+            })
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+    Ok((!virtual_packages.is_empty()).then_some(virtual_packages))
+}
+
 /// The parameters used to construct a BuildConfiguration
 #[derive(Debug)]
 pub struct BuildConfigurationParams {
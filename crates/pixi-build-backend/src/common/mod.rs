@@ -3,6 +3,9 @@ mod configuration;
 mod requirements;
 mod variants;
 
-pub use configuration::{BuildConfigurationParams, build_configuration};
+pub use configuration::{
+    BuildConfigurationParams, build_configuration, platform_with_virtual_packages,
+    requested_virtual_packages,
+};
 pub use requirements::{PackageRequirements, SourceRequirements, requirements};
 pub use variants::compute_variants;
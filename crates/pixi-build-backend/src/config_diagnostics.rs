@@ -0,0 +1,607 @@
+//! Diagnostics for errors in the backend configuration.
+//!
+//! The configuration is passed to the backend as JSON, so serde can tell that
+//! it is invalid but not where. To find the offending key, the configuration is
+//! validated against the JSON Schema of the backend, and the key is highlighted
+//! in the manifest the configuration was read from. Unknown keys come with a
+//! suggestion for a similar key, or with an explanation if the key belongs to
+//! another backend.
+
+use std::{fmt, ops::Range, path::Path, sync::LazyLock};
+
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::generated_recipe::BackendConfig;
+
+/// The checked in configuration schemas of the backends in this repository,
+/// used to explain keys that are passed to the wrong backend.
+const BACKEND_CONFIG_SCHEMAS: &[(&str, &str)] = &[
+    (
+        "pixi-build-cmake",
+        include_str!("../../../schema/config/pixi-build-cmake.json"),
+    ),
+    (
+        "pixi-build-mojo",
+        include_str!("../../../schema/config/pixi-build-mojo.json"),
+    ),
+    (
+        "pixi-build-python",
+        include_str!("../../../schema/config/pixi-build-python.json"),
+    ),
+    (
+        "pixi-build-rattler-build",
+        include_str!("../../../schema/config/pixi-build-rattler-build.json"),
+    ),
+    (
+        "pixi-build-rust",
+        include_str!("../../../schema/config/pixi-build-rust.json"),
+    ),
+];
+
+/// The configuration keys of the backends in this repository.
+static BACKEND_CONFIG_KEYS: LazyLock<Vec<(&str, Vec<String>)>> = LazyLock::new(|| {
+    BACKEND_CONFIG_SCHEMAS
+        .iter()
+        .map(|(backend, schema)| {
+            let schema: Value =
+                serde_json::from_str(schema).expect("the configuration schemas are valid JSON");
+            (*backend, SchemaWalker::new(&schema).config_keys())
+        })
+        .collect()
+});
+
+/// The tables in which the `[package.build]` table can be found in a
+/// manifest, for `pixi.toml` and `pyproject.toml` respectively.
+const BUILD_TABLES: &[&[&str]] = &[&["package", "build"], &["tool", "pixi", "package", "build"]];
+
+/// The minimal similarity for a key to be suggested for an unknown key.
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// The location of a configuration in the `[package.build]` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSection<'a> {
+    /// The `config` table.
+    Base,
+    /// The `target.<selector>.config` table.
+    Target(&'a str),
+}
+
+impl<'a> ConfigSection<'a> {
+    fn keys(self) -> Vec<&'a str> {
+        match self {
+            ConfigSection::Base => vec!["config"],
+            ConfigSection::Target(selector) => vec!["target", selector, "config"],
+        }
+    }
+}
+
+impl fmt::Display for ConfigSection<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.keys().join("."))
+    }
+}
+
+/// An error in the backend configuration.
+#[derive(Debug, Error, Diagnostic)]
+#[error("{message}")]
+pub struct ConfigError {
+    message: String,
+    #[source_code]
+    source_code: Option<NamedSource<String>>,
+    #[label("{label}")]
+    span: Option<SourceSpan>,
+    label: String,
+    #[help]
+    help: Option<String>,
+}
+
+/// A segment of the path to a value in the configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// The path to a value in the configuration, e.g. `config.bins[1].path`.
+struct KeyPath<'a> {
+    section: ConfigSection<'a>,
+    segments: &'a [Segment],
+}
+
+impl fmt::Display for KeyPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.section)?;
+        for segment in self.segments {
+            match segment {
+                Segment::Key(key) => write!(f, ".{key}")?,
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the configuration of a backend that was read from the
+/// `[package.build]` table of the manifest at `manifest_path`.
+pub fn parse_config<C: BackendConfig>(
+    config: &Value,
+    section: ConfigSection<'_>,
+    manifest_path: &Path,
+) -> Result<C, ConfigError> {
+    C::deserialize(config).map_err(|err| {
+        ConfigError::new(
+            config,
+            section,
+            &err.to_string(),
+            C::json_schema().as_ref(),
+            manifest_path,
+        )
+    })
+}
+
+impl ConfigError {
+    fn new(
+        config: &Value,
+        section: ConfigSection<'_>,
+        error: &str,
+        schema: Option<&Value>,
+        manifest_path: &Path,
+    ) -> Self {
+        let violation = schema.and_then(|schema| SchemaWalker::new(schema).validate(config));
+        let segments = violation
+            .as_ref()
+            .map_or(&[][..], |violation| violation.path.as_slice());
+
+        let manifest = Manifest::read(manifest_path);
+        let span = manifest
+            .as_ref()
+            .and_then(|manifest| manifest.span(section, segments))
+            .map(SourceSpan::from);
+        let source_code = manifest.map(|manifest| {
+            NamedSource::new(manifest_path.display().to_string(), manifest.contents)
+        });
+
+        let Some(violation) = violation else {
+            return Self {
+                message: format!("failed to parse `{section}`: {error}"),
+                source_code,
+                span,
+                label: String::from("in this configuration"),
+                help: None,
+            };
+        };
+
+        let key_path = KeyPath {
+            section,
+            segments: &violation.path,
+        };
+        match violation.kind {
+            ViolationKind::UnknownKey { key, expected } => Self {
+                message: format!("unknown key `{key_path}`"),
+                source_code,
+                span,
+                label: String::from("unknown key"),
+                help: unknown_key_help(&key, &expected, violation.path.len() == 1),
+            },
+            ViolationKind::InvalidType { expected } => Self {
+                message: format!(
+                    "invalid value for `{key_path}`, expected {}",
+                    expected.join(" or ")
+                ),
+                source_code,
+                span,
+                label: String::from("invalid value"),
+                help: None,
+            },
+        }
+    }
+}
+
+/// Explains an unknown key, preferring an explanation that the key belongs
+/// to another backend over a suggestion for a similar key.
+fn unknown_key_help(key: &str, expected: &[String], top_level: bool) -> Option<String> {
+    let other_backends = BACKEND_CONFIG_KEYS
+        .iter()
+        .filter(|(_, keys)| keys.iter().any(|candidate| candidate == key))
+        .map(|(backend, _)| format!("`{backend}`"))
+        .collect::<Vec<_>>();
+    if top_level && !other_backends.is_empty() {
+        return Some(format!(
+            "`{key}` is a configuration key of {}, which is not supported by this backend",
+            other_backends.join(", ")
+        ));
+    }
+
+    let suggestion = expected
+        .iter()
+        .map(|candidate| (strsim::jaro_winkler(key, candidate), candidate))
+        .filter(|(similarity, _)| *similarity >= SUGGESTION_THRESHOLD)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b));
+    if let Some((_, suggestion)) = suggestion {
+        return Some(format!("did you mean `{suggestion}`?"));
+    }
+
+    (!expected.is_empty()).then(|| {
+        format!(
+            "the supported keys are {}",
+            expected
+                .iter()
+                .map(|key| format!("`{key}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// A value in the configuration that does not match the schema.
+struct Violation {
+    path: Vec<Segment>,
+    kind: ViolationKind,
+}
+
+enum ViolationKind {
+    /// A key that is not a property of the object it is in.
+    UnknownKey { key: String, expected: Vec<String> },
+    /// A value that does not have one of the expected types.
+    InvalidType { expected: Vec<String> },
+}
+
+/// Walks a configuration along the JSON Schema of the backend.
+struct SchemaWalker<'a> {
+    root: &'a Value,
+}
+
+impl<'a> SchemaWalker<'a> {
+    /// The schema describes the `[package.build]` table, the configuration
+    /// itself is the `config` property.
+    fn new(root: &'a Value) -> Self {
+        Self { root }
+    }
+
+    /// The schema of the configuration.
+    fn config(&self) -> Option<&'a Value> {
+        self.root
+            .pointer("/properties/config")
+            .map(|schema| self.resolve(schema))
+    }
+
+    /// The top-level keys of the configuration.
+    fn config_keys(&self) -> Vec<String> {
+        self.config()
+            .and_then(|config| config.get("properties"))
+            .and_then(Value::as_object)
+            .map(|properties| properties.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the first value in the configuration that does not match the
+    /// schema.
+    fn validate(&self, config: &Value) -> Option<Violation> {
+        self.walk(self.config()?, config, &mut Vec::new())
+    }
+
+    /// Follows a `$ref` to a definition in the root schema.
+    fn resolve(&self, schema: &'a Value) -> &'a Value {
+        schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix('#'))
+            .and_then(|pointer| self.root.pointer(pointer))
+            .map_or(schema, |schema| self.resolve(schema))
+    }
+
+    fn walk(&self, schema: &'a Value, value: &Value, path: &mut Vec<Segment>) -> Option<Violation> {
+        let mut schema = self.resolve(schema);
+        if let Some(alternatives) = schema.get("anyOf").and_then(Value::as_array) {
+            let alternatives = alternatives
+                .iter()
+                .map(|alternative| self.resolve(alternative))
+                .collect::<Vec<_>>();
+            match alternatives
+                .iter()
+                .find(|alternative| matches_type(alternative, value))
+            {
+                Some(alternative) => schema = alternative,
+                None => {
+                    return Some(Violation {
+                        path: path.clone(),
+                        kind: ViolationKind::InvalidType {
+                            expected: alternatives.into_iter().flat_map(types).collect(),
+                        },
+                    });
+                }
+            }
+        }
+
+        if !matches_type(schema, value) {
+            return Some(Violation {
+                path: path.clone(),
+                kind: ViolationKind::InvalidType {
+                    expected: types(schema),
+                },
+            });
+        }
+
+        match value {
+            Value::Object(map) => {
+                let properties = schema.get("properties").and_then(Value::as_object);
+                for (key, value) in map {
+                    path.push(Segment::Key(key.clone()));
+                    let found = match (
+                        properties.and_then(|properties| properties.get(key)),
+                        schema.get("additionalProperties"),
+                    ) {
+                        (Some(property), _) => self.walk(property, value, path),
+                        (None, Some(Value::Bool(false))) => Some(Violation {
+                            path: path.clone(),
+                            kind: ViolationKind::UnknownKey {
+                                key: key.clone(),
+                                expected: properties
+                                    .map(|properties| properties.keys().cloned().collect())
+                                    .unwrap_or_default(),
+                            },
+                        }),
+                        (None, Some(additional @ Value::Object(_))) => {
+                            self.walk(additional, value, path)
+                        }
+                        (None, _) => None,
+                    };
+                    if found.is_some() {
+                        return found;
+                    }
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                let item_schema = schema.get("items")?;
+                for (index, item) in items.iter().enumerate() {
+                    path.push(Segment::Index(index));
+                    let found = self.walk(item_schema, item, path);
+                    if found.is_some() {
+                        return found;
+                    }
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+/// The types that a schema allows.
+fn types(schema: &Value) -> Vec<String> {
+    match schema.get("type") {
+        Some(Value::String(ty)) => vec![ty.clone()],
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns true if the value has one of the types that the schema allows.
+fn matches_type(schema: &Value, value: &Value) -> bool {
+    let types = types(schema);
+    types.is_empty()
+        || types.iter().any(|ty| match (ty.as_str(), value) {
+            ("null", Value::Null)
+            | ("boolean", Value::Bool(_))
+            | ("number", Value::Number(_))
+            | ("string", Value::String(_))
+            | ("array", Value::Array(_))
+            | ("object", Value::Object(_)) => true,
+            ("integer", Value::Number(number)) => number.is_i64() || number.is_u64(),
+            _ => false,
+        })
+}
+
+/// The manifest that the configuration was read from.
+struct Manifest {
+    contents: String,
+    document: toml_edit::Document<String>,
+}
+
+impl Manifest {
+    fn read(path: &Path) -> Option<Self> {
+        let contents = fs_err::read_to_string(path).ok()?;
+        let document = toml_edit::Document::parse(contents.clone()).ok()?;
+        Some(Self { contents, document })
+    }
+
+    /// Returns the span of the key (or array element) at `segments` in the
+    /// configuration, or of the configuration table itself.
+    fn span(&self, section: ConfigSection<'_>, segments: &[Segment]) -> Option<Range<usize>> {
+        let section_keys = section.keys();
+        BUILD_TABLES.iter().find_map(|build_table| {
+            let keys = build_table
+                .iter()
+                .chain(section_keys.iter())
+                .map(|key| Segment::Key(key.to_string()))
+                .chain(segments.iter().cloned());
+
+            let mut item = self.document.as_item();
+            let mut span = None;
+            for segment in keys {
+                match segment {
+                    Segment::Key(key) => {
+                        let (key, value) = item.as_table_like()?.get_key_value(&key)?;
+                        span = key.span();
+                        item = value;
+                    }
+                    Segment::Index(index) => {
+                        item = item.get(index)?;
+                        span = item.span();
+                    }
+                }
+            }
+            span
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn schema() -> Value {
+        json!({
+            "properties": {
+                "config": { "$ref": "#/$defs/Config" },
+            },
+            "$defs": {
+                "Config": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": {
+                        "extra-args": { "type": "array", "items": { "type": "string" } },
+                        "bins": {
+                            "type": ["array", "null"],
+                            "items": { "$ref": "#/$defs/Bin" },
+                        },
+                    },
+                },
+                "Bin": {
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": ["string", "null"] },
+                    },
+                },
+            },
+        })
+    }
+
+    fn spanned(error: &ConfigError) -> &str {
+        let span = error.span.unwrap();
+        &error.source_code.as_ref().unwrap().inner()[span.offset()..span.offset() + span.len()]
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("pixi.toml");
+        fs_err::write(
+            &manifest_path,
+            r#"[package]
+name = "foo"
+
+[package.build.config]
+bins = [{ path = "main.mojo" }, { path = 1 }]
+
+[package.build.target.linux-64.config]
+extra-arg = ["-DFOO=ON"]
+ignore-cargo-manifest = true
+"#,
+        )
+        .unwrap();
+
+        let config = json!({ "bins": [{ "path": "main.mojo" }, { "path": 1 }] });
+        let error = ConfigError::new(
+            &config,
+            ConfigSection::Base,
+            "invalid type: integer `1`, expected a string",
+            Some(&schema()),
+            &manifest_path,
+        );
+        assert_eq!(
+            error.message,
+            "invalid value for `config.bins[1].path`, expected string or null"
+        );
+        assert_eq!(spanned(&error), "path");
+
+        let config = json!({ "extra-arg": ["-DFOO=ON"] });
+        let error = ConfigError::new(
+            &config,
+            ConfigSection::Target("linux-64"),
+            "unknown field",
+            Some(&schema()),
+            &manifest_path,
+        );
+        assert_eq!(
+            error.message,
+            "unknown key `target.linux-64.config.extra-arg`"
+        );
+        assert_eq!(spanned(&error), "extra-arg");
+        assert_eq!(error.help.unwrap(), "did you mean `extra-args`?");
+
+        // Keys of other backends are explained.
+        let config = json!({ "ignore-cargo-manifest": true });
+        let error = ConfigError::new(
+            &config,
+            ConfigSection::Target("linux-64"),
+            "unknown field",
+            Some(&schema()),
+            &manifest_path,
+        );
+        assert_eq!(
+            error.message,
+            "unknown key `target.linux-64.config.ignore-cargo-manifest`"
+        );
+        assert_eq!(spanned(&error), "ignore-cargo-manifest");
+        assert_eq!(
+            error.help.unwrap(),
+            "`ignore-cargo-manifest` is a configuration key of `pixi-build-rust`, which is not supported by this backend"
+        );
+
+        // Errors that the schema does not explain point at the configuration.
+        let config = json!({ "extra-args": ["-DFOO=ON"] });
+        let error = ConfigError::new(
+            &config,
+            ConfigSection::Base,
+            "something else",
+            Some(&schema()),
+            &manifest_path,
+        );
+        assert_eq!(error.message, "failed to parse `config`: something else");
+        assert_eq!(spanned(&error), "config");
+    }
+
+    #[test]
+    fn test_config_errors_in_pyproject() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("pyproject.toml");
+        fs_err::write(
+            &manifest_path,
+            r#"[project]
+name = "foo"
+
+[tool.pixi.package.build]
+config = { extra-args = [1] }
+"#,
+        )
+        .unwrap();
+
+        let config = json!({ "extra-args": [1] });
+        let error = ConfigError::new(
+            &config,
+            ConfigSection::Base,
+            "invalid type: integer `1`, expected a string",
+            Some(&schema()),
+            &manifest_path,
+        );
+        assert_eq!(
+            error.message,
+            "invalid value for `config.extra-args[0]`, expected string"
+        );
+        assert_eq!(spanned(&error), "1");
+    }
+
+    #[test]
+    fn test_backend_config_keys() {
+        let rust_keys = BACKEND_CONFIG_KEYS
+            .iter()
+            .find(|(backend, _)| *backend == "pixi-build-rust")
+            .map(|(_, keys)| keys)
+            .unwrap();
+        assert!(rust_keys.iter().any(|key| key == "ignore-cargo-manifest"));
+        assert_eq!(BACKEND_CONFIG_KEYS.len(), BACKEND_CONFIG_SCHEMAS.len());
+    }
+}
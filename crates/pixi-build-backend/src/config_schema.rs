@@ -0,0 +1,88 @@
+//! JSON Schemas for the backend configurations.
+//!
+//! The schema of a backend describes the `config` and the
+//! `target.<selector>.config` tables of the `[package.build]` section of the
+//! manifest, so that editors can validate them and offer completion. The
+//! schemas are generated from the configuration types and are checked in under
+//! `schema/config`.
+
+#[cfg(feature = "test-utils")]
+use std::path::Path;
+
+use schemars::{JsonSchema, generate::SchemaSettings, json_schema};
+
+/// The keys of the configurations that cannot have a target specific value,
+/// because `merge_with_target_config` rejects them.
+const BASE_ONLY_KEYS: &[&str] = &["debug-dir"];
+
+/// Generates the JSON Schema for the configuration `C` of a backend.
+pub fn config_schema<C: JsonSchema>() -> serde_json::Value {
+    let mut generator = SchemaSettings::draft2020_12().into_generator();
+    let config = generator.subschema_for::<C>();
+    let mut definitions = generator.take_definitions(true);
+
+    // The target specific configuration is the configuration without the keys
+    // that can only be set in the base configuration.
+    let name = C::schema_name();
+    let target_name = format!("{name}Target");
+    let mut target_config = definitions
+        .get(name.as_ref())
+        .cloned()
+        .expect("the configuration of a backend must be a definition");
+    if let Some(properties) = target_config
+        .get_mut("properties")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        for key in BASE_ONLY_KEYS {
+            properties.remove(*key);
+        }
+    }
+    definitions.insert(target_name.clone(), target_config);
+
+    let mut schema = json_schema!({
+        "title": name,
+        "description": "The configuration of the backend in the `[package.build]` table of the manifest.",
+        "type": "object",
+        "properties": {
+            "config": config,
+            "target": {
+                "description": "Target-specific configurations, which are merged into the base configuration on matching platforms.",
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "properties": {
+                        "config": {
+                            "$ref": format!("#/$defs/{target_name}"),
+                        },
+                    },
+                },
+            },
+        },
+    });
+
+    if let Some(meta_schema) = generator.settings().meta_schema.as_deref() {
+        schema.insert("$schema".into(), meta_schema.into());
+    }
+    schema.insert("$defs".into(), definitions.into());
+    schema.to_value()
+}
+
+/// Asserts that the checked in schema of a backend matches the schema that is
+/// generated from its configuration type. The backends call this from their
+/// tests, so that a change to a configuration type that is not reflected in
+/// the checked in schema fails the tests.
+#[cfg(feature = "test-utils")]
+pub fn assert_schema_up_to_date<C: JsonSchema>(backend: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../schema/config")
+        .join(format!("{backend}.json"));
+    let checked_in: serde_json::Value = fs_err::read_to_string(&path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    assert!(
+        checked_in == config_schema::<C>(),
+        "the configuration schema of {backend} in {} is out of date, regenerate it with `pixi run generate-config-schemas`",
+        path.display()
+    );
+}
@@ -1 +1,12 @@
 pub const DEBUG_OUTPUT_DIR: &str = "debug";
+
+// The files in the debug directory that record the requests and their
+// responses. These are used to replay a request.
+pub const INITIALIZE_PARAMS_FILE: &str = "initialize_params.json";
+pub const PROJECT_MODEL_FILE: &str = "project_model.json";
+pub const CONDA_OUTPUTS_PARAMS_FILE: &str = "conda_outputs_params.json";
+pub const CONDA_OUTPUTS_RESPONSE_FILE: &str = "conda_outputs_response.json";
+pub const CONDA_OUTPUTS_ERROR_FILE: &str = "conda_outputs_error.json";
+pub const CONDA_BUILD_V1_PARAMS_FILE: &str = "conda_build_v1_params.json";
+pub const CONDA_BUILD_V1_RESPONSE_FILE: &str = "conda_build_v1_response.json";
+pub const CONDA_BUILD_V1_ERROR_FILE: &str = "conda_build_v1_error.json";
@@ -5,12 +5,15 @@ use std::{
 
 use miette::{Context, Diagnostic, IntoDiagnostic};
 use pixi_build_types as pbt;
-use pixi_build_types::{BinaryPackageSpecV1, NamedSpecV1};
+use pixi_build_types::{
+    BinaryPackageSpecV1, NamedSpecV1,
+    procedures::conda_build_v1::{CondaBuildV1Dependency, CondaBuildV1Prefix},
+};
 use rattler_build::{
     NormalizedKey,
     recipe::{parser::Dependency, variable::Variable},
     render::{
-        pin::PinError,
+        pin::{Pin, PinError},
         resolved_dependencies::{
             DependencyInfo, PinCompatibleDependency, PinSubpackageDependency, ResolveError,
             SourceDependency, VariantDependency,
@@ -20,6 +23,7 @@ use rattler_build::{
 };
 use rattler_conda_types::{
     MatchSpec, NamelessMatchSpec, PackageName, PackageRecord, ParseStrictness::Strict,
+    ParseVersionError, Version,
 };
 use thiserror::Error;
 
@@ -149,6 +153,23 @@ pub enum ConvertDependencyError {
 
     #[error("could not apply pin: {0}")]
     PinApplyError(PinError),
+
+    #[error("could not parse version {1} of variant key {0} to apply pin_compatible: {2}")]
+    PinCompatibleVersionParseError(String, String, ParseVersionError),
+
+    #[error(
+        "could not apply pin_compatible: the build string of {} in the host environment is only known when the package is built",
+        .0.as_source()
+    )]
+    #[diagnostic(help("use `exact=false` to pin the version of the package"))]
+    PinCompatibleExact(PackageName),
+
+    #[error(
+        "could not apply pin_compatible: {} is not part of the variant",
+        .0.as_source()
+    )]
+    #[diagnostic(help("add the package to the build variants to pin its version"))]
+    PinCompatibleNotInVariant(PackageName),
 }
 
 fn convert_nameless_matchspec(spec: NamelessMatchSpec) -> pbt::BinaryPackageSpecV1 {
@@ -222,6 +243,46 @@ fn apply_variant_and_convert(
     }))
 }
 
+/// Resolves a `pin_compatible` dependency.
+///
+/// The pin is applied to the package in the host environment. The host
+/// environment is not known yet when the outputs are determined, in that case
+/// the pin is applied to the version of the package in the variant instead.
+/// Pins that cannot be resolved either way are an error, leaving the
+/// dependency unpinned would report a looser requirement than the built
+/// package has.
+fn resolve_pin_compatible(
+    pin: &Pin,
+    host_records: &HashMap<PackageName, PackageRecord>,
+    variant: &BTreeMap<NormalizedKey, Variable>,
+) -> Result<MatchSpec, ConvertDependencyError> {
+    if let Some(record) = host_records.get(&pin.name) {
+        return pin
+            .apply(&record.version, &record.build)
+            .map_err(ConvertDependencyError::PinApplyError);
+    }
+
+    if pin.args.exact {
+        return Err(ConvertDependencyError::PinCompatibleExact(pin.name.clone()));
+    }
+
+    let Some(version) = variant.get(&(&pin.name).into()).map(Variable::to_string) else {
+        return Err(ConvertDependencyError::PinCompatibleNotInVariant(
+            pin.name.clone(),
+        ));
+    };
+    let version = Version::from_str(&version).map_err(|e| {
+        ConvertDependencyError::PinCompatibleVersionParseError(
+            pin.name.as_normalized().to_string(),
+            version.clone(),
+            e,
+        )
+    })?;
+
+    pin.apply(&version, "")
+        .map_err(ConvertDependencyError::PinApplyError)
+}
+
 fn convert_dependency(
     dependency: Dependency,
     variant: &BTreeMap<NormalizedKey, Variable>,
@@ -261,7 +322,10 @@ fn convert_dependency(
                 .apply(&subpackage.version, &subpackage.build_string)
                 .map_err(ConvertDependencyError::PinApplyError)?
         }
-        _ => todo!("Handle other dependency types"),
+        // The host environment is not known when the outputs are determined.
+        Dependency::PinCompatible(pin) => {
+            resolve_pin_compatible(pin.pin_value(), &HashMap::new(), variant)?
+        }
     };
 
     let (Some(name), spec) = match_spec.into_nameless() else {
@@ -306,7 +370,10 @@ fn convert_binary_dependency(
                 .apply(&subpackage.version, &subpackage.build_string)
                 .map_err(ConvertDependencyError::PinApplyError)?
         }
-        _ => todo!("Handle other dependency types"),
+        // The host environment is not known when the outputs are determined.
+        Dependency::PinCompatible(pin) => {
+            resolve_pin_compatible(pin.pin_value(), &HashMap::new(), variant)?
+        }
     };
 
     // Apply a variant if it is applicable.
@@ -324,6 +391,71 @@ fn convert_binary_dependency(
     })
 }
 
+/// Resolves the `pin_compatible` dependencies against the packages in the host
+/// environment or the variant, see [`resolve_pin_compatible`]. This is used for
+/// the run requirements, to which the variant is otherwise not applied.
+pub fn resolve_pin_compatible_dependencies(
+    dependencies: Vec<Dependency>,
+    host_records: &HashMap<PackageName, PackageRecord>,
+    variant: &BTreeMap<NormalizedKey, Variable>,
+) -> Result<Vec<Dependency>, ConvertDependencyError> {
+    dependencies
+        .into_iter()
+        .map(|dependency| match dependency {
+            Dependency::PinCompatible(pin) => {
+                resolve_pin_compatible(pin.pin_value(), host_records, variant).map(Dependency::Spec)
+            }
+            dependency => Ok(dependency),
+        })
+        .collect()
+}
+
+/// Pins the `pin_compatible` run dependencies of a recipe to the packages in
+/// the host prefix of a build.
+///
+/// The frontend sends the run dependencies that were reported when the outputs
+/// were determined, at which point the pins could only be resolved against the
+/// variant. The dependencies that stem from a `pin_compatible` of a package in
+/// the host prefix are replaced.
+pub fn pin_compatible_to_host_prefix(
+    requirements: &[Dependency],
+    host_prefix: Option<&CondaBuildV1Prefix>,
+    dependencies: Option<Vec<CondaBuildV1Dependency>>,
+) -> Result<Option<Vec<CondaBuildV1Dependency>>, ConvertDependencyError> {
+    let Some(host_prefix) = host_prefix else {
+        return Ok(dependencies);
+    };
+    let Some(mut dependencies) = dependencies else {
+        return Ok(None);
+    };
+    let host_records: HashMap<PackageName, PackageRecord> = host_prefix
+        .packages
+        .iter()
+        .map(|package| {
+            let record = &package.repodata_record.package_record;
+            (record.name.clone(), record.clone())
+        })
+        .collect();
+
+    for requirement in requirements {
+        let Dependency::PinCompatible(pin) = requirement else {
+            continue;
+        };
+        let pin = pin.pin_value();
+        if !host_records.contains_key(&pin.name) {
+            continue;
+        }
+        let spec = resolve_pin_compatible(pin, &host_records, &BTreeMap::new())?;
+        for dependency in &mut dependencies {
+            if dependency.source.is_none() && dependency.spec.name.as_ref() == Some(&pin.name) {
+                dependency.spec = spec.clone();
+            }
+        }
+    }
+
+    Ok(Some(dependencies))
+}
+
 pub fn convert_dependencies(
     dependencies: Vec<Dependency>,
     variant: &BTreeMap<NormalizedKey, Variable>,
@@ -347,6 +479,25 @@ pub fn convert_binary_dependencies(
         .collect()
 }
 
+/// Converts constraints that are not part of the recipe, e.g. the build and
+/// host constraints of a [`crate::generated_recipe::GeneratedRecipe`].
+pub fn convert_constraints(
+    constraints: &[MatchSpec],
+) -> Result<Vec<pbt::NamedSpecV1<pbt::BinaryPackageSpecV1>>, ConvertDependencyError> {
+    constraints
+        .iter()
+        .map(|constraint| {
+            let (Some(name), spec) = constraint.clone().into_nameless() else {
+                return Err(ConvertDependencyError::MissingName);
+            };
+            Ok(pbt::NamedSpecV1 {
+                name: name.as_source().to_owned(),
+                spec: convert_nameless_matchspec(spec),
+            })
+        })
+        .collect()
+}
+
 /// Apply a variant to a dependency list and resolve all pin_subpackage and
 /// compiler dependencies
 pub fn apply_variant(
@@ -423,3 +574,63 @@ pub fn apply_variant(
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use rattler_build::render::pin::PinArgs;
+
+    use super::*;
+
+    fn pin(exact: bool) -> Pin {
+        Pin {
+            name: PackageName::from_str("libfoo").unwrap(),
+            args: PinArgs {
+                exact,
+                ..PinArgs::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_pin_compatible_prefers_the_host_environment() {
+        let record = PackageRecord::new(
+            PackageName::from_str("libfoo").unwrap(),
+            Version::from_str("1.2.3").unwrap(),
+            "h123_0".to_string(),
+        );
+        let host_records = HashMap::from([(record.name.clone(), record)]);
+        let variant = BTreeMap::from([(
+            NormalizedKey::from("libfoo"),
+            Variable::from("3.0".to_string()),
+        )]);
+
+        let spec = resolve_pin_compatible(&pin(false), &host_records, &variant).unwrap();
+        let version_spec = spec.version.unwrap();
+        assert!(version_spec.matches(&Version::from_str("1.2.3").unwrap()));
+        assert!(!version_spec.matches(&Version::from_str("3.0").unwrap()));
+
+        // The build string is known from the host environment.
+        let spec = resolve_pin_compatible(&pin(true), &host_records, &variant).unwrap();
+        assert!(spec.to_string().contains("h123_0"), "{spec}");
+    }
+
+    #[test]
+    fn test_pin_compatible_falls_back_to_the_variant() {
+        let variant = BTreeMap::from([(
+            NormalizedKey::from("libfoo"),
+            Variable::from("3.0".to_string()),
+        )]);
+
+        let spec = resolve_pin_compatible(&pin(false), &HashMap::new(), &variant).unwrap();
+        assert!(
+            spec.version
+                .unwrap()
+                .matches(&Version::from_str("3.0").unwrap())
+        );
+
+        assert!(matches!(
+            resolve_pin_compatible(&pin(true), &HashMap::new(), &variant),
+            Err(ConvertDependencyError::PinCompatibleExact(_))
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+//! Forwarding environment variables of the host to the build script.
+//!
+//! The `env-passthrough` configuration lists the names of host environment
+//! variables, or glob patterns like `CCACHE_*`, that are made available to the
+//! build script. They are added as secrets of the script, so the generated
+//! recipe only contains their names and rattler-build masks their values in
+//! the build output.
+
+use globset::{Glob, GlobSetBuilder};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+
+use crate::generated_recipe::{BackendConfig, GeneratedRecipe};
+
+/// Returns the names of the host environment variables that match one of the
+/// patterns. Variables that are set explicitly in the `env` of the script are
+/// not forwarded, because the explicit value takes precedence.
+pub fn passthrough_secrets(
+    patterns: &[String],
+    script_env: &IndexMap<String, String>,
+    host_env_names: impl IntoIterator<Item = String>,
+) -> miette::Result<Vec<String>> {
+    if patterns.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).into_diagnostic()?);
+    }
+    let globs = builder.build().into_diagnostic()?;
+
+    Ok(host_env_names
+        .into_iter()
+        .filter(|name| globs.is_match(name) && !script_env.contains_key(name))
+        .sorted()
+        .dedup()
+        .collect())
+}
+
+/// Adds the host environment variables that are selected by the
+/// `env-passthrough` configuration as secrets to the build script of the
+/// recipe.
+pub fn add_passthrough_secrets<C: BackendConfig>(
+    config: &C,
+    generated_recipe: &mut GeneratedRecipe,
+) -> miette::Result<()> {
+    let script = &mut generated_recipe.recipe.build.script;
+    let secrets = passthrough_secrets(
+        config.env_passthrough(),
+        &script.env,
+        std::env::vars_os().filter_map(|(name, _)| name.into_string().ok()),
+    )?;
+    for secret in secrets {
+        if !script.secrets.contains(&secret) {
+            script.secrets.push(secret);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passthrough_secrets() {
+        let patterns = ["GITHUB_TOKEN".to_string(), "CCACHE_*".to_string()];
+        let script_env = IndexMap::from([("CCACHE_DIR".to_string(), "/cache".to_string())]);
+        let host_env_names = [
+            "PATH",
+            "CCACHE_MAXSIZE",
+            "GITHUB_TOKEN",
+            "CCACHE_DIR",
+            "GITHUB_TOKEN_2",
+        ]
+        .map(String::from);
+
+        assert_eq!(
+            passthrough_secrets(&patterns, &script_env, host_env_names).unwrap(),
+            ["CCACHE_MAXSIZE", "GITHUB_TOKEN"]
+        );
+    }
+}
@@ -1,7 +1,8 @@
+use globset::{GlobBuilder, GlobSetBuilder};
 use miette::Diagnostic;
 use pixi_build_types::ProjectModelV1;
 use rattler_build::{NormalizedKey, recipe::variable::Variable};
-use rattler_conda_types::{ChannelUrl, Platform, Version};
+use rattler_conda_types::{ChannelUrl, MatchSpec, PackageUrl, Platform, Version};
 use recipe_stage0::recipe::{About, IntermediateRecipe, Package, Value};
 use serde::de::DeserializeOwned;
 use std::collections::HashSet;
@@ -13,7 +14,10 @@ use std::{
 };
 use thiserror::Error;
 
-use crate::specs_conversion::from_targets_v1_to_conditional_requirements;
+use crate::{
+    metadata_chain::MetadataProviderChain, packaging::PackagingOptions, sandbox::SandboxConfig,
+    specs_conversion::from_targets_v1_to_conditional_requirements,
+};
 
 #[derive(Debug, Clone, Default)]
 pub struct PythonParams {
@@ -99,6 +103,42 @@ pub trait BackendConfig: DeserializeOwned + Clone {
     /// Merge this configuration with a target-specific configuration.
     /// Target-specific values typically override base values.
     fn merge_with_target_config(&self, target_config: &Self) -> miette::Result<Self>;
+
+    /// Whether the tests of the package should be executed after it has been
+    /// built.
+    fn run_tests(&self) -> bool {
+        false
+    }
+
+    /// How the built package is archived.
+    fn packaging(&self) -> PackagingOptions {
+        PackagingOptions::default()
+    }
+
+    /// The sandbox the build script runs in, if the build should be
+    /// sandboxed.
+    fn sandbox(&self) -> Option<&SandboxConfig> {
+        None
+    }
+
+    /// The names of, or glob patterns matching, the host environment variables
+    /// that are forwarded to the build script as secrets.
+    fn env_passthrough(&self) -> &[String] {
+        &[]
+    }
+
+    /// The recipe overlay that is merged into the generated recipe, relative
+    /// to the directory of the manifest. See [`crate::overlay`].
+    fn overlay(&self) -> Option<&Path> {
+        None
+    }
+
+    /// The JSON Schema of the configuration, which is printed by the
+    /// `config-schema` subcommand. Use [`crate::config_schema::config_schema`]
+    /// to generate it from the configuration type.
+    fn json_schema() -> Option<serde_json::Value> {
+        None
+    }
 }
 
 #[derive(Debug, Error, Diagnostic)]
@@ -111,11 +151,38 @@ pub enum GenerateRecipeError<MetadataProviderError: Diagnostic + 'static> {
     MetadataProviderError(String, #[source] MetadataProviderError),
 }
 
+/// The source of metadata values that are defined in the project manifest.
+pub const MANIFEST_METADATA_SOURCE: &str = "manifest";
+
+/// The source of metadata values whose provider does not report a source.
+pub const UNKNOWN_METADATA_SOURCE: &str = "metadata provider";
+
+/// The source of license files that were discovered in the manifest root.
+pub const DISCOVERED_LICENSE_FILES_SOURCE: &str = "license files in the manifest root";
+
+/// The globs of the license files that are discovered in the manifest root if
+/// neither the manifest nor the metadata provider specify a license file.
+pub const LICENSE_FILE_GLOBS: [&str; 3] = ["LICENSE*", "COPYING*", "NOTICE*"];
+
 #[derive(Default, Clone)]
 pub struct GeneratedRecipe {
     pub recipe: IntermediateRecipe,
     pub metadata_input_globs: BTreeSet<String>,
     pub build_input_globs: BTreeSet<String>,
+    /// The package URLs (purls) that identify the packages the recipe was
+    /// generated from, e.g. `pkg:pypi/requests@2.32.0`, keyed by the name of
+    /// the output they identify.
+    pub purls: BTreeMap<String, BTreeSet<PackageUrl>>,
+    /// Constraints on the packages in the build environment of an output,
+    /// keyed by the name of the output. Recipes cannot express these, so they
+    /// are only reported to the frontend, which solves the environment.
+    pub build_constraints: BTreeMap<String, Vec<MatchSpec>>,
+    /// Constraints on the packages in the host environment of an output,
+    /// keyed by the name of the output.
+    pub host_constraints: BTreeMap<String, Vec<MatchSpec>>,
+    /// Where each value of the package metadata was taken from, keyed by the
+    /// name of the field, e.g. `pyproject.toml` for the `name`.
+    pub metadata_provenance: BTreeMap<String, String>,
 }
 
 impl GeneratedRecipe {
@@ -126,6 +193,14 @@ impl GeneratedRecipe {
         model: ProjectModelV1,
         provider: &mut M,
     ) -> Result<Self, GenerateRecipeError<M::Error>> {
+        let mut provenance = BTreeMap::new();
+        let mut record = |field: &str, source: Option<String>| {
+            provenance.insert(
+                field.to_string(),
+                source.unwrap_or_else(|| String::from(UNKNOWN_METADATA_SOURCE)),
+            );
+        };
+
         // If the name is not defined in the model, we try to get it from the provider.
         // If the provider cannot provide a name, we return an error.
         let name = match model.name {
@@ -133,26 +208,40 @@ impl GeneratedRecipe {
                 if name.trim().is_empty() {
                     return Err(GenerateRecipeError::NoNameDefined);
                 } else {
+                    record("name", Some(String::from(MANIFEST_METADATA_SOURCE)));
                     name
                 }
             }
-            None => provider
-                .name()
-                .map_err(|e| GenerateRecipeError::MetadataProviderError(String::from("name"), e))?
-                .ok_or(GenerateRecipeError::NoNameDefined)?,
+            None => {
+                let name = provider
+                    .name()
+                    .map_err(|e| {
+                        GenerateRecipeError::MetadataProviderError(String::from("name"), e)
+                    })?
+                    .ok_or(GenerateRecipeError::NoNameDefined)?;
+                record("name", provider.value_source("name"));
+                name
+            }
         };
 
         // If the version is not defined in the model, we try to get it from the
         // provider. If the provider cannot provide a version, we return an
         // error.
         let version = match model.version {
-            Some(v) => v,
-            None => provider
-                .version()
-                .map_err(|e| {
-                    GenerateRecipeError::MetadataProviderError(String::from("version"), e)
-                })?
-                .ok_or(GenerateRecipeError::NoVersionDefined)?,
+            Some(v) => {
+                record("version", Some(String::from(MANIFEST_METADATA_SOURCE)));
+                v
+            }
+            None => {
+                let version = provider
+                    .version()
+                    .map_err(|e| {
+                        GenerateRecipeError::MetadataProviderError(String::from("version"), e)
+                    })?
+                    .ok_or(GenerateRecipeError::NoVersionDefined)?;
+                record("version", provider.value_source("version"));
+                version
+            }
         };
 
         let package = Package {
@@ -164,40 +253,56 @@ impl GeneratedRecipe {
             from_targets_v1_to_conditional_requirements(&model.targets.unwrap_or_default());
 
         macro_rules! derive_value {
-            ($ident:ident) => {
-                match model.$ident {
-                    Some(v) => Some(v.to_string()),
-                    None => provider.$ident().map_err(|e| {
-                        GenerateRecipeError::MetadataProviderError(
-                            String::from(stringify!($ident)),
-                            e,
-                        )
-                    })?,
+            ($ident:ident, $field:literal, $model_value:expr) => {
+                match $model_value {
+                    Some(v) => {
+                        record($field, Some(String::from(MANIFEST_METADATA_SOURCE)));
+                        Some(v)
+                    }
+                    None => {
+                        let value = provider.$ident().map_err(|e| {
+                            GenerateRecipeError::MetadataProviderError(String::from($field), e)
+                        })?;
+                        if value.is_some() {
+                            record($field, provider.value_source($field));
+                        }
+                        value
+                    }
                 }
             };
         }
 
         let about = About {
-            homepage: derive_value!(homepage).map(Value::Concrete),
-            license: derive_value!(license).map(Value::Concrete),
-            description: derive_value!(description).map(Value::Concrete),
-            documentation: derive_value!(documentation).map(Value::Concrete),
-            repository: derive_value!(repository).map(Value::Concrete),
-            license_file: match model.license_file {
-                Some(v) => Some(Value::Concrete(v.display().to_string())),
-                None => provider
-                    .license_file()
-                    .map_err(|e| {
-                        GenerateRecipeError::MetadataProviderError(String::from("license-file"), e)
-                    })?
-                    .map(Value::Concrete),
-            },
-            summary: provider
-                .summary()
-                .map_err(|e| {
-                    GenerateRecipeError::MetadataProviderError(String::from("summary"), e)
-                })?
+            homepage: derive_value!(homepage, "homepage", model.homepage.map(|v| v.to_string()))
+                .map(Value::Concrete),
+            license: derive_value!(license, "license", model.license.map(|v| v.to_string()))
                 .map(Value::Concrete),
+            description: derive_value!(
+                description,
+                "description",
+                model.description.map(|v| v.to_string())
+            )
+            .map(Value::Concrete),
+            documentation: derive_value!(
+                documentation,
+                "documentation",
+                model.documentation.map(|v| v.to_string())
+            )
+            .map(Value::Concrete),
+            repository: derive_value!(
+                repository,
+                "repository",
+                model.repository.map(|v| v.to_string())
+            )
+            .map(Value::Concrete),
+            license_file: derive_value!(
+                license_file,
+                "license-file",
+                model.license_file.map(|v| v.display().to_string())
+            )
+            .map(Value::Concrete),
+            summary: derive_value!(summary, "summary", None::<String>).map(Value::Concrete),
+            ..Default::default()
         };
 
         let ir = IntermediateRecipe {
@@ -209,10 +314,93 @@ impl GeneratedRecipe {
 
         Ok(GeneratedRecipe {
             recipe: ir,
+            metadata_provenance: provenance,
             // TODO: extract real input globs from the model
             ..Default::default()
         })
     }
+
+    /// Creates a new [`GeneratedRecipe`] like [`Self::from_model`], for the
+    /// package in `manifest_root`.
+    ///
+    /// If neither the model nor the provider specify a license file, the
+    /// files in `manifest_root` that match one of the [`LICENSE_FILE_GLOBS`],
+    /// ignoring case, are used.
+    pub fn from_model_with_manifest_root<M: MetadataProvider>(
+        model: ProjectModelV1,
+        provider: &mut M,
+        manifest_root: &Path,
+    ) -> Result<Self, GenerateRecipeError<M::Error>> {
+        let mut generated_recipe = Self::from_model(model, provider)?;
+        let about = generated_recipe
+            .recipe
+            .about
+            .get_or_insert_with(Default::default);
+        if about.license_file.is_some() {
+            return Ok(generated_recipe);
+        }
+
+        let mut license_files = discover_license_files(manifest_root)
+            .into_iter()
+            .map(Value::Concrete);
+        about.license_file = license_files.next();
+        about.additional_license_files = license_files.collect();
+        if about.license_file.is_some() {
+            generated_recipe.metadata_provenance.insert(
+                String::from("license-file"),
+                String::from(DISCOVERED_LICENSE_FILES_SOURCE),
+            );
+        }
+
+        // The globs are part of the inputs even if no license file was found,
+        // so that adding one later on invalidates the metadata.
+        generated_recipe
+            .metadata_input_globs
+            .extend(LICENSE_FILE_GLOBS.map(case_insensitive_glob));
+        Ok(generated_recipe)
+    }
+}
+
+/// Returns a glob that matches the same paths as `glob`, ignoring the case of
+/// its letters, e.g. `[lL][iI][cC][eE][nN][sS][eE]*` for `LICENSE*`.
+fn case_insensitive_glob(glob: &str) -> String {
+    glob.chars()
+        .map(|c| {
+            if c.is_ascii_alphabetic() {
+                format!("[{}{}]", c.to_ascii_lowercase(), c.to_ascii_uppercase())
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Returns the names of the files in `manifest_root` that match one of the
+/// [`LICENSE_FILE_GLOBS`], ignoring case, sorted by name.
+fn discover_license_files(manifest_root: &Path) -> Vec<String> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in LICENSE_FILE_GLOBS {
+        builder.add(
+            GlobBuilder::new(glob)
+                .case_insensitive(true)
+                .build()
+                .expect("the license file globs are valid"),
+        );
+    }
+    let Ok(globs) = builder.build() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs_err::read_dir(manifest_root) else {
+        return Vec::new();
+    };
+    let mut license_files: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| globs.is_match(name))
+        .collect();
+    license_files.sort();
+    license_files
 }
 
 #[derive(Debug, Error, Diagnostic)]
@@ -257,6 +445,66 @@ pub trait MetadataProvider {
     fn repository(&mut self) -> Result<Option<String>, Self::Error> {
         Ok(None)
     }
+
+    /// Returns the set of globs that match the files that influence the
+    /// metadata provided by this provider.
+    fn input_globs(&self) -> BTreeSet<String> {
+        BTreeSet::new()
+    }
+
+    /// Describes where the value of `field` that was returned by this provider
+    /// was read from, e.g. `pyproject.toml`. This is recorded in the debug
+    /// directory so users can see which file each value came from.
+    fn value_source(&self, _field: &str) -> Option<String> {
+        None
+    }
+
+    /// Returns a provider that queries `other` for every value that this
+    /// provider does not provide.
+    fn or<O: MetadataProvider>(self, other: O) -> MetadataProviderChain<Self, O>
+    where
+        Self: Sized,
+    {
+        MetadataProviderChain::new(self, other)
+    }
+}
+
+impl<T: MetadataProvider + ?Sized> MetadataProvider for &mut T {
+    type Error = T::Error;
+
+    fn name(&mut self) -> Result<Option<String>, Self::Error> {
+        (**self).name()
+    }
+    fn version(&mut self) -> Result<Option<Version>, Self::Error> {
+        (**self).version()
+    }
+    fn homepage(&mut self) -> Result<Option<String>, Self::Error> {
+        (**self).homepage()
+    }
+    fn license(&mut self) -> Result<Option<String>, Self::Error> {
+        (**self).license()
+    }
+    fn license_file(&mut self) -> Result<Option<String>, Self::Error> {
+        (**self).license_file()
+    }
+    fn summary(&mut self) -> Result<Option<String>, Self::Error> {
+        (**self).summary()
+    }
+    fn description(&mut self) -> Result<Option<String>, Self::Error> {
+        (**self).description()
+    }
+    fn documentation(&mut self) -> Result<Option<String>, Self::Error> {
+        (**self).documentation()
+    }
+    fn repository(&mut self) -> Result<Option<String>, Self::Error> {
+        (**self).repository()
+    }
+    fn input_globs(&self) -> BTreeSet<String> {
+        (**self).input_globs()
+    }
+    fn value_source(&self, field: &str) -> Option<String> {
+        (**self).value_source(field)
+    }
 }
 
 pub struct DefaultMetadataProvider;
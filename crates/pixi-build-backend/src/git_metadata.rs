@@ -0,0 +1,435 @@
+//! Derives the version of a package from the tags of its git repository.
+//!
+//! The version is computed from the output of `git describe`, similar to the
+//! `post-release` version scheme of setuptools_scm:
+//!
+//! - On a tagged commit the version is the version of the tag, e.g. `1.2.3`
+//!   for the tag `v1.2.3`.
+//! - Three commits after the tag the version is `1.2.3.post3+gabc1234`, where
+//!   `abc1234` is the abbreviated hash of the commit.
+//! - Uncommitted changes to tracked files in the directory of the package add
+//!   `dirty` to the local version, e.g. `1.2.3.post0+gabc1234.dirty`.
+//!
+//! Tags may be prefixed with a name, so `mypkg-v1.2.3` is read as `1.2.3`.
+//! Tags that do not contain a valid version are skipped.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use miette::Diagnostic;
+use rattler_conda_types::{ParseVersionError, Version};
+use thiserror::Error;
+
+use crate::generated_recipe::MetadataProvider;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum GitMetadataError {
+    #[error("the git tag `{tag}` does not contain a version")]
+    #[diagnostic(help(
+        "tags are expected to look like `1.2.3`, `v1.2.3` or `<name>-v1.2.3`, or set the version in the manifest"
+    ))]
+    NoVersionInTag { tag: String },
+    #[error("the git tag `{tag}` does not contain a valid version")]
+    #[diagnostic(help(
+        "tags are expected to look like `1.2.3`, `v1.2.3` or `<name>-v1.2.3`, or set the version in the manifest"
+    ))]
+    InvalidTagVersion {
+        tag: String,
+        #[source]
+        source: ParseVersionError,
+    },
+}
+
+/// The result of `git describe` for a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitDescription {
+    /// The name of the nearest tag.
+    pub tag: String,
+    /// The number of commits since the tag.
+    pub distance: u64,
+    /// The abbreviated hash of the commit.
+    pub commit: String,
+    /// Whether tracked files have uncommitted changes.
+    pub dirty: bool,
+}
+
+impl GitDescription {
+    /// Parses the output of `git describe --tags --long --dirty`, e.g.
+    /// `v1.2.3-4-gabc1234-dirty`.
+    pub fn parse(describe: &str) -> Option<Self> {
+        let describe = describe.trim();
+        let (describe, dirty) = match describe.strip_suffix("-dirty") {
+            Some(describe) => (describe, true),
+            None => (describe, false),
+        };
+        let (describe, commit) = describe.rsplit_once('-')?;
+        let commit = commit.strip_prefix('g')?;
+        let (tag, distance) = describe.rsplit_once('-')?;
+        if tag.is_empty() {
+            return None;
+        }
+        Some(Self {
+            tag: tag.to_string(),
+            distance: distance.parse().ok()?,
+            commit: commit.to_string(),
+            dirty,
+        })
+    }
+
+    /// Returns the version of the package at the described commit.
+    pub fn version(&self) -> Result<Version, GitMetadataError> {
+        let tag_version =
+            tag_version(&self.tag).ok_or_else(|| GitMetadataError::NoVersionInTag {
+                tag: self.tag.clone(),
+            })?;
+        let version = if self.distance == 0 && !self.dirty {
+            tag_version.to_string()
+        } else {
+            let mut version = format!(
+                "{tag_version}.post{distance}+g{commit}",
+                distance = self.distance,
+                commit = self.commit
+            );
+            if self.dirty {
+                version.push_str(".dirty");
+            }
+            version
+        };
+        Version::from_str(&version).map_err(|source| GitMetadataError::InvalidTagVersion {
+            tag: self.tag.clone(),
+            source,
+        })
+    }
+}
+
+/// Returns the version part of a tag by stripping an optional `<name>-`
+/// prefix, a `v` prefix and a local version, or `None` if the tag does not
+/// contain a version. Versions cannot contain a `-`, so `2024-nightly` does
+/// not contain one.
+fn tag_version(tag: &str) -> Option<&str> {
+    let starts_version = |version: &str| {
+        let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+        version.starts_with(|c: char| c.is_ascii_digit())
+    };
+    let version = std::iter::once(tag)
+        .chain(tag.match_indices('-').map(|(idx, _)| &tag[idx + 1..]))
+        .find(|version| starts_version(version) && !version.contains('-'))?;
+    let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+    Some(
+        version
+            .split_once('+')
+            .map_or(version, |(version, _)| version),
+    )
+}
+
+/// The patterns that `git describe` matches tags against, the tags that
+/// [`tag_version`] can read a version from.
+const VERSION_TAG_PATTERNS: &[&str] = &["[0-9]*", "[vV][0-9]*", "*-[0-9]*", "*-[vV][0-9]*"];
+
+/// An implementation of [`MetadataProvider`] that derives the version of a
+/// package from the tags of the git repository that contains it.
+///
+/// The provider only provides a version, and only if the package is part of a
+/// git repository that has a version tag reachable from `HEAD`.
+pub struct GitMetadataProvider {
+    manifest_root: PathBuf,
+    description: Option<Option<GitDescription>>,
+    inputs: Option<GitInputs>,
+}
+
+/// The directories of the repository whose refs the version depends on.
+struct GitInputs {
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+}
+
+impl GitMetadataProvider {
+    /// Constructs a new `GitMetadataProvider` for the package in
+    /// `manifest_root`.
+    pub fn new(manifest_root: impl Into<PathBuf>) -> Self {
+        Self {
+            manifest_root: manifest_root.into(),
+            description: None,
+            inputs: None,
+        }
+    }
+
+    /// Describes `HEAD` of the repository, the result is cached.
+    fn ensure_description(&mut self) -> Option<&GitDescription> {
+        if self.description.is_none() {
+            let description = self.describe();
+            if description.is_some() {
+                self.inputs = self.inputs();
+            }
+            self.description = Some(description);
+        }
+        self.description.as_ref().and_then(Option::as_ref)
+    }
+
+    fn describe(&self) -> Option<GitDescription> {
+        // The patterns also match tags like `2024-nightly` that do not contain
+        // a valid version, these are excluded until a valid one is found.
+        let mut excluded_tags = Vec::new();
+        let mut description = loop {
+            let mut args = vec!["describe", "--tags", "--long", "--abbrev=7"];
+            for pattern in VERSION_TAG_PATTERNS {
+                args.extend(["--match", pattern]);
+            }
+            for tag in &excluded_tags {
+                args.extend(["--exclude", tag.as_str()]);
+            }
+            let description = GitDescription::parse(&git(&self.manifest_root, &args)?)?;
+            if description.version().is_ok() {
+                break description;
+            }
+            tracing::debug!(
+                "skipping the git tag `{}`, it does not contain a valid version",
+                description.tag
+            );
+            excluded_tags.push(escape_pattern(&description.tag));
+        };
+
+        // Only changes to the package make it dirty, changes elsewhere in the
+        // repository do not affect it.
+        description.dirty = !git(
+            &self.manifest_root,
+            &["status", "--porcelain", "--untracked-files=no", "--", "."],
+        )?
+        .trim()
+        .is_empty();
+        Some(description)
+    }
+
+    fn inputs(&self) -> Option<GitInputs> {
+        let dirs = git(
+            &self.manifest_root,
+            &["rev-parse", "--git-dir", "--git-common-dir"],
+        )?;
+        let mut dirs = dirs.lines().map(|line| self.manifest_root.join(line));
+        let (git_dir, common_dir) = (dirs.next()?, dirs.next()?);
+        Some(GitInputs {
+            git_dir,
+            common_dir,
+        })
+    }
+}
+
+impl MetadataProvider for GitMetadataProvider {
+    type Error = GitMetadataError;
+
+    fn version(&mut self) -> Result<Option<Version>, Self::Error> {
+        self.ensure_description()
+            .map(GitDescription::version)
+            .transpose()
+    }
+
+    /// Returns the set of globs that match the files of the git repository
+    /// that determine the tag and the commit of the version: `HEAD` and the
+    /// refs. The index and the files of the package are not included, so that
+    /// editing the package does not invalidate the metadata, whether it is
+    /// dirty is determined whenever the version is.
+    ///
+    /// The globs are relative to the manifest root and only returned if the
+    /// version was derived from the repository.
+    fn input_globs(&self) -> BTreeSet<String> {
+        let mut input_globs = BTreeSet::new();
+        let Some(inputs) = &self.inputs else {
+            return input_globs;
+        };
+
+        // Both paths are canonicalized, so that the git directories are
+        // relative to the manifest root even if git reports them as absolute
+        // paths.
+        let manifest_root = canonicalize(&self.manifest_root);
+        let relative = |path: &Path| {
+            let path = canonicalize(path);
+            pathdiff::diff_paths(&path, &manifest_root)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+                .replace('\\', "/")
+        };
+        let git_dir = relative(&inputs.git_dir);
+        let common_dir = relative(&inputs.common_dir);
+        input_globs.insert(format!("{git_dir}/HEAD"));
+        input_globs.insert(format!("{common_dir}/refs/**"));
+        input_globs.insert(format!("{common_dir}/packed-refs"));
+        input_globs
+    }
+
+    fn value_source(&self, _field: &str) -> Option<String> {
+        let description = self.description.as_ref()?.as_ref()?;
+        Some(format!("git tag `{}`", description.tag))
+    }
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Escapes the characters of `tag` that `git describe --exclude` would read as
+/// a pattern.
+fn escape_pattern(tag: &str) -> String {
+    let mut escaped = String::with_capacity(tag.len());
+    for c in tag.chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Runs git in `dir` and returns its output, or `None` if git is not
+/// available or fails.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::outputs_cache::find_input_files;
+
+    use super::*;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=pixi",
+                "-c",
+                "user.email=pixi@example.com",
+                "-c",
+                "commit.gpgsign=false",
+                "-c",
+                "tag.gpgsign=false",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    fn provider_version(manifest_root: &Path) -> Option<String> {
+        GitMetadataProvider::new(manifest_root)
+            .version()
+            .unwrap()
+            .map(|version| version.to_string())
+    }
+
+    fn version(describe: &str) -> String {
+        GitDescription::parse(describe)
+            .unwrap()
+            .version()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_parse_git_description() {
+        assert_eq!(
+            GitDescription::parse("mypkg-v1.2.3-4-gabc1234-dirty\n"),
+            Some(GitDescription {
+                tag: String::from("mypkg-v1.2.3"),
+                distance: 4,
+                commit: String::from("abc1234"),
+                dirty: true,
+            })
+        );
+        assert_eq!(GitDescription::parse("abc1234"), None);
+    }
+
+    #[test]
+    fn test_version_from_git_description() {
+        assert_eq!(version("v1.2.3-0-gabc1234"), "1.2.3");
+        assert_eq!(version("1.2.3-3-gabc1234"), "1.2.3.post3+gabc1234");
+        assert_eq!(
+            version("v1.2.3-0-gabc1234-dirty"),
+            "1.2.3.post0+gabc1234.dirty"
+        );
+        assert_eq!(
+            version("my-pkg-v2.0+local-1-g0123abc"),
+            "2.0.post1+g0123abc"
+        );
+
+        let post_release = Version::from_str(&version("v1.2.3-3-gabc1234")).unwrap();
+        assert!(post_release > Version::from_str("1.2.3").unwrap());
+        assert!(post_release < Version::from_str("1.2.4").unwrap());
+
+        for describe in ["nightly-0-gabc1234", "2024-nightly-0-gabc1234"] {
+            assert!(matches!(
+                GitDescription::parse(describe).unwrap().version(),
+                Err(GitMetadataError::NoVersionInTag { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_version_from_git_repository() {
+        let repo = tempfile::tempdir().unwrap();
+        let package = repo.path().join("pkg");
+        fs_err::create_dir_all(&package).unwrap();
+        fs_err::write(package.join("pixi.toml"), "[package]").unwrap();
+        fs_err::write(repo.path().join("README.md"), "readme").unwrap();
+
+        run_git(repo.path(), &["init", "-q"]);
+        run_git(repo.path(), &["add", "."]);
+        run_git(repo.path(), &["commit", "-q", "-m", "initial"]);
+        run_git(repo.path(), &["tag", "v1.2.3"]);
+        // Tags that are not versions are skipped.
+        run_git(repo.path(), &["tag", "nightly"]);
+
+        assert_eq!(provider_version(&package).as_deref(), Some("1.2.3"));
+
+        // The globs point at the repository from the package and match the files
+        // that determine the version.
+        let mut provider = GitMetadataProvider::new(&package);
+        provider.version().unwrap();
+        let input_globs = provider.input_globs();
+        assert_eq!(
+            input_globs,
+            BTreeSet::from(
+                ["../.git/HEAD", "../.git/packed-refs", "../.git/refs/**",].map(String::from)
+            )
+        );
+        let input_files = find_input_files(&package, &input_globs).unwrap();
+        for file in ["../.git/HEAD", "../.git/refs/tags/v1.2.3"] {
+            assert!(
+                input_files.contains(&PathBuf::from(file)),
+                "{file} is not in {input_files:?}"
+            );
+        }
+
+        // Changes outside of the package do not make it dirty.
+        fs_err::write(repo.path().join("README.md"), "changed").unwrap();
+        assert_eq!(provider_version(&package).as_deref(), Some("1.2.3"));
+
+        // Changes to a tracked file of the package do.
+        fs_err::write(package.join("pixi.toml"), "[package]\nname = \"foo\"").unwrap();
+        let dirty = provider_version(&package).unwrap();
+        assert!(dirty.starts_with("1.2.3.post0+g") && dirty.ends_with(".dirty"));
+
+        run_git(repo.path(), &["commit", "-q", "-a", "-m", "change"]);
+        let post_release = provider_version(&package).unwrap();
+        assert!(post_release.starts_with("1.2.3.post1+g"));
+        assert!(!post_release.ends_with(".dirty"));
+
+        // Tags that match the patterns but do not contain a valid version
+        // are skipped as well.
+        run_git(repo.path(), &["tag", "2024-nightly"]);
+        assert_eq!(provider_version(&package), Some(post_release));
+    }
+}
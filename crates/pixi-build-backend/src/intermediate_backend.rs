@@ -1,6 +1,6 @@
 use std::{
-    collections::{BTreeMap, HashMap},
-    path::{Path, PathBuf},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Component, Path, PathBuf},
     sync::Arc,
 };
 
@@ -8,7 +8,8 @@ use indexmap::{IndexMap, IndexSet};
 use miette::{Context, IntoDiagnostic};
 use ordermap::OrderMap;
 use pixi_build_types::{
-    BackendCapabilities, PathSpecV1, ProjectModelV1, SourcePackageSpecV1, TargetSelectorV1,
+    BackendCapabilities, PathSpecV1, PlatformAndVirtualPackages, ProjectModelV1,
+    SourcePackageSpecV1, TargetSelectorV1,
     procedures::{
         conda_build_v1::{CondaBuildV1Output, CondaBuildV1Params, CondaBuildV1Result},
         conda_outputs::{
@@ -20,6 +21,7 @@ use pixi_build_types::{
     },
 };
 use rattler_build::{
+    NormalizedKey,
     build::{WorkingDirectoryBehavior, run_build},
     console_utils::LoggingOutputHandler,
     hash::HashInfo,
@@ -28,23 +30,40 @@ use rattler_build::{
     selectors::SelectorConfig,
     source_code::Source,
     tool_configuration::Configuration,
-    types::{Directories, PackageIdentifier, PackagingSettings},
+    types::{Directories, PackageIdentifier},
     variant_config::{DiscoveredOutput, ParseErrors, VariantConfig},
 };
-use rattler_conda_types::{Platform, compression_level::CompressionLevel, package::ArchiveType};
+use rattler_conda_types::{ChannelUrl, Platform};
 
 use serde::Deserialize;
-use tracing::warn;
+use tracing::{Instrument, warn};
 
 use crate::{
-    TargetSelector,
+    build_timestamp::BuildTimestamp,
+    cancellation::{register_build_process_group, run_build_scripts_in_process_group},
+    common::{platform_with_virtual_packages, requested_virtual_packages},
+    config_diagnostics::{ConfigSection, parse_config},
     consts::DEBUG_OUTPUT_DIR,
     dependencies::{
-        convert_binary_dependencies, convert_dependencies, convert_input_variant_configuration,
+        convert_binary_dependencies, convert_constraints, convert_dependencies,
+        convert_input_variant_configuration, pin_compatible_to_host_prefix,
+        resolve_pin_compatible_dependencies,
     },
-    generated_recipe::{BackendConfig, GenerateRecipe, PythonParams},
-    protocol::{Protocol, ProtocolInstantiator},
+    env_passthrough::add_passthrough_secrets,
+    generated_recipe::{BackendConfig, GenerateRecipe, GeneratedRecipe, PythonParams},
+    notifications::{BuildPhase, PROGRESS_TARGET, build_span},
+    outputs_cache::{
+        CacheLookup, CacheMissReason, DebugFiles, OutputsCache, OutputsCacheKey, backend_identity,
+        hash_value,
+    },
+    overlay::apply_overlay,
+    package_test::run_package_tests,
+    protocol::{
+        GenerateRecipeParams, GenerateRecipeResult, GeneratedOutput, Protocol, ProtocolInstantiator,
+    },
+    sandbox::{SandboxConfig, run_sandboxed},
     specs_conversion::from_build_v1_args_to_finalized_dependencies,
+    target_config::TargetConfigs,
     tools::{OneOrMultipleOutputs, output_directory},
 };
 
@@ -82,10 +101,22 @@ pub struct IntermediateBackend<T: GenerateRecipe> {
     pub(crate) manifest_rel_path: PathBuf,
     pub(crate) project_model: ProjectModelV1,
     pub(crate) generate_recipe: Arc<T>,
-    pub(crate) config: T::Config,
-    pub(crate) target_config: OrderMap<TargetSelectorV1, T::Config>,
+    /// The configuration and the target-specific configurations.
+    pub(crate) config: TargetConfigs<T::Config>,
+    /// A hash of the raw configuration and target configurations.
+    pub(crate) config_hash: String,
     pub(crate) cache_dir: Option<PathBuf>,
 }
+/// A generated recipe together with the outputs that were discovered by
+/// expanding its variants.
+struct ExpandedRecipe {
+    generated_recipe: GeneratedRecipe,
+    variant_config: VariantConfig,
+    named_source: Source,
+    selector_config_for_variants: SelectorConfig,
+    discovered_outputs: IndexSet<DiscoveredOutput>,
+}
+
 impl<T: GenerateRecipe> IntermediateBackend<T> {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -127,9 +158,16 @@ impl<T: GenerateRecipe> IntermediateBackend<T> {
             }
         };
 
-        let config = serde_json::from_value::<T::Config>(config)
-            .into_diagnostic()
-            .context("failed to parse configuration")?;
+        let config_hash = hash_value(&(
+            &config,
+            target_config
+                .iter()
+                .map(|(target, config)| (target.to_string(), config))
+                .collect::<Vec<_>>(),
+        ))?;
+
+        let raw_config = config.clone();
+        let config = parse_config::<T::Config>(&config, ConfigSection::Base, &manifest_path)?;
 
         if let Some(path) = config.debug_dir() {
             warn!(
@@ -140,13 +178,13 @@ impl<T: GenerateRecipe> IntermediateBackend<T> {
 
         let target_config = target_config
             .into_iter()
-            .map(|(target, config)| {
-                let config = serde_json::from_value::<T::Config>(config)
-                    .into_diagnostic()
-                    .wrap_err_with(|| {
-                        format!("failed to parse target configuration for {target}")
-                    })?;
-                Ok((target, config))
+            .map(|(target, raw_config)| {
+                let config = parse_config::<T::Config>(
+                    &raw_config,
+                    ConfigSection::Target(&target.to_string()),
+                    &manifest_path,
+                )?;
+                Ok((target, (config, raw_config)))
             })
             .collect::<Result<_, miette::Report>>()?;
 
@@ -155,8 +193,8 @@ impl<T: GenerateRecipe> IntermediateBackend<T> {
             manifest_rel_path,
             project_model,
             generate_recipe,
-            config,
-            target_config,
+            config: TargetConfigs::new(config, raw_config, target_config),
+            config_hash,
             logging_output_handler,
             cache_dir,
         })
@@ -212,31 +250,41 @@ where
             capabilities: default_capabilities(),
         })
     }
+
+    fn config_schema() -> Option<serde_json::Value> {
+        T::Config::json_schema()
+    }
 }
 
-#[async_trait::async_trait]
-impl<T> Protocol for IntermediateBackend<T>
+impl<T> IntermediateBackend<T>
 where
     T: GenerateRecipe + Clone + Send + Sync + 'static,
     T::Config: BackendConfig + Send + Sync + 'static,
 {
-    async fn conda_outputs(
-        &self,
-        params: CondaOutputsParams,
-    ) -> miette::Result<CondaOutputsResult> {
-        let build_platform = params.host_platform;
-
-        let config = self
-            .target_config
-            .iter()
-            .find(|(selector, _)| selector.matches(params.host_platform))
-            .map(|(_, target_config)| self.config.merge_with_target_config(target_config))
-            .unwrap_or_else(|| Ok(self.config.clone()))?;
+    /// Returns the directory that contains the manifest.
+    fn manifest_dir(&self) -> PathBuf {
+        let manifest_path = self.source_dir.join(&self.manifest_rel_path);
+        manifest_path
+            .parent()
+            .map_or_else(|| self.source_dir.clone(), Path::to_path_buf)
+    }
 
+    /// Generates the recipe for a platform and determines its outputs by
+    /// expanding the variants. The virtual packages of the `host` platform
+    /// are added to the variants.
+    fn expand_recipe(
+        &self,
+        config: &T::Config,
+        host: &PlatformWithVirtualPackages,
+        channels: Vec<ChannelUrl>,
+        variant_configuration: Option<BTreeMap<String, Vec<String>>>,
+        variant_files: Option<Vec<PathBuf>>,
+    ) -> miette::Result<ExpandedRecipe> {
+        let host_platform = host.platform;
         let selector_config_for_variants = SelectorConfig {
-            target_platform: params.host_platform,
-            host_platform: params.host_platform,
-            build_platform,
+            target_platform: host_platform,
+            host_platform,
+            build_platform: host_platform,
             hash: None,
             variant: Default::default(),
             experimental: false,
@@ -244,34 +292,39 @@ where
             recipe_path: Some(self.source_dir.join(&self.manifest_rel_path)),
         };
 
-        let mut variants = self
-            .generate_recipe
-            .default_variants(params.host_platform)?;
+        let mut variants = self.generate_recipe.default_variants(host_platform)?;
 
         // Construct a `VariantConfig` based on the input parameters. This is a
         // combination of defaults provided by the generator (lowest priority),
         // variants loaded from external files, and finally the user supplied
         // variants (highest priority).
-        let variant_files = params.variant_files.unwrap_or_default();
+        let variant_files = variant_files.unwrap_or_default();
         let mut variant_config =
             VariantConfig::from_files(&variant_files, &selector_config_for_variants)?;
         variants.append(&mut variant_config.variants);
         variant_config.variants = variants;
 
         let mut param_variants =
-            convert_input_variant_configuration(params.variant_configuration.clone())
-                .unwrap_or_default();
+            convert_input_variant_configuration(variant_configuration).unwrap_or_default();
         variant_config.variants.append(&mut param_variants);
+        add_virtual_package_variants(&mut variant_config.variants, host);
 
         // Construct the intermediate recipe
-        let generated_recipe = self.generate_recipe.generate_recipe(
+        let mut generated_recipe = self.generate_recipe.generate_recipe(
             &self.project_model,
-            &config,
+            config,
             self.source_dir.clone(),
-            params.host_platform,
+            host_platform,
             Some(PythonParams { editable: false }),
             &variant_config.variants.keys().cloned().collect(),
-            params.channels,
+            channels,
+        )?;
+        add_passthrough_secrets(config, &mut generated_recipe)?;
+        apply_overlay(
+            config,
+            &self.source_dir,
+            &self.manifest_dir(),
+            &mut generated_recipe,
         )?;
 
         // Convert the recipe to source code.
@@ -288,7 +341,7 @@ where
                     .into_diagnostic()?
                     .as_str(),
             ),
-            path: recipe_path.clone(),
+            path: recipe_path,
         };
 
         // Determine the different outputs that are supported by the recipe by expanding
@@ -299,10 +352,45 @@ where
         // values like the variant. We should introduce a new type of selector config
         // for this particular case.
         let outputs = find_outputs_from_src(named_source.clone())?;
-        let discovered_outputs = variant_config.find_variants(
-            &outputs,
-            named_source.clone(),
-            &selector_config_for_variants,
+        let discovered_outputs = tracing::info_span!(BuildPhase::ResolvingVariants.span_name())
+            .in_scope(|| {
+                variant_config.find_variants(
+                    &outputs,
+                    named_source.clone(),
+                    &selector_config_for_variants,
+                )
+            })?;
+
+        Ok(ExpandedRecipe {
+            generated_recipe,
+            variant_config,
+            named_source,
+            selector_config_for_variants,
+            discovered_outputs,
+        })
+    }
+
+    /// Determines the outputs by generating the recipe and expanding the
+    /// variants. Also returns the debug files that should be written to the
+    /// work directory.
+    fn generate_conda_outputs(
+        &self,
+        config: &T::Config,
+        host_platform: &PlatformWithVirtualPackages,
+        params: CondaOutputsParams,
+    ) -> miette::Result<(CondaOutputsResult, DebugFiles)> {
+        let ExpandedRecipe {
+            generated_recipe,
+            variant_config,
+            named_source,
+            selector_config_for_variants,
+            discovered_outputs,
+        } = self.expand_recipe(
+            config,
+            host_platform,
+            params.channels,
+            params.variant_configuration,
+            params.variant_files,
         )?;
 
         // Construct a mapping that for packages that we want from source.
@@ -319,14 +407,31 @@ where
             })
             .collect();
 
+        // The globs of the files that are used by the build. These are the same
+        // for all outputs, only the sources differ.
+        let mut build_input_globs = self.generate_recipe.extract_input_globs_from_build(
+            config,
+            &params.work_directory,
+            false,
+        )?;
+        build_input_globs.extend(generated_recipe.build_input_globs.iter().cloned());
+
         let mut subpackages = HashMap::new();
         let mut outputs = Vec::new();
+        let mut debug_files = DebugFiles::new();
 
         let num_of_outputs = discovered_outputs.len();
 
         let mut variants_saved = false;
 
-        for discovered_output in discovered_outputs {
+        for (index, discovered_output) in discovered_outputs.into_iter().enumerate() {
+            tracing::info!(
+                target: PROGRESS_TARGET,
+                current = index as u64 + 1,
+                total = num_of_outputs as u64,
+                "resolved {}",
+                discovered_output.name
+            );
             let variant = discovered_output.used_vars;
             let hash = HashInfo::from_variant(&variant, &discovered_output.noarch_type);
 
@@ -357,6 +462,13 @@ where
 
             let build_number = recipe.build().number;
 
+            let mut input_globs = build_input_globs.clone();
+            input_globs.extend(source_input_globs(
+                &recipe,
+                named_source.path.parent().unwrap_or(&self.source_dir),
+                &self.source_dir,
+            )?);
+
             let directories = output_directory(
                 if num_of_outputs == 1 {
                     OneOrMultipleOutputs::Single(discovered_output.name.clone())
@@ -370,7 +482,11 @@ where
             // Save intermediate recipe and the used variant
             // in the debug dir by hash of the variant
             // and entire variants.yaml at the root of debug_dir
-            let debug_dir = &directories.build_dir.join("debug");
+            let debug_dir = directories
+                .build_dir
+                .strip_prefix(&params.work_directory)
+                .unwrap_or(&directories.build_dir)
+                .join("debug");
 
             let recipe_path = debug_dir.join("recipe.yaml");
             let variants_path = debug_dir.join("variants.yaml");
@@ -379,23 +495,15 @@ where
             let package_recipe_path = package_debug_dir.join("recipe.yaml");
             let package_variant_path = package_debug_dir.join("variants.yaml");
 
-            tokio_fs::create_dir_all(&package_debug_dir)
-                .await
-                .into_diagnostic()?;
-
             let recipe_yaml = generated_recipe.recipe.to_yaml_pretty().into_diagnostic()?;
 
-            tokio_fs::write(&package_recipe_path, &recipe_yaml)
-                .await
-                .into_diagnostic()?;
+            debug_files.insert(package_recipe_path, recipe_yaml.clone());
 
             let variant_yaml = serde_yaml::to_string(&variant)
                 .into_diagnostic()
                 .context("failed to serialize variant to YAML")?;
 
-            tokio_fs::write(&package_variant_path, variant_yaml)
-                .await
-                .into_diagnostic()?;
+            debug_files.insert(package_variant_path, variant_yaml);
 
             // write the entire variants.yaml at the root of debug_dir
             if !variants_saved {
@@ -403,13 +511,14 @@ where
                     .into_diagnostic()
                     .context("failed to serialize variant config to YAML")?;
 
-                tokio_fs::write(&variants_path, variants)
-                    .await
-                    .into_diagnostic()?;
+                debug_files.insert(variants_path, variants);
+                debug_files.insert(recipe_path, recipe_yaml);
+
+                let provenance = serde_yaml::to_string(&generated_recipe.metadata_provenance)
+                    .into_diagnostic()
+                    .context("failed to serialize metadata provenance to YAML")?;
 
-                tokio_fs::write(&recipe_path, recipe_yaml)
-                    .await
-                    .into_diagnostic()?;
+                debug_files.insert(debug_dir.join("metadata_provenance.yaml"), provenance);
 
                 variants_saved = true;
             }
@@ -433,7 +542,7 @@ where
                     license: recipe.about.license.map(|l| l.to_string()),
                     license_family: recipe.about.license_family,
                     noarch: recipe.build.noarch,
-                    purls: None,
+                    purls: generated_recipe.purls.get(&discovered_output.name).cloned(),
                     python_site_packages_path: None,
                     variant: variant
                         .iter()
@@ -447,7 +556,12 @@ where
                         &subpackages,
                         &local_source_packages,
                     )?,
-                    constraints: Vec::new(),
+                    constraints: convert_constraints(
+                        generated_recipe
+                            .build_constraints
+                            .get(&discovered_output.name)
+                            .map_or(&[], Vec::as_slice),
+                    )?,
                 }),
                 host_dependencies: Some(CondaOutputDependencies {
                     depends: convert_dependencies(
@@ -456,17 +570,30 @@ where
                         &subpackages,
                         &local_source_packages,
                     )?,
-                    constraints: Vec::new(),
+                    constraints: convert_constraints(
+                        generated_recipe
+                            .host_constraints
+                            .get(&discovered_output.name)
+                            .map_or(&[], Vec::as_slice),
+                    )?,
                 }),
                 run_dependencies: CondaOutputDependencies {
                     depends: convert_dependencies(
-                        recipe.requirements.run,
+                        resolve_pin_compatible_dependencies(
+                            recipe.requirements.run,
+                            &HashMap::new(),
+                            &variant,
+                        )?,
                         &BTreeMap::default(), // Variants are not applied to run dependencies
                         &subpackages,
                         &local_source_packages,
                     )?,
                     constraints: convert_binary_dependencies(
-                        recipe.requirements.run_constraints,
+                        resolve_pin_compatible_dependencies(
+                            recipe.requirements.run_constraints,
+                            &HashMap::new(),
+                            &variant,
+                        )?,
                         &BTreeMap::default(), // Variants are not applied to run constraints
                         &subpackages,
                     )?,
@@ -516,15 +643,164 @@ where
                     )?,
                 },
 
-                // The input globs are the same for all outputs
-                input_globs: None,
-                // TODO: Implement caching
+                input_globs: Some(input_globs),
             });
         }
 
-        Ok(CondaOutputsResult {
+        Ok((
+            CondaOutputsResult {
+                outputs,
+                input_globs: generated_recipe.metadata_input_globs,
+            },
+            debug_files,
+        ))
+    }
+
+    /// Computes the key of the `conda/outputs` cache for a request.
+    fn outputs_cache_key(
+        &self,
+        host_platform: &PlatformWithVirtualPackages,
+        params: &CondaOutputsParams,
+    ) -> miette::Result<OutputsCacheKey> {
+        // The variant files are hashed by content because they can change
+        // without their path changing.
+        let variant_files = params
+            .variant_files
+            .iter()
+            .flatten()
+            .map(|path| {
+                fs_err::read_to_string(path)
+                    .into_diagnostic()
+                    .map(|contents| (path, contents))
+            })
+            .collect::<miette::Result<Vec<_>>>()?;
+
+        Ok(OutputsCacheKey {
+            backend: hash_value(&backend_identity())?,
+            project_model: hash_value(&self.project_model)?,
+            config: self.config_hash.clone(),
+            variants: hash_value(&(
+                params.host_platform,
+                host_platform
+                    .virtual_packages
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                &params.variant_configuration,
+                variant_files,
+                &params.channels,
+            ))?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Protocol for IntermediateBackend<T>
+where
+    T: GenerateRecipe + Clone + Send + Sync + 'static,
+    T::Config: BackendConfig + Send + Sync + 'static,
+{
+    async fn conda_outputs(
+        &self,
+        params: CondaOutputsParams,
+    ) -> miette::Result<CondaOutputsResult> {
+        let debug_dir = params.work_directory.join(DEBUG_OUTPUT_DIR);
+        let (config, merge_record) = self.config.for_platform(params.host_platform)?;
+        merge_record.write(&debug_dir).await?;
+
+        // The virtual packages can be used in selectors, so they have to be the
+        // same ones that `conda/build_v1` uses.
+        let host_platform = platform_with_virtual_packages(PlatformAndVirtualPackages {
+            platform: params.host_platform,
+            virtual_packages: requested_virtual_packages(single_valued_variants(
+                params.variant_configuration.as_ref(),
+            ))?,
+        })?;
+
+        let Some(cache_dir) = &self.cache_dir else {
+            let work_directory = params.work_directory.clone();
+            let (result, debug_files) =
+                self.generate_conda_outputs(&config, &host_platform, params)?;
+            write_debug_files(&work_directory, &debug_files).await?;
+            return Ok(result);
+        };
+
+        let cache = OutputsCache::new(
+            cache_dir,
+            &self.source_dir,
+            &format!(
+                "{}:{}",
+                self.manifest_rel_path.display(),
+                params.host_platform
+            ),
+        )?;
+        let key = self.outputs_cache_key(&host_platform, &params)?;
+        let work_directory = params.work_directory.clone();
+
+        let reason = match cache.get(&key) {
+            CacheLookup::Hit {
+                result,
+                debug_files,
+            } => {
+                log_outputs_cache_status(&debug_dir, None).await?;
+                write_debug_files(&work_directory, &debug_files).await?;
+                return Ok(result);
+            }
+            CacheLookup::Miss(reason) => reason,
+        };
+        tracing::debug!("conda/outputs cache miss: {reason}");
+        log_outputs_cache_status(&debug_dir, Some(&reason)).await?;
+
+        let (result, debug_files) = self.generate_conda_outputs(&config, &host_platform, params)?;
+        write_debug_files(&work_directory, &debug_files).await?;
+        if let Err(err) = cache.insert(key, &result, &debug_files) {
+            warn!("failed to write the conda/outputs cache: {err}");
+        }
+        Ok(result)
+    }
+
+    async fn generate_recipe(
+        &self,
+        params: GenerateRecipeParams,
+    ) -> miette::Result<GenerateRecipeResult> {
+        let (config, _) = self.config.for_platform(params.host_platform)?;
+        let host_platform = platform_with_virtual_packages(PlatformAndVirtualPackages {
+            platform: params.host_platform,
+            virtual_packages: requested_virtual_packages(single_valued_variants(
+                params.variant_configuration.as_ref(),
+            ))?,
+        })?;
+        let ExpandedRecipe {
+            generated_recipe,
+            discovered_outputs,
+            ..
+        } = self.expand_recipe(
+            &config,
+            &host_platform,
+            params.channels,
+            params.variant_configuration,
+            params.variant_files,
+        )?;
+
+        let outputs = discovered_outputs
+            .into_iter()
+            .filter(|output| !output.recipe.build.skip())
+            .map(|output| GeneratedOutput {
+                version: output.recipe.package.version.to_string(),
+                name: output.name,
+                build: output.build_string,
+                subdir: output.target_platform,
+                variant: output
+                    .used_vars
+                    .iter()
+                    .map(|(key, value)| (key.0.clone(), value.to_string()))
+                    .collect(),
+            })
+            .collect();
+
+        Ok(GenerateRecipeResult {
+            recipe: generated_recipe.recipe.to_yaml_pretty().into_diagnostic()?,
             outputs,
-            input_globs: generated_recipe.metadata_input_globs,
         })
     }
 
@@ -541,23 +817,40 @@ where
             .as_ref()
             .map_or_else(Platform::current, |prefix| prefix.platform);
 
-        let config = self
-            .target_config
-            .iter()
-            .find(|(selector, _)| selector.matches(host_platform))
-            .map(|(_, target_config)| self.config.merge_with_target_config(target_config))
-            .unwrap_or_else(|| Ok(self.config.clone()))?;
+        let (config, merge_record) = self.config.for_platform(host_platform)?;
+        merge_record
+            .write(&params.work_directory.join(DEBUG_OUTPUT_DIR))
+            .await?;
 
         // Construct the variants based on the input parameters. We only
         // have a single variant here so we can just use the variant from the
         // parameters.
-        let variants: BTreeMap<_, _> = params
+        let mut variants: BTreeMap<_, _> = params
             .output
             .variant
             .iter()
             .map(|(k, v)| (k.as_str().into(), vec![Variable::from_string(v)]))
             .collect();
 
+        // Use the same virtual packages as `conda/outputs` so that selectors on
+        // them evaluate the same. These are the ones in the variant of the
+        // output, unless the frontend did not send any.
+        let host_virtual_packages = platform_with_virtual_packages(PlatformAndVirtualPackages {
+            platform: host_platform,
+            virtual_packages: requested_virtual_packages(
+                params
+                    .output
+                    .variant
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str())),
+            )?,
+        })?;
+        let build_virtual_packages = platform_with_virtual_packages(PlatformAndVirtualPackages {
+            platform: build_platform,
+            virtual_packages: None,
+        })?;
+        add_virtual_package_variants(&mut variants, &host_virtual_packages);
+
         // Construct the intermediate recipe
         let mut recipe = self.generate_recipe.generate_recipe(
             &self.project_model,
@@ -568,8 +861,11 @@ where
                 editable: params.editable.unwrap_or_default(),
             }),
             &variants.keys().cloned().collect(),
-            params.channels,
+            params.channels.clone(),
         )?;
+        add_passthrough_secrets(&config, &mut recipe)?;
+        apply_overlay(&config, &self.source_dir, &self.manifest_dir(), &mut recipe)?;
+        run_build_scripts_in_process_group(&mut recipe.recipe);
 
         // Convert the recipe to source code.
         // TODO(baszalmstra): In the future it would be great if we could just
@@ -600,11 +896,32 @@ where
             pin_run_as_build: None,
             zip_keys: None,
         };
-        let discovered_outputs = variant_config.find_variants(
-            &outputs,
-            named_source.clone(),
-            &selector_config_for_variants,
-        )?;
+        let discovered_outputs = tracing::info_span!(BuildPhase::ResolvingVariants.span_name())
+            .in_scope(|| {
+                variant_config.find_variants(
+                    &outputs,
+                    named_source.clone(),
+                    &selector_config_for_variants,
+                )
+            })?;
+
+        // All the outputs of a multi-output recipe are subpackages of each other,
+        // this allows `pin_subpackage` to refer to sibling outputs.
+        let subpackages: BTreeMap<_, _> = discovered_outputs
+            .iter()
+            .filter(|output| !output.recipe.build().skip())
+            .map(|output| {
+                (
+                    output.recipe.package().name().clone(),
+                    PackageIdentifier {
+                        name: output.recipe.package().name().clone(),
+                        version: output.recipe.package().version().clone(),
+                        build_string: output.build_string.clone(),
+                    },
+                )
+            })
+            .collect();
+
         let discovered_output = find_matching_output(&params.output, discovered_outputs)?;
 
         // Set up the proper directories for the build.
@@ -616,6 +933,7 @@ where
             params.output_directory.as_deref(),
             recipe_path,
         );
+        register_build_process_group(&directories.work_dir);
 
         // Save intermediate recipe and the used variant
         // in the debug dir by hash of the variant
@@ -665,6 +983,15 @@ where
             .await
             .into_diagnostic()?;
 
+        // write where the package metadata came from
+        let provenance = serde_yaml::to_string(&recipe.metadata_provenance)
+            .into_diagnostic()
+            .context("failed to serialize metadata provenance to YAML")?;
+
+        tokio_fs::write(debug_dir.join("metadata_provenance.yaml"), provenance)
+            .await
+            .into_diagnostic()?;
+
         let tool_config = Configuration::builder()
             .with_opt_cache_dir(self.cache_dir.clone())
             .with_logging_output_handler(self.logging_output_handler.clone())
@@ -676,41 +1003,66 @@ where
             .with_environments_externally_managed(true)
             .finish();
 
+        // Extract the input globs from the build and recipe
+        let mut input_globs = self.generate_recipe.extract_input_globs_from_build(
+            &config,
+            &params.work_directory,
+            params.editable.unwrap_or_default(),
+        )?;
+        input_globs.append(&mut recipe.build_input_globs);
+
+        // Derive the timestamp from the source so that rebuilding the same
+        // source produces an identical package.
+        let timestamp = BuildTimestamp::determine(
+            &self.source_dir,
+            &self.source_dir.join(&self.manifest_rel_path),
+            &input_globs,
+        )?;
+
+        let packaging = config.packaging();
+        // The `pin_compatible` run dependencies were pinned to the variant when
+        // the outputs were determined, now the host prefix is known.
+        let requirements = &discovered_output.recipe.requirements;
+        let run_dependencies = pin_compatible_to_host_prefix(
+            &requirements.run,
+            params.host_prefix.as_ref(),
+            params.run_dependencies,
+        )?;
+        let run_constraints = pin_compatible_to_host_prefix(
+            &requirements.run_constraints,
+            params.host_prefix.as_ref(),
+            params.run_constraints,
+        )?;
+
         let output = Output {
             recipe: discovered_output.recipe,
             build_configuration: BuildConfiguration {
                 target_platform: discovered_output.target_platform,
-                host_platform: PlatformWithVirtualPackages {
-                    platform: host_platform,
-                    virtual_packages: vec![],
-                },
-                build_platform: PlatformWithVirtualPackages {
-                    platform: build_platform,
-                    virtual_packages: vec![],
-                },
+                host_platform: host_virtual_packages,
+                build_platform: build_virtual_packages,
                 hash: discovered_output.hash,
                 variant,
                 directories,
                 channels: vec![],
                 channel_priority: Default::default(),
                 solve_strategy: Default::default(),
-                timestamp: chrono::Utc::now(),
-                subpackages: BTreeMap::new(),
-                packaging_settings: PackagingSettings::from_args(
-                    ArchiveType::Conda,
-                    CompressionLevel::default(),
-                ),
-                store_recipe: false,
+                timestamp: timestamp.timestamp,
+                subpackages,
+                packaging_settings: packaging.packaging_settings(),
+                store_recipe: packaging.store_recipe,
                 force_colors: true,
-                sandbox_config: None,
+                sandbox_config: config
+                    .sandbox()
+                    .map(SandboxConfig::configuration)
+                    .transpose()?,
                 debug: Debug::new(false),
                 exclude_newer: None,
             },
             finalized_dependencies: Some(from_build_v1_args_to_finalized_dependencies(
                 params.build_prefix,
                 params.host_prefix,
-                params.run_dependencies,
-                params.run_constraints,
+                run_dependencies,
+                run_constraints,
                 params.run_exports,
             )),
             finalized_sources: None,
@@ -721,16 +1073,31 @@ where
             extra_meta: None,
         };
 
-        let (output, output_path) =
-            run_build(output, &tool_config, WorkingDirectoryBehavior::Preserve).await?;
+        // Everything that is logged while building is tagged with the output.
+        let build_span = build_span(
+            output.name().as_normalized(),
+            &output.build_configuration.hash.hash,
+        );
+        let package = output.name().as_normalized().to_string();
+        let (output, output_path) = async {
+            let build = run_build(output, &tool_config, WorkingDirectoryBehavior::Preserve);
+            let (output, output_path) = if config.sandbox().is_some() {
+                run_sandboxed(&package, build).await?
+            } else {
+                build.await?
+            };
 
-        // Extract the input globs from the build and recipe
-        let mut input_globs = self.generate_recipe.extract_input_globs_from_build(
-            &config,
-            &params.work_directory,
-            params.editable.unwrap_or_default(),
-        )?;
-        input_globs.append(&mut recipe.build_input_globs);
+            // Run the tests of the package if requested.
+            if config.run_tests() {
+                run_package_tests(&output, &output_path, params.channels, &tool_config)
+                    .instrument(tracing::info_span!(BuildPhase::Testing.span_name()))
+                    .await?;
+            }
+
+            Ok::<_, miette::Report>((output, output_path))
+        }
+        .instrument(build_span)
+        .await?;
 
         Ok(CondaBuildV1Result {
             output_file: output_path,
@@ -809,6 +1176,122 @@ pub fn conda_build_v1_directories(
     }
 }
 
+/// Returns the globs that match the local path sources of an output, relative
+/// to the source directory. Like rattler-build, relative source paths are
+/// resolved against the directory of the recipe.
+fn source_input_globs(
+    recipe: &Recipe,
+    recipe_dir: &Path,
+    source_dir: &Path,
+) -> miette::Result<BTreeSet<String>> {
+    recipe
+        .sources()
+        .iter()
+        .filter_map(|source| match source {
+            rattler_build::recipe::parser::Source::Path(path_source) => {
+                Some(recipe_dir.join(&path_source.path).components().fold(
+                    PathBuf::new(),
+                    |mut path, component| {
+                        match component {
+                            Component::ParentDir if path.file_name().is_some() => {
+                                path.pop();
+                            }
+                            component => path.push(component),
+                        }
+                        path
+                    },
+                ))
+            }
+            _ => None,
+        })
+        .map(|path| {
+            let relative_path = pathdiff::diff_paths(&path, source_dir).ok_or_else(|| {
+                miette::miette!(
+                    "could not compute relative path from '{}' to '{}'",
+                    source_dir.display(),
+                    path.display()
+                )
+            })?;
+            let glob = relative_path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            Ok(match (path.is_dir(), glob.is_empty()) {
+                (true, true) => String::from("**"),
+                (true, false) => format!("{glob}/**"),
+                (false, _) => glob,
+            })
+        })
+        .collect()
+}
+
+/// Returns the variant keys of `variant_configuration` that have a single
+/// value, together with that value.
+fn single_valued_variants(
+    variant_configuration: Option<&BTreeMap<String, Vec<String>>>,
+) -> impl Iterator<Item = (&str, &str)> {
+    variant_configuration
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, values)| match values.as_slice() {
+            [value] => Some((key.as_str(), value.as_str())),
+            _ => None,
+        })
+}
+
+/// Adds the virtual packages of the platform to the variants, e.g.
+/// `__glibc: 2.35`, so that recipes can select on them. Variants that are
+/// already set take precedence.
+fn add_virtual_package_variants(
+    variants: &mut BTreeMap<NormalizedKey, Vec<Variable>>,
+    platform: &PlatformWithVirtualPackages,
+) {
+    for package in &platform.virtual_packages {
+        variants
+            .entry(package.name.as_normalized().into())
+            .or_insert_with(|| vec![Variable::from_string(&package.version.to_string())]);
+    }
+}
+
+/// Writes the debug files of a `conda/outputs` request to the work directory.
+async fn write_debug_files(work_directory: &Path, debug_files: &DebugFiles) -> miette::Result<()> {
+    for (path, contents) in debug_files {
+        let path = work_directory.join(path);
+        if let Some(parent) = path.parent() {
+            tokio_fs::create_dir_all(parent).await.into_diagnostic()?;
+        }
+        tokio_fs::write(&path, contents).await.into_diagnostic()?;
+    }
+    Ok(())
+}
+
+/// Writes whether the `conda/outputs` cache was hit, and if not why, to the
+/// debug directory.
+async fn log_outputs_cache_status(
+    debug_dir: &Path,
+    miss: Option<&CacheMissReason>,
+) -> miette::Result<()> {
+    let status = match miss {
+        None => serde_json::json!({ "hit": true }),
+        Some(reason) => serde_json::json!({
+            "hit": false,
+            "miss": reason,
+            "message": reason.to_string(),
+        }),
+    };
+
+    tokio_fs::create_dir_all(debug_dir)
+        .await
+        .into_diagnostic()?;
+    tokio_fs::write(
+        debug_dir.join("conda_outputs_cache.json"),
+        serde_json::to_string_pretty(&status).into_diagnostic()?,
+    )
+    .await
+    .into_diagnostic()
+}
+
 /// Returns the capabilities for this backend
 fn default_capabilities() -> BackendCapabilities {
     BackendCapabilities {
@@ -1,12 +1,27 @@
+pub mod build_timestamp;
 pub mod cli;
+pub mod config_diagnostics;
+pub mod config_schema;
+pub mod env_passthrough;
 pub mod generated_recipe;
+pub mod git_metadata;
 pub mod intermediate_backend;
+pub mod metadata_chain;
+pub mod notifications;
+pub mod outputs_cache;
+pub mod overlay;
+pub mod package_test;
+pub mod packaging;
 pub mod protocol;
 pub mod rattler_build_integration;
+pub mod replay;
+pub mod sandbox;
 pub mod server;
 pub mod specs_conversion;
+pub mod target_config;
 
 pub mod cache;
+pub mod cancellation;
 pub mod common;
 pub mod compilers;
 pub mod dependencies;
@@ -0,0 +1,210 @@
+//! Combining several [`MetadataProvider`]s into one.
+//!
+//! A backend can read the name of a package from one file, the version from the
+//! git tags and the description from yet another file by chaining providers
+//! with [`MetadataProvider::or`]:
+//!
+//! ```ignore
+//! let mut metadata = PyprojectMetadataProvider::new(&manifest_root, false)
+//!     .or(GitMetadataProvider::new(&manifest_root));
+//! ```
+//!
+//! The chain remembers which provider returned each value, so that
+//! [`MetadataProvider::value_source`] reports the file the value came from.
+
+use std::collections::{BTreeSet, HashSet};
+
+use miette::Diagnostic;
+use rattler_conda_types::Version;
+use thiserror::Error;
+
+use crate::generated_recipe::MetadataProvider;
+
+/// An error of one of the providers of a [`MetadataProviderChain`].
+#[derive(Debug, Error, Diagnostic)]
+pub enum MetadataProviderChainError<A: Diagnostic + 'static, B: Diagnostic + 'static> {
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    First(A),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Second(B),
+}
+
+/// A [`MetadataProvider`] that queries `second` for every value that `first`
+/// does not provide. Errors of `first` are returned as they are, they do not
+/// fall back to `second`.
+///
+/// Use [`MetadataProvider::or`] to construct a chain.
+pub struct MetadataProviderChain<A, B> {
+    first: A,
+    second: B,
+    /// The fields whose value was returned by `second`.
+    from_second: HashSet<&'static str>,
+}
+
+impl<A: MetadataProvider, B: MetadataProvider> MetadataProviderChain<A, B> {
+    /// Constructs a chain that queries `first` before `second`.
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            from_second: HashSet::new(),
+        }
+    }
+
+    /// Returns the provider that is queried first.
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    /// Returns a mutable reference to the provider that is queried first.
+    pub fn first_mut(&mut self) -> &mut A {
+        &mut self.first
+    }
+
+    /// Returns the provider that is queried for the values that the first
+    /// provider does not provide.
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    fn query<T>(
+        &mut self,
+        field: &'static str,
+        first: impl FnOnce(&mut A) -> Result<Option<T>, A::Error>,
+        second: impl FnOnce(&mut B) -> Result<Option<T>, B::Error>,
+    ) -> Result<Option<T>, MetadataProviderChainError<A::Error, B::Error>> {
+        if let Some(value) = first(&mut self.first).map_err(MetadataProviderChainError::First)? {
+            self.from_second.remove(field);
+            return Ok(Some(value));
+        }
+        let value = second(&mut self.second).map_err(MetadataProviderChainError::Second)?;
+        if value.is_some() {
+            self.from_second.insert(field);
+        }
+        Ok(value)
+    }
+}
+
+impl<A: MetadataProvider, B: MetadataProvider> MetadataProvider for MetadataProviderChain<A, B> {
+    type Error = MetadataProviderChainError<A::Error, B::Error>;
+
+    fn name(&mut self) -> Result<Option<String>, Self::Error> {
+        self.query("name", A::name, B::name)
+    }
+
+    fn version(&mut self) -> Result<Option<Version>, Self::Error> {
+        self.query("version", A::version, B::version)
+    }
+
+    fn homepage(&mut self) -> Result<Option<String>, Self::Error> {
+        self.query("homepage", A::homepage, B::homepage)
+    }
+
+    fn license(&mut self) -> Result<Option<String>, Self::Error> {
+        self.query("license", A::license, B::license)
+    }
+
+    fn license_file(&mut self) -> Result<Option<String>, Self::Error> {
+        self.query("license-file", A::license_file, B::license_file)
+    }
+
+    fn summary(&mut self) -> Result<Option<String>, Self::Error> {
+        self.query("summary", A::summary, B::summary)
+    }
+
+    fn description(&mut self) -> Result<Option<String>, Self::Error> {
+        self.query("description", A::description, B::description)
+    }
+
+    fn documentation(&mut self) -> Result<Option<String>, Self::Error> {
+        self.query("documentation", A::documentation, B::documentation)
+    }
+
+    fn repository(&mut self) -> Result<Option<String>, Self::Error> {
+        self.query("repository", A::repository, B::repository)
+    }
+
+    /// Returns the input globs of both providers.
+    fn input_globs(&self) -> BTreeSet<String> {
+        let mut input_globs = self.first.input_globs();
+        input_globs.extend(self.second.input_globs());
+        input_globs
+    }
+
+    fn value_source(&self, field: &str) -> Option<String> {
+        if self.from_second.contains(field) {
+            self.second.value_source(field)
+        } else {
+            self.first.value_source(field)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, str::FromStr};
+
+    use super::*;
+
+    /// A provider that returns fixed values.
+    #[derive(Default)]
+    struct FixedProvider {
+        source: &'static str,
+        name: Option<&'static str>,
+        version: Option<&'static str>,
+        input_globs: &'static [&'static str],
+    }
+
+    impl MetadataProvider for FixedProvider {
+        type Error = Infallible;
+
+        fn name(&mut self) -> Result<Option<String>, Self::Error> {
+            Ok(self.name.map(String::from))
+        }
+
+        fn version(&mut self) -> Result<Option<Version>, Self::Error> {
+            Ok(self.version.map(|v| Version::from_str(v).unwrap()))
+        }
+
+        fn input_globs(&self) -> BTreeSet<String> {
+            self.input_globs
+                .iter()
+                .map(|glob| glob.to_string())
+                .collect()
+        }
+
+        fn value_source(&self, _field: &str) -> Option<String> {
+            Some(self.source.to_string())
+        }
+    }
+
+    #[test]
+    fn test_chain_queries_providers_in_order() {
+        let mut pyproject = FixedProvider {
+            source: "pyproject.toml",
+            name: Some("foo"),
+            input_globs: &["pyproject.toml"],
+            ..Default::default()
+        };
+        let git = FixedProvider {
+            source: "git tag `v1.0.0`",
+            name: Some("bar"),
+            version: Some("1.0.0"),
+            input_globs: &[".git/HEAD"],
+        };
+
+        let mut chain = (&mut pyproject).or(git);
+        assert_eq!(chain.name().unwrap().as_deref(), Some("foo"));
+        assert_eq!(chain.version().unwrap().unwrap().to_string(), "1.0.0");
+        assert_eq!(chain.homepage().unwrap(), None);
+
+        assert_eq!(chain.value_source("name").unwrap(), "pyproject.toml");
+        assert_eq!(chain.value_source("version").unwrap(), "git tag `v1.0.0`");
+        assert_eq!(
+            chain.input_globs(),
+            BTreeSet::from([".git/HEAD".to_string(), "pyproject.toml".to_string()])
+        );
+    }
+}
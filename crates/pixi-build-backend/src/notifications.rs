@@ -0,0 +1,370 @@
+//! Structured build events that are sent to the client as JSON-RPC
+//! notifications.
+//!
+//! The backend logs through `tracing`. The [`BuildEventLayer`] observes the
+//! spans and events and turns them into notifications:
+//!
+//! - [`PHASE_STARTED_METHOD_NAME`] when a span of a known [`BuildPhase`] is
+//!   created,
+//! - [`PROGRESS_METHOD_NAME`] for events with the [`PROGRESS_TARGET`] target,
+//! - [`LOG_METHOD_NAME`] for all other events up to [`MAX_LOG_LEVEL`].
+//!
+//! Every notification is tagged with the `output` and `variant_hash` fields of
+//! the span it was emitted in, see [`build_span`].
+
+use std::{fmt, sync::Arc};
+
+use jsonrpc_core::{Notification, Params, Version};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+    span,
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+/// The name of the notification that is sent when a build phase starts.
+pub const PHASE_STARTED_METHOD_NAME: &str = "build/phaseStarted";
+
+/// The name of the notification that carries a single log line.
+pub const LOG_METHOD_NAME: &str = "build/log";
+
+/// The name of the notification that reports progress.
+pub const PROGRESS_METHOD_NAME: &str = "build/progress";
+
+/// The tracing target of events that should be reported as progress. These
+/// events should have `current` and `total` fields.
+pub const PROGRESS_TARGET: &str = "pixi_build_backend::progress";
+
+/// The most verbose level of the events that are sent as
+/// [`LOG_METHOD_NAME`] notifications. Debug and trace events would flood the
+/// channel, they are only written to the log of the backend.
+pub const MAX_LOG_LEVEL: Level = Level::INFO;
+
+/// A phase of building a package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BuildPhase {
+    ResolvingVariants,
+    RunningScript,
+    Packaging,
+    Testing,
+}
+
+impl BuildPhase {
+    /// The name of the span that marks the start of this phase.
+    ///
+    /// The script and packaging phases are spans that are created by
+    /// rattler-build, the `test_conda_build_v1` integration test fails when
+    /// rattler-build renames them.
+    pub const fn span_name(self) -> &'static str {
+        match self {
+            BuildPhase::ResolvingVariants => "Resolving variants",
+            BuildPhase::RunningScript => "Running build script",
+            BuildPhase::Packaging => "Packaging new files",
+            BuildPhase::Testing => "Running tests",
+        }
+    }
+
+    fn from_span_name(name: &str) -> Option<Self> {
+        [
+            BuildPhase::ResolvingVariants,
+            BuildPhase::RunningScript,
+            BuildPhase::Packaging,
+            BuildPhase::Testing,
+        ]
+        .into_iter()
+        .find(|phase| phase.span_name() == name)
+    }
+}
+
+/// Creates the span in which an output is built. All notifications emitted in
+/// this span are tagged with the name of the output and the variant hash.
+pub fn build_span(output: &str, variant_hash: &str) -> tracing::Span {
+    tracing::info_span!("Building", output, variant_hash)
+}
+
+/// The parameters of the [`PHASE_STARTED_METHOD_NAME`] notification.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseStartedParams {
+    pub phase: BuildPhase,
+    #[serde(flatten)]
+    pub tags: BuildTags,
+}
+
+/// The parameters of the [`LOG_METHOD_NAME`] notification.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogParams {
+    pub level: String,
+    pub message: String,
+    #[serde(flatten)]
+    pub tags: BuildTags,
+}
+
+/// The parameters of the [`PROGRESS_METHOD_NAME`] notification.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressParams {
+    pub message: String,
+    pub current: u64,
+    pub total: u64,
+    #[serde(flatten)]
+    pub tags: BuildTags,
+}
+
+/// Identifies the output a notification belongs to.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildTags {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant_hash: Option<String>,
+}
+
+/// A handle to the channel the notifications are written to. Notifications are
+/// dropped while no client is connected.
+#[derive(Clone, Default)]
+pub struct NotificationSink {
+    sender: Arc<Mutex<Option<mpsc::UnboundedSender<String>>>>,
+}
+
+impl NotificationSink {
+    /// Sends all future notifications to the given channel.
+    pub fn connect(&self, sender: mpsc::UnboundedSender<String>) {
+        *self.sender.lock() = Some(sender);
+    }
+
+    /// Stops sending notifications.
+    pub fn disconnect(&self) {
+        *self.sender.lock() = None;
+    }
+
+    /// Returns a tracing layer that turns spans and events into notifications
+    /// on this sink.
+    pub fn layer(&self) -> BuildEventLayer {
+        BuildEventLayer { sink: self.clone() }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.sender
+            .lock()
+            .as_ref()
+            .is_some_and(|sender| !sender.is_closed())
+    }
+
+    /// Sends a notification with the given method and parameters.
+    pub fn notify(&self, method: &str, params: impl Serialize) {
+        let guard = self.sender.lock();
+        let Some(sender) = guard.as_ref() else {
+            return;
+        };
+
+        let params = match serde_json::to_value(params) {
+            Ok(serde_json::Value::Object(map)) => Params::Map(map),
+            _ => return,
+        };
+        let notification = Notification {
+            jsonrpc: Some(Version::V2),
+            method: method.to_string(),
+            params,
+        };
+        if let Ok(notification) = serde_json::to_string(&notification) {
+            // The receiver is gone when the client disconnected.
+            let _ = sender.send(notification);
+        }
+    }
+}
+
+/// A [`Layer`] that sends spans and events to a [`NotificationSink`].
+pub struct BuildEventLayer {
+    sink: NotificationSink,
+}
+
+impl<S> Layer<S> for BuildEventLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        // Inherit the tags from the parent span.
+        let mut visitor = TagVisitor {
+            tags: span
+                .parent()
+                .and_then(|parent| parent.extensions().get::<BuildTags>().cloned())
+                .unwrap_or_default(),
+        };
+        attrs.record(&mut visitor);
+
+        if let Some(phase) = BuildPhase::from_span_name(attrs.metadata().name()) {
+            self.sink.notify(
+                PHASE_STARTED_METHOD_NAME,
+                PhaseStartedParams {
+                    phase,
+                    tags: visitor.tags.clone(),
+                },
+            );
+        }
+
+        span.extensions_mut().insert(visitor.tags);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if !self.sink.is_connected()
+            || (metadata.target() != PROGRESS_TARGET && *metadata.level() > MAX_LOG_LEVEL)
+        {
+            return;
+        }
+
+        let tags = ctx
+            .event_span(event)
+            .and_then(|span| span.extensions().get::<BuildTags>().cloned())
+            .unwrap_or_default();
+
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+
+        if metadata.target() == PROGRESS_TARGET {
+            self.sink.notify(
+                PROGRESS_METHOD_NAME,
+                ProgressParams {
+                    message: visitor.message,
+                    current: visitor.current.unwrap_or_default(),
+                    total: visitor.total.unwrap_or_default(),
+                    tags,
+                },
+            );
+        } else {
+            self.sink.notify(
+                LOG_METHOD_NAME,
+                LogParams {
+                    level: level_name(metadata.level()).to_string(),
+                    message: visitor.message,
+                    tags,
+                },
+            );
+        }
+    }
+}
+
+fn level_name(level: &Level) -> &'static str {
+    match *level {
+        Level::TRACE => "trace",
+        Level::DEBUG => "debug",
+        Level::INFO => "info",
+        Level::WARN => "warn",
+        Level::ERROR => "error",
+    }
+}
+
+/// Records the `output` and `variant_hash` fields of a span.
+struct TagVisitor {
+    tags: BuildTags,
+}
+
+impl Visit for TagVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "output" => self.tags.output = Some(value.to_string()),
+            "variant_hash" => self.tags.variant_hash = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{value:?}"));
+    }
+}
+
+/// Records the message and the progress fields of an event.
+#[derive(Default)]
+pub(crate) struct EventVisitor {
+    pub(crate) message: String,
+    current: Option<u64>,
+    total: Option<u64>,
+}
+
+impl Visit for EventVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "current" => self.current = Some(value),
+            "total" => self.total = Some(value),
+            _ => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        match u64::try_from(value) {
+            Ok(value) => self.record_u64(field, value),
+            Err(_) => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            if !self.message.is_empty() {
+                self.message.push(' ');
+            }
+            self.message
+                .push_str(&format!("{}={value:?}", field.name()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn test_notifications_are_tagged() {
+        let sink = NotificationSink::default();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        sink.connect(sender);
+
+        let subscriber = tracing_subscriber::registry().with(sink.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let _build = build_span("libfoo", "h1234").entered();
+            let _script = tracing::info_span!("Running build script").entered();
+            tracing::info!("compiling");
+            tracing::debug!("too verbose to be sent");
+            tracing::info!(target: PROGRESS_TARGET, current = 1u64, total = 2u64, "half way");
+        });
+
+        let mut notifications = Vec::new();
+        while let Ok(notification) = receiver.try_recv() {
+            notifications.push(serde_json::from_str::<serde_json::Value>(&notification).unwrap());
+        }
+
+        let methods: Vec<_> = notifications
+            .iter()
+            .map(|n| n["method"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                PHASE_STARTED_METHOD_NAME,
+                LOG_METHOD_NAME,
+                PROGRESS_METHOD_NAME
+            ]
+        );
+
+        assert_eq!(notifications[0]["params"]["phase"], "running-script");
+        assert_eq!(notifications[1]["params"]["message"], "compiling");
+        assert_eq!(notifications[1]["params"]["output"], "libfoo");
+        assert_eq!(notifications[1]["params"]["variantHash"], "h1234");
+        assert_eq!(notifications[2]["params"]["current"], 1);
+        assert_eq!(notifications[2]["params"]["total"], 2);
+    }
+}
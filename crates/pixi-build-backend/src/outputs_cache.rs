@@ -0,0 +1,524 @@
+//! A persistent cache for the results of `conda/outputs` requests.
+//!
+//! Determining the outputs of a package requires generating the recipe and
+//! expanding all the variants, which is expensive when it has to be done for
+//! many packages. The result only depends on:
+//!
+//! - the backend itself,
+//! - the project model,
+//! - the backend configuration,
+//! - the variant inputs (the variant configuration, the variant files, the
+//!   channels and the platform),
+//! - the files that are matched by the metadata input globs of the generated
+//!   recipe (e.g. a `pyproject.toml` or `Cargo.toml`).
+//!
+//! The cache stores a hash of each of these next to the result, so when an
+//! entry cannot be used we can tell *why*. The debug files that were written
+//! while determining the outputs are stored as well, they are written again
+//! when the entry is used.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use fs_err as fs;
+use globset::{GlobBuilder, GlobSetBuilder};
+use miette::IntoDiagnostic;
+use pixi_build_types::procedures::conda_outputs::CondaOutputsResult;
+use rattler_digest::{Sha256, Sha256Hash, compute_bytes_digest, compute_file_digest};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+/// The name of the directory in the cache directory that holds the entries.
+const CACHE_DIR_NAME: &str = "conda-outputs-v1";
+
+/// Directories that are not searched for input files unless a glob points
+/// into them explicitly, e.g. `.git/HEAD`.
+const IGNORED_DIRECTORIES: &[&str] = &[".git", ".pixi"];
+
+/// The files that are written to the work directory for debugging, by path
+/// relative to the work directory.
+pub type DebugFiles = BTreeMap<PathBuf, String>;
+
+/// Hashes of everything, except for the input files, that determines the
+/// result of a `conda/outputs` request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OutputsCacheKey {
+    pub backend: String,
+    pub project_model: String,
+    pub config: String,
+    pub variants: String,
+}
+
+impl OutputsCacheKey {
+    /// Returns the names of the components that differ between two keys.
+    fn changed_components(&self, other: &Self) -> Vec<&'static str> {
+        [
+            ("backend", self.backend == other.backend),
+            ("project model", self.project_model == other.project_model),
+            ("backend configuration", self.config == other.config),
+            ("variant inputs", self.variants == other.variants),
+        ]
+        .into_iter()
+        .filter_map(|(name, equal)| (!equal).then_some(name))
+        .collect()
+    }
+}
+
+/// Computes a hash of a serializable value.
+pub fn hash_value<T: Serialize + ?Sized>(value: &T) -> miette::Result<String> {
+    let json = serde_json::to_vec(value).into_diagnostic()?;
+    Ok(format!("{:x}", compute_bytes_digest::<Sha256>(json)))
+}
+
+/// Identifies the backend executable, so that upgrading or rebuilding the
+/// backend invalidates the cache.
+pub fn backend_identity() -> (&'static str, Option<PathBuf>, Option<SystemTime>) {
+    let executable = std::env::current_exe().ok();
+    let modified = executable
+        .as_ref()
+        .and_then(|path| fs::metadata(path).ok())
+        .and_then(|metadata| metadata.modified().ok());
+    (env!("CARGO_PKG_VERSION"), executable, modified)
+}
+
+/// A cache entry on disk.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CacheEntry<R, D> {
+    key: OutputsCacheKey,
+    input_globs: BTreeSet<String>,
+    input_files: String,
+    result: R,
+    debug_files: D,
+}
+
+/// Why a cache entry could not be used.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum CacheMissReason {
+    /// There is no cache entry yet.
+    NoEntry,
+    /// The cache entry could not be read, e.g. because it was written by a
+    /// different version of the backend.
+    InvalidEntry { error: String },
+    /// One or more of the inputs changed.
+    InputsChanged { inputs: Vec<&'static str> },
+    /// One or more files that match the input globs were added, removed or
+    /// modified.
+    InputFilesChanged,
+}
+
+impl fmt::Display for CacheMissReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheMissReason::NoEntry => write!(f, "there is no cache entry"),
+            CacheMissReason::InvalidEntry { error } => {
+                write!(f, "the cache entry is invalid: {error}")
+            }
+            CacheMissReason::InputsChanged { inputs } => {
+                write!(f, "the {} changed", inputs.join(", "))
+            }
+            CacheMissReason::InputFilesChanged => {
+                write!(f, "the files matched by the input globs changed")
+            }
+        }
+    }
+}
+
+/// The result of looking up an entry in the [`OutputsCache`].
+pub enum CacheLookup {
+    Hit {
+        result: CondaOutputsResult,
+        debug_files: DebugFiles,
+    },
+    Miss(CacheMissReason),
+}
+
+/// A cache of `conda/outputs` results for a single source and platform.
+pub struct OutputsCache {
+    entry_path: PathBuf,
+    source_dir: PathBuf,
+}
+
+impl OutputsCache {
+    /// Returns the cache for the given source in the cache directory. Every
+    /// source and platform combination has a single entry, which is
+    /// overwritten when the inputs change.
+    pub fn new(cache_dir: &Path, source_dir: &Path, slot: &str) -> miette::Result<Self> {
+        let entry_name = hash_value(&(source_dir, slot))?;
+        Ok(Self {
+            entry_path: cache_dir
+                .join(CACHE_DIR_NAME)
+                .join(format!("{entry_name}.json")),
+            source_dir: source_dir.to_path_buf(),
+        })
+    }
+
+    /// Looks up the result for the given key.
+    pub fn get(&self, key: &OutputsCacheKey) -> CacheLookup {
+        let contents = match fs::read_to_string(&self.entry_path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return CacheLookup::Miss(CacheMissReason::NoEntry);
+            }
+            Err(err) => {
+                return CacheLookup::Miss(CacheMissReason::InvalidEntry {
+                    error: err.to_string(),
+                });
+            }
+        };
+
+        let entry: CacheEntry<CondaOutputsResult, DebugFiles> =
+            match serde_json::from_str(&contents) {
+                Ok(entry) => entry,
+                Err(err) => {
+                    return CacheLookup::Miss(CacheMissReason::InvalidEntry {
+                        error: err.to_string(),
+                    });
+                }
+            };
+
+        let changed = entry.key.changed_components(key);
+        if !changed.is_empty() {
+            return CacheLookup::Miss(CacheMissReason::InputsChanged { inputs: changed });
+        }
+
+        match hash_input_files(&self.source_dir, &entry.input_globs) {
+            Ok(input_files) if input_files == entry.input_files => CacheLookup::Hit {
+                result: entry.result,
+                debug_files: entry.debug_files,
+            },
+            Ok(_) => CacheLookup::Miss(CacheMissReason::InputFilesChanged),
+            Err(err) => CacheLookup::Miss(CacheMissReason::InvalidEntry {
+                error: err.to_string(),
+            }),
+        }
+    }
+
+    /// Stores the result and the debug files for the given key. The files that
+    /// match the input globs of the result are hashed so that changes to them
+    /// invalidate the entry.
+    pub fn insert(
+        &self,
+        key: OutputsCacheKey,
+        result: &CondaOutputsResult,
+        debug_files: &DebugFiles,
+    ) -> miette::Result<()> {
+        let input_globs = result.input_globs.iter().cloned().collect();
+        let input_files = hash_input_files(&self.source_dir, &input_globs)?;
+        let entry = CacheEntry {
+            key,
+            input_globs,
+            input_files,
+            result,
+            debug_files,
+        };
+
+        if let Some(parent) = self.entry_path.parent() {
+            fs::create_dir_all(parent).into_diagnostic()?;
+        }
+
+        // Write to a temporary file first so that a concurrent reader never
+        // observes a partially written entry.
+        let temp_path = self.entry_path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec(&entry).into_diagnostic()?).into_diagnostic()?;
+        fs::rename(&temp_path, &self.entry_path).into_diagnostic()
+    }
+}
+
+/// Returns the paths, relative to `root`, of all the files that match the
+/// globs. Globs that start with `!` exclude files.
+///
+/// The globs are relative to `root` but may point outside of it, e.g.
+/// `../Cargo.toml` or `../.git/refs/**`. Only the directory a glob is anchored
+/// at, the path before its first wildcard, is searched. `*` does not match
+/// `/`, use `**` to match files in subdirectories.
+pub(crate) fn find_input_files(
+    root: &Path,
+    globs: &BTreeSet<String>,
+) -> miette::Result<Vec<PathBuf>> {
+    let mut files = BTreeSet::new();
+    let mut exclude = GlobSetBuilder::new();
+    let mut patterns_by_base: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for glob in globs {
+        if let Some(glob) = glob.strip_prefix('!') {
+            exclude.add(build_glob(glob)?);
+            continue;
+        }
+        match split_glob(glob) {
+            (path, None) => {
+                if root.join(&path).is_file() {
+                    files.insert(path);
+                }
+            }
+            (base, Some(pattern)) => patterns_by_base.entry(base).or_default().push(pattern),
+        }
+    }
+    let exclude = exclude.build().into_diagnostic()?;
+
+    for (base, patterns) in patterns_by_base {
+        let base_dir = root.join(&base);
+        if !base_dir.is_dir() {
+            continue;
+        }
+
+        let mut include = GlobSetBuilder::new();
+        for pattern in &patterns {
+            include.add(build_glob(pattern)?);
+        }
+        let include = include.build().into_diagnostic()?;
+
+        // Without `**` a pattern cannot match deeper than its number of
+        // components.
+        let max_depth = if patterns.iter().any(|pattern| pattern.contains("**")) {
+            usize::MAX
+        } else {
+            patterns
+                .iter()
+                .map(|pattern| pattern.split('/').count())
+                .max()
+                .unwrap_or_default()
+        };
+
+        let walker = WalkDir::new(&base_dir)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0
+                    || !(entry.file_type().is_dir()
+                        && IGNORED_DIRECTORIES
+                            .iter()
+                            .any(|name| entry.file_name() == *name))
+            });
+        for entry in walker {
+            let entry = entry.into_diagnostic()?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative_path = entry
+                .path()
+                .strip_prefix(&base_dir)
+                .expect("walked paths are inside the base directory");
+            if include.is_match(relative_path) {
+                files.insert(base.join(relative_path));
+            }
+        }
+    }
+
+    Ok(files
+        .into_iter()
+        .filter(|path| !exclude.is_match(path))
+        .collect())
+}
+
+/// Splits a glob into the literal path before its first wildcard and the
+/// pattern that follows, e.g. `../.git/refs/**` into `../.git/refs` and `**`.
+/// The pattern is `None` if the glob does not contain a wildcard.
+fn split_glob(glob: &str) -> (PathBuf, Option<String>) {
+    let components: Vec<&str> = glob
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+    let literal = components
+        .iter()
+        .take_while(|component| !component.contains(['*', '?', '[', '{']))
+        .count();
+    let path = components[..literal].iter().collect();
+    let pattern = (literal < components.len()).then(|| components[literal..].join("/"));
+    (path, pattern)
+}
+
+fn build_glob(glob: &str) -> miette::Result<globset::Glob> {
+    GlobBuilder::new(glob)
+        .literal_separator(true)
+        .build()
+        .into_diagnostic()
+}
+
+/// Computes a hash over the paths and contents of all the files that match
+/// the globs, see [`find_input_files`].
+fn hash_input_files(root: &Path, globs: &BTreeSet<String>) -> miette::Result<String> {
+    let mut files: BTreeMap<PathBuf, Sha256Hash> = BTreeMap::new();
+    for relative_path in find_input_files(root, globs)? {
+        let digest = compute_file_digest::<Sha256>(root.join(&relative_path)).into_diagnostic()?;
+        files.insert(relative_path, digest);
+    }
+
+    hash_value(
+        &files
+            .into_iter()
+            .map(|(path, digest)| (path, format!("{digest:x}")))
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> OutputsCacheKey {
+        OutputsCacheKey {
+            backend: String::from("backend"),
+            project_model: String::from("project-model"),
+            config: String::from("config"),
+            variants: String::from("variants"),
+        }
+    }
+
+    fn result() -> CondaOutputsResult {
+        CondaOutputsResult {
+            outputs: Vec::new(),
+            input_globs: ["pyproject.toml".to_string()].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn test_cache_hit_and_misses() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        fs::write(source_dir.path().join("pyproject.toml"), "version = 1").unwrap();
+
+        let cache = OutputsCache::new(cache_dir.path(), source_dir.path(), "linux-64").unwrap();
+        assert!(matches!(
+            cache.get(&key()),
+            CacheLookup::Miss(CacheMissReason::NoEntry)
+        ));
+
+        let debug_files = DebugFiles::from([(
+            PathBuf::from("debug/recipe.yaml"),
+            String::from("package: {}"),
+        )]);
+        cache.insert(key(), &result(), &debug_files).unwrap();
+        let CacheLookup::Hit {
+            debug_files: cached_debug_files,
+            ..
+        } = cache.get(&key())
+        else {
+            panic!("expected a cache hit");
+        };
+        assert_eq!(cached_debug_files, debug_files);
+
+        // A different configuration is a miss.
+        let other_key = OutputsCacheKey {
+            config: String::from("other-config"),
+            ..key()
+        };
+        let CacheLookup::Miss(reason) = cache.get(&other_key) else {
+            panic!("expected a cache miss");
+        };
+        assert_eq!(
+            reason,
+            CacheMissReason::InputsChanged {
+                inputs: vec!["backend configuration"]
+            }
+        );
+
+        // Modifying an input file is a miss.
+        fs::write(source_dir.path().join("pyproject.toml"), "version = 2").unwrap();
+        assert!(matches!(
+            cache.get(&key()),
+            CacheLookup::Miss(CacheMissReason::InputFilesChanged)
+        ));
+    }
+
+    #[test]
+    fn test_input_files_outside_the_source_dir() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let source_dir = workspace.path().join("crates").join("foo");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::create_dir_all(workspace.path().join(".git").join("refs").join("tags")).unwrap();
+        fs::write(workspace.path().join("Cargo.toml"), "version = \"1.0.0\"").unwrap();
+        fs::write(workspace.path().join(".git").join("HEAD"), "ref: main").unwrap();
+
+        let result = CondaOutputsResult {
+            outputs: Vec::new(),
+            input_globs: [
+                "../../Cargo.toml".to_string(),
+                "../../.git/HEAD".to_string(),
+                "../../.git/refs/**".to_string(),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let cache = OutputsCache::new(cache_dir.path(), &source_dir, "linux-64").unwrap();
+        cache.insert(key(), &result, &DebugFiles::new()).unwrap();
+        assert!(matches!(cache.get(&key()), CacheLookup::Hit { .. }));
+
+        // Bumping the version of the workspace is a miss.
+        fs::write(workspace.path().join("Cargo.toml"), "version = \"1.1.0\"").unwrap();
+        assert!(matches!(
+            cache.get(&key()),
+            CacheLookup::Miss(CacheMissReason::InputFilesChanged)
+        ));
+
+        // So is a new tag.
+        cache.insert(key(), &result, &DebugFiles::new()).unwrap();
+        fs::write(
+            workspace
+                .path()
+                .join(".git")
+                .join("refs")
+                .join("tags")
+                .join("v1.1.0"),
+            "abc1234",
+        )
+        .unwrap();
+        assert!(matches!(
+            cache.get(&key()),
+            CacheLookup::Miss(CacheMissReason::InputFilesChanged)
+        ));
+    }
+
+    #[test]
+    fn test_find_input_files() {
+        let root = tempfile::tempdir().unwrap();
+        for path in [
+            "a.toml",
+            "sub/b.toml",
+            "sub/c.txt",
+            ".git/HEAD",
+            ".pixi/d.toml",
+        ] {
+            let path = root.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let find = |globs: &[&str]| {
+            find_input_files(root.path(), &globs.iter().map(|g| g.to_string()).collect())
+                .unwrap()
+                .into_iter()
+                .map(|path| path.display().to_string().replace('\\', "/"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(find(&["*.toml"]), ["a.toml"]);
+        assert_eq!(find(&["**/*.toml"]), ["a.toml", "sub/b.toml"]);
+        assert_eq!(find(&["**/*.toml", "!sub/**"]), ["a.toml"]);
+        assert_eq!(
+            find(&["sub/*", ".git/HEAD"]),
+            [".git/HEAD", "sub/b.toml", "sub/c.txt"]
+        );
+    }
+
+    #[test]
+    fn test_split_glob() {
+        assert_eq!(
+            split_glob("../.git/refs/**"),
+            (PathBuf::from("../.git/refs"), Some(String::from("**")))
+        );
+        assert_eq!(
+            split_glob("./src/*.rs"),
+            (PathBuf::from("src"), Some(String::from("*.rs")))
+        );
+        assert_eq!(
+            split_glob("pyproject.toml"),
+            (PathBuf::from("pyproject.toml"), None)
+        );
+    }
+}
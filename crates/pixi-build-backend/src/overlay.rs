@@ -0,0 +1,839 @@
+//! Overlays that patch the recipe generated by a backend.
+//!
+//! Backends fully own the recipe they generate. When a generated recipe gets a
+//! detail wrong, an overlay can patch it without switching to a hand-written
+//! recipe. The overlay is a YAML file with the same structure as the
+//! generated recipe that is deep-merged into it:
+//!
+//! ```yaml
+//! build:
+//!   number: 1
+//! requirements:
+//!   host:
+//!     remove: [numpy]
+//!   run:
+//!     add: ["libfoo >=1.2"]
+//! about:
+//!   documentation: ~
+//! ```
+//!
+//! - Mappings are merged key by key.
+//! - Scalars replace the generated value, and `~` (null) removes the key.
+//! - Lists are changed with explicit operations: `replace` replaces the whole
+//!   list, `remove` removes the matching items and `add` appends items.
+//!   Dependencies can be removed by their package name, which also removes
+//!   them from the branches of conditional dependencies, and outputs can be
+//!   removed by the name of their package. A single output cannot be patched
+//!   in place, remove it and add the changed output instead.
+//!
+//! The overlay is read from the file configured with the `overlay` key of the
+//! backend configuration, or from [`DEFAULT_OVERLAY_FILE`] next to the
+//! manifest.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use marked_yaml::Node;
+use miette::{Diagnostic, IntoDiagnostic, NamedSource, SourceSpan};
+use recipe_stage0::recipe::IntermediateRecipe;
+use serde_yaml::{Mapping, Value};
+use thiserror::Error;
+
+use crate::generated_recipe::{BackendConfig, GeneratedRecipe};
+
+/// The overlay that is applied if the configuration does not name one.
+pub const DEFAULT_OVERLAY_FILE: &str = "overlay.yaml";
+
+/// The operations that change a list.
+const LIST_OPERATIONS: [&str; 3] = ["add", "remove", "replace"];
+
+/// The minimal similarity for a key to be suggested for an unknown key.
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// An error in a recipe overlay.
+#[derive(Debug, Error, Diagnostic)]
+#[error("{message}")]
+pub struct OverlayError {
+    message: String,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[label("{label}")]
+    span: Option<SourceSpan>,
+    label: String,
+    #[help]
+    help: Option<String>,
+}
+
+/// A segment of the path to a value in the overlay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// The path to a value in the overlay, e.g. `requirements.host.remove[0]`.
+struct KeyPath<'a>(&'a [Segment]);
+
+impl fmt::Display for KeyPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if idx == 0 => write!(f, "{key}")?,
+                Segment::Key(key) => write!(f, ".{key}")?,
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A parsed recipe overlay.
+pub struct RecipeOverlay {
+    name: String,
+    source: String,
+    value: Value,
+}
+
+impl RecipeOverlay {
+    /// Parses an overlay, `name` is used to refer to it in errors.
+    pub fn parse(name: impl Into<String>, source: impl Into<String>) -> Result<Self, OverlayError> {
+        let mut overlay = Self {
+            name: name.into(),
+            source: source.into(),
+            value: Value::Null,
+        };
+        match serde_yaml::from_str(&overlay.source) {
+            Ok(Value::Null) => overlay.value = Value::Mapping(Mapping::new()),
+            Ok(value @ Value::Mapping(_)) => overlay.value = value,
+            Ok(_) => {
+                return Err(overlay.error(
+                    String::from("a recipe overlay must be a mapping"),
+                    &[],
+                    "expected a mapping",
+                    None,
+                ));
+            }
+            Err(err) => {
+                let span = err
+                    .location()
+                    .map(|location| SourceSpan::new(location.index().into(), 0));
+                return Err(OverlayError {
+                    message: format!("failed to parse the recipe overlay: {err}"),
+                    source_code: overlay.source_code(),
+                    span,
+                    label: String::from("here"),
+                    help: None,
+                });
+            }
+        }
+        Ok(overlay)
+    }
+
+    /// Reads and parses the overlay at `path`.
+    pub fn from_path(path: &Path) -> miette::Result<Self> {
+        let source = fs_err::read_to_string(path).into_diagnostic()?;
+        Ok(Self::parse(path.display().to_string(), source)?)
+    }
+
+    /// Deep-merges the overlay into `recipe`.
+    pub fn apply(&self, recipe: &mut IntermediateRecipe) -> Result<(), OverlayError> {
+        let generated = serde_yaml::to_value(&*recipe).map_err(|err| {
+            self.error(
+                format!("failed to serialize the generated recipe: {err}"),
+                &[],
+                "",
+                None,
+            )
+        })?;
+        let mut merged = generated.clone();
+        self.merge(&mut merged, &self.value, &mut Vec::new())?;
+
+        let patched: IntermediateRecipe =
+            serde_yaml::from_value(merged).map_err(|err| match self.invalid_path(&generated) {
+                Some(path) => self.error(
+                    format!(
+                        "invalid value for `{}` in the recipe overlay: {err}",
+                        KeyPath(&path)
+                    ),
+                    &path,
+                    "invalid value",
+                    None,
+                ),
+                None => self.error(
+                    format!("the recipe overlay produces an invalid recipe: {err}"),
+                    &[],
+                    "",
+                    None,
+                ),
+            })?;
+
+        // Keys that are not part of the recipe are silently dropped when the
+        // recipe is deserialized, report them instead.
+        let serialized = serde_yaml::to_value(&patched).map_err(|err| {
+            self.error(
+                format!("failed to serialize the patched recipe: {err}"),
+                &[],
+                "",
+                None,
+            )
+        })?;
+        self.check_keys(&serialized, &self.value, &mut Vec::new())?;
+
+        *recipe = patched;
+        Ok(())
+    }
+
+    fn merge(
+        &self,
+        target: &mut Value,
+        overlay: &Value,
+        path: &mut Vec<Segment>,
+    ) -> Result<(), OverlayError> {
+        match overlay {
+            Value::Mapping(operations) if is_list_operations(target, operations) => {
+                self.apply_list_operations(target, operations, path)
+            }
+            Value::Mapping(overlay) => {
+                if !target.is_mapping() {
+                    *target = Value::Mapping(Mapping::new());
+                }
+                let target = target.as_mapping_mut().expect("the target is a mapping");
+                for (key, value) in overlay {
+                    path.push(Segment::Key(key_name(key)));
+                    if value.is_null() {
+                        target.remove(key);
+                    } else {
+                        let entry = target.entry(key.clone()).or_insert(Value::Null);
+                        self.merge(entry, value, path)?;
+                    }
+                    path.pop();
+                }
+                Ok(())
+            }
+            Value::Sequence(_) => Err(self.error(
+                format!("cannot set the list `{}` directly", KeyPath(path)),
+                path,
+                "a list",
+                Some(String::from(
+                    "lists are changed with `add`, `remove` or `replace`, e.g. `replace: [...]`",
+                )),
+            )),
+            _ => {
+                *target = overlay.clone();
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the path of the first value of the overlay that makes the
+    /// `generated` recipe invalid on its own. Deserializing a [`Value`] does not
+    /// report where it failed, so the values are tried one at a time instead.
+    fn invalid_path(&self, generated: &Value) -> Option<Vec<Segment>> {
+        let mut parts = Vec::new();
+        split_overlay(
+            generated,
+            &self.value,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut parts,
+        );
+        parts.into_iter().find_map(|(path, part)| {
+            let mut merged = generated.clone();
+            self.merge(&mut merged, &part, &mut Vec::new()).ok()?;
+            serde_yaml::from_value::<IntermediateRecipe>(merged)
+                .is_err()
+                .then_some(path)
+        })
+    }
+
+    fn apply_list_operations(
+        &self,
+        target: &mut Value,
+        operations: &Mapping,
+        path: &mut Vec<Segment>,
+    ) -> Result<(), OverlayError> {
+        let mut items = match std::mem::replace(target, Value::Null) {
+            Value::Sequence(items) => items,
+            _ => Vec::new(),
+        };
+
+        for (operation, _) in operations {
+            let name = key_name(operation);
+            if !LIST_OPERATIONS.contains(&name.as_str()) {
+                path.push(Segment::Key(name.clone()));
+                return Err(self.key_error(
+                    format!("unknown list operation `{name}`"),
+                    path,
+                    "unknown operation",
+                    Some(suggestion_help(&name, &LIST_OPERATIONS)),
+                ));
+            }
+        }
+        if operations.contains_key("replace")
+            && (operations.contains_key("add") || operations.contains_key("remove"))
+        {
+            path.push(Segment::Key(String::from("replace")));
+            return Err(self.key_error(
+                format!(
+                    "`replace` cannot be combined with `add` or `remove` in `{}`",
+                    KeyPath(&path[..path.len() - 1])
+                ),
+                path,
+                "replaces the whole list",
+                Some(String::from("list the items to keep in `replace` instead")),
+            ));
+        }
+
+        if let Some(replacement) = operations.get("replace") {
+            items = self.operation_items("replace", replacement, path)?.to_vec();
+        }
+
+        if let Some(removals) = operations.get("remove") {
+            path.push(Segment::Key(String::from("remove")));
+            let removals = self.operation_items("remove", removals, path)?;
+            for (idx, removal) in removals.iter().enumerate() {
+                if !remove_matching(&mut items, removal) {
+                    path.push(Segment::Index(idx));
+                    let listed = path[..path.len() - 2].to_vec();
+                    return Err(self.error(
+                        format!(
+                            "`{}` does not match any item of `{}`",
+                            display_value(removal),
+                            KeyPath(&listed)
+                        ),
+                        path,
+                        "no matching item",
+                        Some(if items.is_empty() {
+                            String::from("the list is empty")
+                        } else {
+                            format!(
+                                "the list contains {}",
+                                items
+                                    .iter()
+                                    .map(|item| format!("`{}`", display_value(item)))
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            )
+                        }),
+                    ));
+                }
+            }
+            path.pop();
+        }
+
+        if let Some(additions) = operations.get("add") {
+            items.extend_from_slice(self.operation_items("add", additions, path)?);
+        }
+
+        *target = Value::Sequence(items);
+        Ok(())
+    }
+
+    /// Returns the items of a list operation.
+    fn operation_items<'a>(
+        &self,
+        operation: &str,
+        value: &'a Value,
+        path: &[Segment],
+    ) -> Result<&'a [Value], OverlayError> {
+        match value {
+            Value::Sequence(items) => Ok(items),
+            _ => {
+                let mut path = path.to_vec();
+                if path.last() != Some(&Segment::Key(operation.to_string())) {
+                    path.push(Segment::Key(operation.to_string()));
+                }
+                Err(self.error(
+                    format!("`{}` expects a list of items", KeyPath(&path)),
+                    &path,
+                    "expected a list",
+                    None,
+                ))
+            }
+        }
+    }
+
+    /// Checks that every key the overlay sets is part of the patched recipe.
+    fn check_keys(
+        &self,
+        recipe: &Value,
+        overlay: &Value,
+        path: &mut Vec<Segment>,
+    ) -> Result<(), OverlayError> {
+        let (Value::Mapping(recipe), Value::Mapping(overlay)) = (recipe, overlay) else {
+            return Ok(());
+        };
+        for (key, value) in overlay {
+            if value.is_null() {
+                continue;
+            }
+            path.push(Segment::Key(key_name(key)));
+            let Some(recipe_value) = recipe.get(key) else {
+                let candidates: Vec<String> = recipe.keys().map(key_name).collect();
+                let name = key_name(key);
+                return Err(self.key_error(
+                    format!("unknown key `{}` in the recipe overlay", KeyPath(path)),
+                    path,
+                    "unknown key",
+                    Some(suggestion_help(&name, &candidates)),
+                ));
+            };
+            if !recipe_value.is_sequence() {
+                self.check_keys(recipe_value, value, path)?;
+            }
+            path.pop();
+        }
+        Ok(())
+    }
+
+    fn source_code(&self) -> NamedSource<String> {
+        NamedSource::new(&self.name, self.source.clone())
+    }
+
+    /// Returns an error that points at the value at `path`.
+    fn error(
+        &self,
+        message: String,
+        path: &[Segment],
+        label: &str,
+        help: Option<String>,
+    ) -> OverlayError {
+        OverlayError {
+            message,
+            source_code: self.source_code(),
+            span: self.span(path, false),
+            label: label.to_string(),
+            help,
+        }
+    }
+
+    /// Returns an error that points at the key of the value at `path`.
+    fn key_error(
+        &self,
+        message: String,
+        path: &[Segment],
+        label: &str,
+        help: Option<String>,
+    ) -> OverlayError {
+        OverlayError {
+            span: self.span(path, true),
+            ..self.error(message, path, label, help)
+        }
+    }
+
+    /// Returns the span of the value at `path`, or of its key if `prefer_key`
+    /// is set or the value is not a scalar.
+    fn span(&self, path: &[Segment], prefer_key: bool) -> Option<SourceSpan> {
+        if path.is_empty() {
+            return None;
+        }
+        let root = marked_yaml::parse_yaml(0, &self.source).ok()?;
+        let mut node = &root;
+        let mut key_span = None;
+        for segment in path {
+            node = match (segment, node) {
+                (Segment::Key(name), Node::Mapping(mapping)) => {
+                    let (key, value) = mapping
+                        .iter()
+                        .find(|(key, _)| key.as_str() == name.as_str())?;
+                    key_span = self.marker_span(key.span(), key.as_str().len());
+                    value
+                }
+                (Segment::Index(index), Node::Sequence(sequence)) => {
+                    key_span = None;
+                    sequence.get(*index)?
+                }
+                _ => return None,
+            };
+        }
+        match node {
+            _ if prefer_key && key_span.is_some() => key_span,
+            Node::Scalar(scalar) => self
+                .marker_span(scalar.span(), scalar.as_str().len())
+                .or(key_span),
+            _ => key_span.or_else(|| self.marker_span(node.span(), 1)),
+        }
+    }
+
+    /// Converts the start of a span of the YAML parser into a [`SourceSpan`].
+    fn marker_span(&self, span: &marked_yaml::Span, len: usize) -> Option<SourceSpan> {
+        let start = span.start()?;
+        let line_offset: usize = self
+            .source
+            .split_inclusive('\n')
+            .take(start.line().saturating_sub(1))
+            .map(str::len)
+            .sum();
+        let column_offset: usize = self.source[line_offset..]
+            .chars()
+            .take(start.column().saturating_sub(1))
+            .map(char::len_utf8)
+            .sum();
+        let offset = line_offset + column_offset;
+        Some(SourceSpan::new(
+            offset.into(),
+            len.min(self.source.len() - offset),
+        ))
+    }
+}
+
+/// Applies the recipe overlay to the generated recipe. The overlay is the file
+/// configured with the `overlay` key, or [`DEFAULT_OVERLAY_FILE`] in
+/// `manifest_dir` if it exists.
+///
+/// The overlay is added to the input globs of the recipe, relative to
+/// `source_dir`, so that changing it regenerates the recipe.
+pub fn apply_overlay<C: BackendConfig>(
+    config: &C,
+    source_dir: &Path,
+    manifest_dir: &Path,
+    generated_recipe: &mut GeneratedRecipe,
+) -> miette::Result<()> {
+    let (path, required) = match config.overlay() {
+        Some(overlay) => (manifest_dir.join(overlay), true),
+        None => (manifest_dir.join(DEFAULT_OVERLAY_FILE), false),
+    };
+
+    let glob = input_glob(source_dir, &path);
+    generated_recipe.metadata_input_globs.insert(glob.clone());
+    generated_recipe.build_input_globs.insert(glob);
+
+    if !required && !path.is_file() {
+        return Ok(());
+    }
+    if !path.is_file() {
+        miette::bail!(
+            help = "the path of the `overlay` is relative to the directory of the manifest",
+            "the recipe overlay `{}` does not exist",
+            path.display()
+        );
+    }
+
+    let overlay = RecipeOverlay::from_path(&path)?;
+    overlay.apply(&mut generated_recipe.recipe)?;
+    Ok(())
+}
+
+/// Returns the glob that matches `path`, relative to `source_dir`.
+fn input_glob(source_dir: &Path, path: &Path) -> String {
+    pathdiff::diff_paths(path, source_dir)
+        .unwrap_or_else(|| PathBuf::from(path))
+        .display()
+        .to_string()
+        .replace('\\', "/")
+}
+
+/// Returns whether `operations` change the list `target`. Values that are
+/// not set yet are treated as lists if only list operations are given.
+fn is_list_operations(target: &Value, operations: &Mapping) -> bool {
+    match target {
+        Value::Sequence(_) => true,
+        Value::Null => {
+            !operations.is_empty()
+                && operations
+                    .keys()
+                    .all(|key| LIST_OPERATIONS.contains(&key_name(key).as_str()))
+        }
+        _ => false,
+    }
+}
+
+/// Splits `overlay` into overlays that each set a single value, together with
+/// the path of that value. Items that are added to a list are split as well.
+fn split_overlay(
+    target: &Value,
+    overlay: &Value,
+    path: &mut Vec<Segment>,
+    keys: &mut Vec<Value>,
+    parts: &mut Vec<(Vec<Segment>, Value)>,
+) {
+    let Value::Mapping(overlay) = overlay else {
+        return;
+    };
+    if is_list_operations(target, overlay) {
+        for operation in ["add", "replace"] {
+            let Some(Value::Sequence(items)) = overlay.get(operation) else {
+                continue;
+            };
+            path.push(Segment::Key(operation.to_string()));
+            for (idx, item) in items.iter().enumerate() {
+                path.push(Segment::Index(idx));
+                let part = Mapping::from_iter([(
+                    Value::from(operation),
+                    Value::Sequence(vec![item.clone()]),
+                )]);
+                parts.push((path.clone(), nest(keys, Value::Mapping(part))));
+                path.pop();
+            }
+            path.pop();
+        }
+        return;
+    }
+    for (key, value) in overlay {
+        let target = target
+            .as_mapping()
+            .and_then(|target| target.get(key))
+            .unwrap_or(&Value::Null);
+        path.push(Segment::Key(key_name(key)));
+        keys.push(key.clone());
+        if value.is_mapping() {
+            split_overlay(target, value, path, keys, parts);
+        } else {
+            parts.push((path.clone(), nest(keys, value.clone())));
+        }
+        keys.pop();
+        path.pop();
+    }
+}
+
+/// Returns an overlay that only sets `value` at `keys`.
+fn nest(keys: &[Value], value: Value) -> Value {
+    keys.iter().rev().fold(value, |value, key| {
+        Value::Mapping(Mapping::from_iter([(key.clone(), value)]))
+    })
+}
+
+/// Removes the items of a list that are matched by `pattern` and returns
+/// whether any item was removed. The dependencies in the branches of a
+/// conditional item are removed as well, and the conditional is removed once
+/// both of its branches are empty.
+fn remove_matching(items: &mut Vec<Value>, pattern: &Value) -> bool {
+    let mut removed = false;
+    items.retain_mut(|item| {
+        if matches_item(item, pattern) {
+            removed = true;
+            return false;
+        }
+        let Some(conditional) = item.as_mapping_mut().filter(|item| item.contains_key("if")) else {
+            return true;
+        };
+        let mut matched = false;
+        let mut empty = true;
+        for branch in ["then", "else"] {
+            let Some(value) = conditional.get_mut(branch) else {
+                continue;
+            };
+            let mut branch_items = match std::mem::replace(value, Value::Null) {
+                Value::Sequence(items) => items,
+                Value::Null => Vec::new(),
+                item => vec![item],
+            };
+            matched |= remove_matching(&mut branch_items, pattern);
+            empty &= branch_items.is_empty();
+            *value = Value::Sequence(branch_items);
+        }
+        removed |= matched;
+        !(matched && empty)
+    });
+    removed
+}
+
+/// Returns whether `item` of a list is matched by `pattern`. Besides equal
+/// items, a package name matches the dependencies on that package, e.g.
+/// `numpy` matches `numpy >=1.20`, and the output that builds the package.
+fn matches_item(item: &Value, pattern: &Value) -> bool {
+    if item == pattern {
+        return true;
+    }
+    let Some(name) = pattern.as_str() else {
+        return false;
+    };
+    if let Some(output) = item.get("package").and_then(|package| package.get("name")) {
+        return output.as_str() == Some(name);
+    }
+    let Some(item) = item.as_str() else {
+        return false;
+    };
+    item.strip_prefix(name)
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_whitespace() || "<>=!~[".contains(c)))
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        key => display_value(key),
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => serde_yaml::to_string(value)
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Suggests a candidate that is similar to `key`, or lists all candidates.
+fn suggestion_help(key: &str, candidates: &[impl AsRef<str>]) -> String {
+    let suggestion = candidates
+        .iter()
+        .map(|candidate| (strsim::jaro_winkler(key, candidate.as_ref()), candidate))
+        .filter(|(similarity, _)| *similarity >= SUGGESTION_THRESHOLD)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b));
+    match suggestion {
+        Some((_, suggestion)) => format!("did you mean `{}`?", suggestion.as_ref()),
+        None => format!(
+            "expected one of {}",
+            candidates
+                .iter()
+                .map(|candidate| format!("`{}`", candidate.as_ref()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use recipe_stage0::{
+        matchspec::PackageDependency,
+        recipe::{ConditionalList, Item, Output, Value as RecipeValue},
+    };
+
+    use super::*;
+
+    fn recipe() -> IntermediateRecipe {
+        let mut recipe = IntermediateRecipe::default();
+        recipe.requirements.host = vec![
+            "python".parse().unwrap(),
+            "numpy >=1.20".parse().unwrap(),
+            "pip".parse().unwrap(),
+        ];
+        recipe.requirements.run = vec!["python".parse().unwrap()];
+        recipe
+    }
+
+    fn requirements(items: &ConditionalList<PackageDependency>) -> Vec<String> {
+        items.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_overlay_is_merged_into_the_recipe() {
+        let overlay = RecipeOverlay::parse(
+            "overlay.yaml",
+            r#"
+build:
+  number: 3
+requirements:
+  host:
+    remove: [numpy, pip]
+  run:
+    add: ["libfoo >=1.2"]
+  build:
+    replace: [cmake]
+about:
+  license: MIT
+"#,
+        )
+        .unwrap();
+
+        let mut recipe = recipe();
+        overlay.apply(&mut recipe).unwrap();
+
+        assert_eq!(
+            recipe.build.number.map(|number| number.to_string()),
+            Some(String::from("3"))
+        );
+        assert_eq!(requirements(&recipe.requirements.host), ["python"]);
+        assert_eq!(
+            requirements(&recipe.requirements.run),
+            ["python", "libfoo >=1.2"]
+        );
+        assert_eq!(requirements(&recipe.requirements.build), ["cmake"]);
+        assert_eq!(
+            recipe.about.and_then(|about| about.license),
+            Some(RecipeValue::Concrete(String::from("MIT")))
+        );
+    }
+
+    #[test]
+    fn test_overlay_errors_point_at_the_overlay() {
+        let source = "requirements:\n  host:\n    remove: [scipy]\n";
+        let error = RecipeOverlay::parse("overlay.yaml", source)
+            .unwrap()
+            .apply(&mut recipe())
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "`scipy` does not match any item of `requirements.host`"
+        );
+        let span = error.span.unwrap();
+        assert_eq!(&source[span.offset()..][..span.len()], "scipy");
+
+        let source = "build:\n  numbr: 3\n";
+        let error = RecipeOverlay::parse("overlay.yaml", source)
+            .unwrap()
+            .apply(&mut recipe())
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "unknown key `build.numbr` in the recipe overlay"
+        );
+        assert_eq!(error.help.as_deref(), Some("did you mean `number`?"));
+        let span = error.span.unwrap();
+        assert_eq!(&source[span.offset()..][..span.len()], "numbr");
+
+        let error = RecipeOverlay::parse("overlay.yaml", "requirements:\n  run: [foo]\n")
+            .unwrap()
+            .apply(&mut recipe())
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "cannot set the list `requirements.run` directly"
+        );
+
+        let source = "build:\n  number: 3\n  noarch: foo\n";
+        let error = RecipeOverlay::parse("overlay.yaml", source)
+            .unwrap()
+            .apply(&mut recipe())
+            .unwrap_err();
+        assert!(
+            error
+                .message
+                .starts_with("invalid value for `build.noarch` in the recipe overlay"),
+            "{}",
+            error.message
+        );
+        let span = error.span.unwrap();
+        assert_eq!(&source[span.offset()..][..span.len()], "foo");
+    }
+
+    #[test]
+    fn test_overlay_removes_conditional_dependencies_and_outputs() {
+        let mut recipe = recipe();
+        recipe.requirements.run.push(Item::new_from_conditional(
+            String::from("win"),
+            vec!["numpy".parse().unwrap()],
+            Vec::new(),
+        ));
+        recipe.requirements.run.push(Item::new_from_conditional(
+            String::from("unix"),
+            vec!["numpy".parse().unwrap()],
+            vec!["pip".parse().unwrap()],
+        ));
+        recipe.outputs = vec![Output::new("foo"), Output::new("bar")];
+
+        let overlay = RecipeOverlay::parse(
+            "overlay.yaml",
+            "requirements:\n  run:\n    remove: [numpy]\noutputs:\n  remove: [foo]\n",
+        )
+        .unwrap();
+        overlay.apply(&mut recipe).unwrap();
+
+        assert_eq!(
+            requirements(&recipe.requirements.run),
+            ["python", "if unix then [] else pip"]
+        );
+        assert_eq!(
+            recipe
+                .outputs
+                .iter()
+                .map(|output| output.package.to_string())
+                .collect::<Vec<_>>(),
+            ["bar"]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Running the tests of a recipe against a package that was just built.
+
+use std::path::{Path, PathBuf};
+
+use miette::Diagnostic;
+use rattler_build::{
+    metadata::{Debug, Output},
+    package_test::{TestConfiguration, run_test},
+    tool_configuration::Configuration,
+};
+use rattler_conda_types::ChannelUrl;
+use thiserror::Error;
+
+/// The tests of a built package failed.
+///
+/// This error is reported back to the frontend with a dedicated JSON-RPC
+/// error code, see [`crate::server::PACKAGE_TEST_FAILED_ERROR_CODE`].
+#[derive(Debug, Error, Diagnostic)]
+#[error("the tests of '{package}' failed")]
+pub struct PackageTestError {
+    /// The name of the package whose tests failed.
+    pub package: String,
+    /// The location of the package that was tested.
+    pub package_file: PathBuf,
+    #[source]
+    pub source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+/// Runs all the tests of the recipe of `output` against the built package.
+///
+/// The test environments are solved against the given channels, the package
+/// itself is made available through a temporary local channel.
+pub async fn run_package_tests(
+    output: &Output,
+    package_file: &Path,
+    channels: Vec<ChannelUrl>,
+    tool_configuration: &Configuration,
+) -> Result<(), PackageTestError> {
+    let build_configuration = &output.build_configuration;
+    let test_configuration = TestConfiguration {
+        test_prefix: build_configuration.directories.build_dir.join("test"),
+        target_platform: Some(build_configuration.target_platform),
+        host_platform: Some(build_configuration.host_platform.clone()),
+        current_platform: build_configuration.build_platform.clone(),
+        keep_test_prefix: false,
+        test_index: None,
+        channels,
+        channel_priority: build_configuration.channel_priority,
+        solve_strategy: build_configuration.solve_strategy,
+        tool_configuration: tool_configuration.clone(),
+        output_dir: build_configuration.directories.output_dir.clone(),
+        debug: Debug::new(false),
+        exclude_newer: None,
+    };
+
+    run_test(package_file, &test_configuration, None)
+        .await
+        .map_err(|err| PackageTestError {
+            package: output.name().as_normalized().to_string(),
+            package_file: package_file.to_path_buf(),
+            source: Box::new(err),
+        })
+}
@@ -0,0 +1,140 @@
+//! Options that control how a built package is archived.
+//!
+//! Every backend exposes these options through its configuration (see
+//! [`crate::generated_recipe::BackendConfig::packaging`]) and they are used to
+//! construct the [`PackagingSettings`] of the rattler-build build
+//! configuration.
+
+use rattler_build::types::PackagingSettings;
+use rattler_conda_types::{compression_level::CompressionLevel, package::ArchiveType};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The archive format of a built package.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub enum PackageArchiveType {
+    /// The `.conda` format.
+    #[default]
+    #[serde(rename = "conda")]
+    Conda,
+    /// The legacy `.tar.bz2` format.
+    #[serde(rename = "tar.bz2")]
+    TarBz2,
+}
+
+impl From<PackageArchiveType> for ArchiveType {
+    fn from(value: PackageArchiveType) -> Self {
+        match value {
+            PackageArchiveType::Conda => ArchiveType::Conda,
+            PackageArchiveType::TarBz2 => ArchiveType::TarBz2,
+        }
+    }
+}
+
+/// The compression level of a built package, either one of the named levels
+/// or a number that is passed on to the compressor of the archive format
+/// (`-7` to `22` for `.conda`, `1` to `9` for `.tar.bz2`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum PackageCompressionLevel {
+    Named(NamedCompressionLevel),
+    Numeric(i32),
+}
+
+impl Default for PackageCompressionLevel {
+    fn default() -> Self {
+        Self::Named(NamedCompressionLevel::Fast)
+    }
+}
+
+/// A named compression level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum NamedCompressionLevel {
+    /// The fastest compression, which is best suited for local development.
+    Fast,
+    /// The default compression level of the archive format.
+    Default,
+    /// The maximum compression, which is best suited for releases.
+    Max,
+}
+
+impl From<PackageCompressionLevel> for CompressionLevel {
+    fn from(value: PackageCompressionLevel) -> Self {
+        match value {
+            PackageCompressionLevel::Named(NamedCompressionLevel::Fast) => CompressionLevel::Lowest,
+            PackageCompressionLevel::Named(NamedCompressionLevel::Default) => {
+                CompressionLevel::Default
+            }
+            PackageCompressionLevel::Named(NamedCompressionLevel::Max) => CompressionLevel::Highest,
+            PackageCompressionLevel::Numeric(level) => CompressionLevel::Numeric(level),
+        }
+    }
+}
+
+/// The resolved packaging options of a backend configuration.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PackagingOptions {
+    /// The archive format of the package.
+    pub archive_type: PackageArchiveType,
+    /// The compression level of the package.
+    pub compression_level: PackageCompressionLevel,
+    /// Whether the rendered recipe is stored inside the package.
+    pub store_recipe: bool,
+}
+
+impl PackagingOptions {
+    /// Resolves the packaging options from the optional values of a backend
+    /// configuration, falling back to the defaults for unset values.
+    pub fn from_config(
+        archive_type: Option<PackageArchiveType>,
+        compression_level: Option<PackageCompressionLevel>,
+        store_recipe: Option<bool>,
+    ) -> Self {
+        Self {
+            archive_type: archive_type.unwrap_or_default(),
+            compression_level: compression_level.unwrap_or_default(),
+            store_recipe: store_recipe.unwrap_or(false),
+        }
+    }
+
+    /// Returns the rattler-build packaging settings.
+    pub fn packaging_settings(&self) -> PackagingSettings {
+        PackagingSettings::from_args(self.archive_type.into(), self.compression_level.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_deserialize_packaging_options() {
+        assert_eq!(
+            serde_json::from_value::<PackageArchiveType>(json!("tar.bz2")).unwrap(),
+            PackageArchiveType::TarBz2
+        );
+        assert_eq!(
+            serde_json::from_value::<PackageCompressionLevel>(json!("max")).unwrap(),
+            PackageCompressionLevel::Named(NamedCompressionLevel::Max)
+        );
+        assert_eq!(
+            serde_json::from_value::<PackageCompressionLevel>(json!(19)).unwrap(),
+            PackageCompressionLevel::Numeric(19)
+        );
+        assert!(serde_json::from_value::<PackageCompressionLevel>(json!("maximum")).is_err());
+    }
+
+    #[test]
+    fn test_default_packaging_options() {
+        let options = PackagingOptions::from_config(None, None, None);
+        assert_eq!(options.archive_type, PackageArchiveType::Conda);
+        assert_eq!(
+            options.compression_level,
+            PackageCompressionLevel::Named(NamedCompressionLevel::Fast)
+        );
+        assert!(!options.store_recipe);
+    }
+}
@@ -1,9 +1,13 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
 use pixi_build_types::procedures::conda_build_v1::{CondaBuildV1Params, CondaBuildV1Result};
 use pixi_build_types::procedures::conda_outputs::{CondaOutputsParams, CondaOutputsResult};
 use pixi_build_types::procedures::{
     initialize::{InitializeParams, InitializeResult},
     negotiate_capabilities::{NegotiateCapabilitiesParams, NegotiateCapabilitiesResult},
 };
+use rattler_conda_types::{ChannelUrl, Platform};
+use serde::Serialize;
 
 /// A trait that is used to instantiate a new protocol connection
 /// and endpoint that can handle the RPC calls.
@@ -21,6 +25,12 @@ pub trait ProtocolInstantiator: Send + Sync + 'static {
         &self,
         params: InitializeParams,
     ) -> miette::Result<(Box<dyn Protocol + Send + Sync + 'static>, InitializeResult)>;
+
+    /// Returns the JSON Schema of the backend configuration, if the backend
+    /// provides one.
+    fn config_schema() -> Option<serde_json::Value> {
+        None
+    }
 }
 
 /// A trait that defines the protocol for a pixi build backend.
@@ -43,4 +53,41 @@ pub trait Protocol {
     ) -> miette::Result<CondaBuildV1Result> {
         unimplemented!("conda_build_v1 not implemented");
     }
+
+    /// Generates the recipe and determines its outputs without building
+    /// anything. This is used by the `generate-recipe` subcommand.
+    async fn generate_recipe(
+        &self,
+        _params: GenerateRecipeParams,
+    ) -> miette::Result<GenerateRecipeResult> {
+        miette::bail!("this backend does not support generating a recipe");
+    }
+}
+
+/// The parameters of [`Protocol::generate_recipe`].
+#[derive(Debug, Clone)]
+pub struct GenerateRecipeParams {
+    pub host_platform: Platform,
+    pub channels: Vec<ChannelUrl>,
+    pub variant_configuration: Option<BTreeMap<String, Vec<String>>>,
+    pub variant_files: Option<Vec<PathBuf>>,
+}
+
+/// The result of [`Protocol::generate_recipe`].
+#[derive(Debug, Serialize)]
+pub struct GenerateRecipeResult {
+    /// The generated recipe as YAML.
+    pub recipe: String,
+    /// The outputs that are discovered by expanding the variants.
+    pub outputs: Vec<GeneratedOutput>,
+}
+
+/// An output of a generated recipe.
+#[derive(Debug, Serialize)]
+pub struct GeneratedOutput {
+    pub name: String,
+    pub version: String,
+    pub build: String,
+    pub subdir: Platform,
+    pub variant: BTreeMap<String, String>,
 }
@@ -0,0 +1,311 @@
+//! Replaying the requests that were recorded in a debug directory.
+//!
+//! The server writes the parameters of the `initialize` request and of every
+//! procedure, together with its response, to the debug directory in the work
+//! directory of the procedure. Replaying these reproduces a build without
+//! running pixi.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use miette::{Context, IntoDiagnostic};
+use pixi_build_types::{
+    ProjectModelV1,
+    procedures::{
+        conda_build_v1::CondaBuildV1Params, conda_outputs::CondaOutputsParams,
+        initialize::InitializeParams,
+    },
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    consts::{
+        CONDA_BUILD_V1_ERROR_FILE, CONDA_BUILD_V1_PARAMS_FILE, CONDA_BUILD_V1_RESPONSE_FILE,
+        CONDA_OUTPUTS_ERROR_FILE, CONDA_OUTPUTS_PARAMS_FILE, CONDA_OUTPUTS_RESPONSE_FILE,
+        INITIALIZE_PARAMS_FILE, PROJECT_MODEL_FILE,
+    },
+    protocol::Protocol,
+    server::convert_error,
+};
+
+/// A procedure that can be replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReplayProcedure {
+    CondaOutputs,
+    CondaBuildV1,
+}
+
+impl ReplayProcedure {
+    fn params_file(self) -> &'static str {
+        match self {
+            ReplayProcedure::CondaOutputs => CONDA_OUTPUTS_PARAMS_FILE,
+            ReplayProcedure::CondaBuildV1 => CONDA_BUILD_V1_PARAMS_FILE,
+        }
+    }
+
+    fn response_file(self) -> &'static str {
+        match self {
+            ReplayProcedure::CondaOutputs => CONDA_OUTPUTS_RESPONSE_FILE,
+            ReplayProcedure::CondaBuildV1 => CONDA_BUILD_V1_RESPONSE_FILE,
+        }
+    }
+
+    fn error_file(self) -> &'static str {
+        match self {
+            ReplayProcedure::CondaOutputs => CONDA_OUTPUTS_ERROR_FILE,
+            ReplayProcedure::CondaBuildV1 => CONDA_BUILD_V1_ERROR_FILE,
+        }
+    }
+}
+
+/// The parameters of a recorded procedure.
+#[derive(Debug)]
+pub enum RecordedProcedure {
+    CondaOutputs(CondaOutputsParams),
+    CondaBuildV1(CondaBuildV1Params),
+}
+
+/// The requests that were recorded in a debug directory.
+#[derive(Debug)]
+pub struct Recording {
+    pub initialize: InitializeParams,
+    pub procedure: RecordedProcedure,
+}
+
+impl Recording {
+    /// Loads the recording from a debug directory.
+    ///
+    /// If no procedure is given, `conda/build_v1` is replayed if it was
+    /// recorded and `conda/outputs` otherwise. The manifest overrides the
+    /// recorded manifest path, and is required for debug directories that were
+    /// written before the `initialize` parameters were recorded.
+    pub fn load(
+        debug_dir: &Path,
+        procedure: Option<ReplayProcedure>,
+        manifest: Option<PathBuf>,
+    ) -> miette::Result<Self> {
+        let procedure = procedure.unwrap_or_else(|| {
+            if debug_dir.join(CONDA_BUILD_V1_PARAMS_FILE).is_file() {
+                ReplayProcedure::CondaBuildV1
+            } else {
+                ReplayProcedure::CondaOutputs
+            }
+        });
+
+        let initialize_path = debug_dir.join(INITIALIZE_PARAMS_FILE);
+        let initialize = if initialize_path.is_file() {
+            let mut initialize: InitializeParams = read_json(&initialize_path)?;
+            if let Some(manifest) = manifest {
+                initialize.manifest_path = manifest;
+            }
+            initialize
+        } else {
+            let manifest = manifest.ok_or_else(|| {
+                miette::miette!(
+                    "{} does not contain {INITIALIZE_PARAMS_FILE}, pass the manifest of the package with --manifest",
+                    debug_dir.display()
+                )
+            })?;
+            let project_model: ProjectModelV1 = read_json(&debug_dir.join(PROJECT_MODEL_FILE))?;
+            InitializeParams {
+                workspace_root: None,
+                source_dir: None,
+                manifest_path: manifest,
+                project_model: Some(project_model.into()),
+                configuration: None,
+                target_configuration: None,
+                cache_directory: None,
+            }
+        };
+
+        let params_path = debug_dir.join(procedure.params_file());
+        let procedure = match procedure {
+            ReplayProcedure::CondaOutputs => {
+                RecordedProcedure::CondaOutputs(read_json(&params_path)?)
+            }
+            ReplayProcedure::CondaBuildV1 => {
+                RecordedProcedure::CondaBuildV1(read_json(&params_path)?)
+            }
+        };
+
+        Ok(Self {
+            initialize,
+            procedure,
+        })
+    }
+}
+
+impl RecordedProcedure {
+    /// The kind of the procedure.
+    pub fn kind(&self) -> ReplayProcedure {
+        match self {
+            RecordedProcedure::CondaOutputs(_) => ReplayProcedure::CondaOutputs,
+            RecordedProcedure::CondaBuildV1(_) => ReplayProcedure::CondaBuildV1,
+        }
+    }
+
+    /// Issues the procedure in `work_directory` and returns its response as
+    /// JSON.
+    ///
+    /// The recorded work directory contains the debug directory that is
+    /// replayed, so the procedure is issued in a different work directory to
+    /// leave the recording intact. For the same reason the built package is
+    /// written to the work directory instead of the recorded output directory.
+    pub async fn replay(
+        self,
+        protocol: &(dyn Protocol + Send + Sync),
+        work_directory: &Path,
+    ) -> miette::Result<Value> {
+        match self {
+            RecordedProcedure::CondaOutputs(mut params) => {
+                params.work_directory = work_directory.to_path_buf();
+                serde_json::to_value(protocol.conda_outputs(params).await?).into_diagnostic()
+            }
+            RecordedProcedure::CondaBuildV1(mut params) => {
+                params.work_directory = work_directory.to_path_buf();
+                params.output_directory = None;
+                serde_json::to_value(protocol.conda_build_v1(params).await?).into_diagnostic()
+            }
+        }
+    }
+}
+
+/// Returns the recorded response of a procedure, or the recorded error if the
+/// procedure failed.
+pub fn recorded_response(debug_dir: &Path, procedure: ReplayProcedure) -> miette::Result<Value> {
+    let response_path = debug_dir.join(procedure.response_file());
+    if response_path.is_file() {
+        read_json(&response_path)
+    } else {
+        read_json(&debug_dir.join(procedure.error_file()))
+    }
+}
+
+/// Converts the result of a replayed procedure to JSON in the same way the
+/// server does, so that it can be compared to the recorded response.
+pub fn response_to_json(result: miette::Result<Value>) -> Value {
+    match result {
+        Ok(response) => response,
+        Err(err) => serde_json::to_value(convert_error(err)).expect("failed to convert to json"),
+    }
+}
+
+/// A value that differs between the recorded and the replayed response.
+#[derive(Debug, PartialEq)]
+pub struct JsonDifference {
+    /// The path to the value, e.g. `$.outputs[0].metadata.build`.
+    pub path: String,
+    pub recorded: Option<Value>,
+    pub replayed: Option<Value>,
+}
+
+impl fmt::Display for JsonDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = |value: &Option<Value>| {
+            value
+                .as_ref()
+                .map_or_else(|| String::from("<missing>"), Value::to_string)
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path,
+            format(&self.recorded),
+            format(&self.replayed)
+        )
+    }
+}
+
+/// Returns the values that differ between two JSON documents.
+pub fn diff_json(recorded: &Value, replayed: &Value) -> Vec<JsonDifference> {
+    let mut differences = Vec::new();
+    diff_json_at(
+        String::from("$"),
+        Some(recorded),
+        Some(replayed),
+        &mut differences,
+    );
+    differences
+}
+
+fn diff_json_at(
+    path: String,
+    recorded: Option<&Value>,
+    replayed: Option<&Value>,
+    differences: &mut Vec<JsonDifference>,
+) {
+    match (recorded, replayed) {
+        (Some(Value::Object(recorded)), Some(Value::Object(replayed))) => {
+            let mut keys = recorded.keys().chain(replayed.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                diff_json_at(
+                    format!("{path}.{key}"),
+                    recorded.get(key),
+                    replayed.get(key),
+                    differences,
+                );
+            }
+        }
+        (Some(Value::Array(recorded)), Some(Value::Array(replayed))) => {
+            for index in 0..recorded.len().max(replayed.len()) {
+                diff_json_at(
+                    format!("{path}[{index}]"),
+                    recorded.get(index),
+                    replayed.get(index),
+                    differences,
+                );
+            }
+        }
+        (recorded, replayed) if recorded != replayed => differences.push(JsonDifference {
+            path,
+            recorded: recorded.cloned(),
+            replayed: replayed.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> miette::Result<T> {
+    let contents = fs_err::read_to_string(path).into_diagnostic()?;
+    serde_json::from_str(&contents)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_json() {
+        let recorded = json!({
+            "outputs": [{ "name": "foo", "build": "h1234_0" }],
+            "inputGlobs": ["pixi.toml"],
+        });
+        let replayed = json!({
+            "outputs": [{ "name": "foo", "build": "h5678_0" }, { "name": "bar" }],
+            "inputGlobs": ["pixi.toml"],
+        });
+
+        let differences = diff_json(&recorded, &replayed)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            differences,
+            [
+                r#"$.outputs[0].build: "h1234_0" -> "h5678_0""#,
+                r#"$.outputs[1]: <missing> -> {"name":"bar"}"#,
+            ]
+        );
+        assert!(diff_json(&recorded, &recorded).is_empty());
+    }
+}
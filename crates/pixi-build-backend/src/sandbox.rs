@@ -0,0 +1,330 @@
+//! Running build scripts in the sandbox of rattler-build.
+//!
+//! The sandbox is opt-in through the `sandbox` table of the backend
+//! configuration. It restricts the build script to the build directories, the
+//! paths that are read by every build and the paths that are listed in the
+//! configuration, and it blocks network access unless it is allowed.
+//!
+//! The sandbox itself does not report what it denied, the build script simply
+//! fails. Therefore the output of a sandboxed build is captured (see
+//! [`SandboxLogLayer`]) and searched for the errors that denied accesses cause
+//! when the build fails.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    future::Future,
+    path::PathBuf,
+    sync::LazyLock,
+};
+
+use itertools::Itertools;
+use miette::Diagnostic;
+use parking_lot::Mutex;
+use rattler_build::script::{SandboxArguments, SandboxConfiguration};
+use rattler_conda_types::Platform;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{Event, Instrument, Subscriber, span};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::notifications::EventVisitor;
+
+/// The name of the span that a sandboxed build runs in.
+const SANDBOX_SPAN_NAME: &str = "sandbox";
+
+/// The maximum number of lines that are kept of the output of a sandboxed
+/// build.
+const MAX_CAPTURED_LINES: usize = 10_000;
+
+/// Messages of failed accesses to a path.
+const PATH_DENIED_MESSAGES: &[&str] = &[
+    "Permission denied",
+    "Operation not permitted",
+    "Read-only file system",
+];
+
+/// Messages of failed network accesses.
+const NETWORK_DENIED_MESSAGES: &[&str] = &[
+    "Could not resolve host",
+    "Temporary failure in name resolution",
+    "Name or service not known",
+    "nodename nor servname provided",
+    "Network is unreachable",
+    "Failed to establish a new connection",
+];
+
+/// The `sandbox` table of the backend configuration.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SandboxConfig {
+    /// Allow the build script to access the network. Defaults to `false`.
+    #[serde(default)]
+    pub network: bool,
+    /// Additional paths the build script is allowed to read.
+    #[serde(default)]
+    pub read: Vec<PathBuf>,
+    /// Additional paths the build script is allowed to read and write.
+    #[serde(default)]
+    pub write: Vec<PathBuf>,
+}
+
+impl SandboxConfig {
+    /// Returns the rattler-build sandbox configuration.
+    pub fn configuration(&self) -> miette::Result<SandboxConfiguration> {
+        let platform = Platform::current();
+        if !(platform.is_linux() || platform.is_osx()) {
+            miette::bail!(
+                "the build sandbox is only supported on Linux and macOS, remove the `sandbox` configuration to build on {platform}"
+            );
+        }
+
+        let arguments = SandboxArguments {
+            sandbox: true,
+            allow_network: self.network,
+            allow_read: self.read.clone(),
+            allow_read_execute: Vec::new(),
+            allow_read_write: self.write.clone(),
+            overwrite_default_sandbox_config: false,
+        };
+        Ok(Option::<SandboxConfiguration>::from(arguments)
+            .expect("the sandbox is enabled in the arguments"))
+    }
+}
+
+/// An access of a sandboxed build that was denied.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SandboxDenial {
+    /// An access to a path.
+    Path { path: String, line: String },
+    /// An access to the network.
+    Network { line: String },
+}
+
+impl fmt::Display for SandboxDenial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxDenial::Path { path, line } => {
+                write!(f, "access to `{path}` was denied: {line}")
+            }
+            SandboxDenial::Network { line } => write!(f, "network access was denied: {line}"),
+        }
+    }
+}
+
+/// A sandboxed build failed.
+#[derive(Debug, Error, Diagnostic)]
+#[error("the sandboxed build of '{package}' failed")]
+pub struct SandboxedBuildError {
+    /// The name of the package that failed to build.
+    pub package: String,
+    /// The accesses that were denied by the sandbox, as far as they could be
+    /// determined from the output of the build.
+    pub denials: Vec<SandboxDenial>,
+    #[help]
+    help: String,
+    #[source]
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl SandboxedBuildError {
+    fn new(package: String, denials: Vec<SandboxDenial>, source: miette::Report) -> Self {
+        let help = if denials.is_empty() {
+            String::from(
+                "the build might have accessed a path or the network that is not allowed by the sandbox, use the `read`, `write` and `network` options of the `sandbox` configuration to allow it",
+            )
+        } else {
+            format!(
+                "the sandbox denied the following accesses, use the `read`, `write` and `network` options of the `sandbox` configuration to allow them:\n{}",
+                denials
+                    .iter()
+                    .map(|denial| format!("- {denial}"))
+                    .join("\n")
+            )
+        };
+        Self {
+            package,
+            denials,
+            help,
+            source: source.into(),
+        }
+    }
+}
+
+/// Searches the output of a build for accesses that were denied.
+pub fn find_denials<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<SandboxDenial> {
+    lines
+        .into_iter()
+        .map(str::trim)
+        .filter_map(|line| {
+            if NETWORK_DENIED_MESSAGES
+                .iter()
+                .any(|message| line.contains(message))
+            {
+                Some(SandboxDenial::Network {
+                    line: line.to_string(),
+                })
+            } else if PATH_DENIED_MESSAGES
+                .iter()
+                .any(|message| line.contains(message))
+            {
+                denied_path(line).map(|path| SandboxDenial::Path {
+                    path,
+                    line: line.to_string(),
+                })
+            } else {
+                None
+            }
+        })
+        .unique()
+        .collect()
+}
+
+/// Extracts the absolute path from an error message, preferring quoted paths.
+fn denied_path(line: &str) -> Option<String> {
+    let quoted = line
+        .split(['\'', '"', '`', '‘', '’'])
+        .skip(1)
+        .step_by(2)
+        .find(|part| part.starts_with('/'));
+    let unquoted = || {
+        line.split([' ', ':', ','])
+            .find(|part| part.starts_with('/') && part.len() > 1)
+    };
+    quoted.or_else(unquoted).map(str::to_string)
+}
+
+/// Runs a build in the sandbox. When the build fails the error is turned into
+/// a [`SandboxedBuildError`] that lists the accesses that were denied.
+pub async fn run_sandboxed<T, E: Into<miette::Report>>(
+    package: &str,
+    build: impl Future<Output = Result<T, E>>,
+) -> miette::Result<T> {
+    let span = tracing::info_span!(SANDBOX_SPAN_NAME);
+    let capture = LogCapture::start(&span);
+    let result = build.instrument(span).await.map_err(Into::into);
+    let lines = capture.map(LogCapture::finish).unwrap_or_default();
+
+    result.map_err(|err| {
+        let denials = find_denials(lines.iter().map(String::as_str));
+        SandboxedBuildError::new(package.to_string(), denials, err).into()
+    })
+}
+
+/// The output that is captured per sandbox span.
+static CAPTURED_LINES: LazyLock<Mutex<HashMap<span::Id, VecDeque<String>>>> =
+    LazyLock::new(Default::default);
+
+/// Captures the messages that are logged inside a span.
+struct LogCapture {
+    /// Keeps the span open, so that its id is not reused.
+    span: tracing::Span,
+    id: span::Id,
+}
+
+impl LogCapture {
+    /// Starts capturing, returns `None` if the span is disabled.
+    fn start(span: &tracing::Span) -> Option<Self> {
+        let id = span.id()?;
+        CAPTURED_LINES.lock().insert(id.clone(), VecDeque::new());
+        Some(Self {
+            span: span.clone(),
+            id,
+        })
+    }
+
+    /// Stops capturing and returns the captured lines.
+    fn finish(self) -> Vec<String> {
+        let lines = CAPTURED_LINES.lock().remove(&self.id);
+        drop(self.span);
+        lines.map(Vec::from).unwrap_or_default()
+    }
+}
+
+/// A [`Layer`] that captures the output of sandboxed builds.
+pub struct SandboxLogLayer;
+
+impl<S> Layer<S> for SandboxLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut captured = CAPTURED_LINES.lock();
+        if captured.is_empty() {
+            return;
+        }
+        let Some(id) = ctx.event_scope(event).and_then(|mut scope| {
+            scope
+                .find(|span| span.name() == SANDBOX_SPAN_NAME && captured.contains_key(&span.id()))
+                .map(|span| span.id())
+        }) else {
+            return;
+        };
+        let lines = captured.get_mut(&id).expect("the span is captured");
+
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+        lines.extend(visitor.message.lines().map(str::to_string));
+        let excess = lines.len().saturating_sub(MAX_CAPTURED_LINES);
+        lines.drain(..excess);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn test_find_denials() {
+        let output = [
+            "-- Configuring done",
+            "mkdir: cannot create directory '/home/user/.cache/foo': Permission denied",
+            "PermissionError: [Errno 13] Permission denied: '/etc/foo.conf'",
+            "bash: /opt/tool/bin/setup: Operation not permitted",
+            "curl: (6) Could not resolve host: example.com",
+            "curl: (6) Could not resolve host: example.com",
+        ];
+        let denials = find_denials(output);
+        assert_eq!(
+            denials
+                .iter()
+                .map(|denial| match denial {
+                    SandboxDenial::Path { path, .. } => path.as_str(),
+                    SandboxDenial::Network { .. } => "network",
+                })
+                .collect::<Vec<_>>(),
+            [
+                "/home/user/.cache/foo",
+                "/etc/foo.conf",
+                "/opt/tool/bin/setup",
+                "network"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sandboxed_build_error_lists_denials() {
+        let subscriber = tracing_subscriber::registry().with(SandboxLogLayer);
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let err = run_sandboxed::<(), miette::Report>("foo", async {
+            tracing::info!("touch: cannot touch '/usr/share/foo': Read-only file system");
+            miette::bail!("script failed with exit code 1")
+        })
+        .await
+        .unwrap_err();
+
+        let err = err.downcast_ref::<SandboxedBuildError>().unwrap();
+        assert_eq!(err.package, "foo");
+        assert_eq!(
+            err.denials,
+            [SandboxDenial::Path {
+                path: String::from("/usr/share/foo"),
+                line: String::from("touch: cannot touch '/usr/share/foo': Read-only file system"),
+            }]
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use std::{net::SocketAddr, path::Path, sync::Arc};
 
 use fs_err::tokio as tokio_fs;
-use jsonrpc_core::{Error, IoHandler, Params, serde_json, to_value};
+use jsonrpc_core::{Call, Error, MetaIoHandler, Params, serde_json, to_value};
 use miette::{Context, IntoDiagnostic, JSONReportHandler};
 use pixi_build_types::{
     VersionedProjectModel,
@@ -14,19 +14,38 @@ use pixi_build_types::{
     },
 };
 use serde::Serialize;
-use tokio::sync::{Mutex, RwLock};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{Mutex, RwLock, mpsc},
+};
 
-use crate::consts::DEBUG_OUTPUT_DIR;
+use crate::cancellation::{CANCEL_REQUEST_METHOD_NAME, CancelRequestParams, RequestCancellation};
+use crate::consts::{
+    CONDA_BUILD_V1_ERROR_FILE, CONDA_BUILD_V1_PARAMS_FILE, CONDA_BUILD_V1_RESPONSE_FILE,
+    CONDA_OUTPUTS_ERROR_FILE, CONDA_OUTPUTS_PARAMS_FILE, CONDA_OUTPUTS_RESPONSE_FILE,
+    DEBUG_OUTPUT_DIR, INITIALIZE_PARAMS_FILE, PROJECT_MODEL_FILE,
+};
+use crate::notifications::NotificationSink;
+use crate::package_test::PackageTestError;
 use crate::protocol::{Protocol, ProtocolInstantiator};
 
+/// The JSON-RPC error code of a request that failed.
+pub const SERVER_ERROR_CODE: i64 = -32000;
+
+/// The JSON-RPC error code of a `conda/build_v1` request for which the package
+/// was built but its tests failed. The error data contains the `package` name
+/// and the `package_file` that was tested.
+pub const PACKAGE_TEST_FAILED_ERROR_CODE: i64 = -32001;
+
 /// A JSONRPC server that can be used to communicate with a client.
 pub struct Server<T: ProtocolInstantiator> {
-    instatiator: T,
+    instatiator: Arc<T>,
+    notifications: NotificationSink,
 }
 
 enum ServerState<T: ProtocolInstantiator> {
     /// Server has not been initialized yet.
-    Uninitialized(T),
+    Uninitialized(Arc<T>),
     /// Server has been initialized, with a protocol.
     Initialized(Box<dyn Protocol + Send + Sync + 'static>),
 }
@@ -45,14 +64,72 @@ impl<T: ProtocolInstantiator> ServerState<T> {
 
 impl<T: ProtocolInstantiator> Server<T> {
     pub fn new(instatiator: T) -> Self {
-        Self { instatiator }
+        Self {
+            instatiator: Arc::new(instatiator),
+            notifications: NotificationSink::default(),
+        }
+    }
+
+    /// Sends the notifications of the given sink to the client. Notifications
+    /// are not supported when communicating over HTTP.
+    pub fn with_notifications(self, notifications: NotificationSink) -> Self {
+        Self {
+            notifications,
+            ..self
+        }
     }
 
     /// Run the server, communicating over stdin/stdout.
+    ///
+    /// Notifications are written to stdout in between the responses.
     pub async fn run(self) -> miette::Result<()> {
         let io = self.setup_io();
-        jsonrpc_stdio_server::ServerBuilder::new(io).build().await;
-        Ok(())
+        serve_lines(
+            io,
+            tokio::io::stdin(),
+            tokio::io::stdout(),
+            &self.notifications,
+        )
+        .await
+    }
+
+    /// Run the server, communicating over a Unix domain socket at the given
+    /// path.
+    ///
+    /// The server keeps running after a client disconnects so that the same
+    /// backend process can be reused by multiple clients. Clients are served
+    /// one at a time and every connection starts with a fresh, uninitialized
+    /// state. The socket is removed when the server receives `SIGINT` or
+    /// `SIGTERM`.
+    #[cfg(unix)]
+    pub async fn run_over_socket(self, path: &Path) -> miette::Result<()> {
+        let listener = SocketListener::bind(path)?;
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .into_diagnostic()?;
+        tokio::select! {
+            result = self.serve_socket(&listener) => result,
+            _ = tokio::signal::ctrl_c() => Ok(()),
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+
+    /// Serves the clients that connect to the socket, one at a time.
+    #[cfg(unix)]
+    async fn serve_socket(&self, listener: &SocketListener) -> miette::Result<()> {
+        let path = listener.path.display();
+        loop {
+            let (stream, _) = listener.listener.accept().await.into_diagnostic()?;
+            tracing::debug!("client connected to {path}");
+
+            let (reader, writer) = stream.into_split();
+            if let Err(err) =
+                serve_lines(self.setup_io(), reader, writer, &self.notifications).await
+            {
+                tracing::warn!("{err:?}");
+            }
+            tracing::debug!("client disconnected from {path}");
+        }
     }
 
     /// Run the server, communicating over HTTP.
@@ -66,9 +143,27 @@ impl<T: ProtocolInstantiator> Server<T> {
     }
 
     /// Setup the IO inner handler.
-    fn setup_io(self) -> IoHandler {
-        // Construct a server
-        let mut io = IoHandler::new();
+    fn setup_io(&self) -> MetaIoHandler<(), RequestCancellation> {
+        // Construct a server, all requests can be cancelled by the client.
+        let cancellation = RequestCancellation::default();
+        let mut io = MetaIoHandler::with_middleware(cancellation.clone());
+        io.add_notification(CANCEL_REQUEST_METHOD_NAME, move |params: Params| {
+            let params: CancelRequestParams = match params.parse() {
+                Ok(params) => params,
+                Err(err) => {
+                    tracing::warn!("invalid {CANCEL_REQUEST_METHOD_NAME} notification: {err}");
+                    return;
+                }
+            };
+
+            if !cancellation.cancel(&params.id) {
+                tracing::debug!(
+                    "{:?} is not in-flight, it is cancelled if it arrives later on",
+                    params.id
+                );
+            }
+        });
+
         io.add_method(
             procedures::negotiate_capabilities::METHOD_NAME,
             move |params: Params| async move {
@@ -80,24 +175,29 @@ impl<T: ProtocolInstantiator> Server<T> {
             },
         );
 
-        let project_model = Arc::new(Mutex::new(None));
+        let initialize_log = Arc::new(Mutex::new(None));
 
-        let state = Arc::new(RwLock::new(ServerState::Uninitialized(self.instatiator)));
+        let state = Arc::new(RwLock::new(ServerState::Uninitialized(
+            self.instatiator.clone(),
+        )));
         let initialize_state = state.clone();
-        let initialize_project_model = project_model.clone();
+        let initialize_log_for_initialize = initialize_log.clone();
         io.add_method(
             procedures::initialize::METHOD_NAME,
             move |params: Params| {
-                let pm = initialize_project_model.clone();
+                let initialize_log = initialize_log_for_initialize.clone();
                 let state = initialize_state.clone();
 
                 async move {
                     let params: InitializeParams = params.parse()?;
 
-                    if let Some(project_model) = &params.project_model {
-                        let mut lock = pm.lock().await;
-                        *lock = Some(project_model.clone());
-                    }
+                    // The debug directory is only known once the first
+                    // procedure is called, so the initialization is logged
+                    // then.
+                    *initialize_log.lock().await = Some(InitializeLog {
+                        params: to_value(&params).expect("failed to convert to json"),
+                        project_model: params.project_model.clone(),
+                    });
 
                     let mut state = state.write().await;
                     let ServerState::Uninitialized(initializer) = &mut *state else {
@@ -116,11 +216,11 @@ impl<T: ProtocolInstantiator> Server<T> {
         );
 
         let conda_outputs = state.clone();
-        let conda_outputs_project_model = project_model.clone();
+        let conda_outputs_initialize_log = initialize_log.clone();
         io.add_method(
             procedures::conda_outputs::METHOD_NAME,
             move |params: Params| {
-                let pm = conda_outputs_project_model.clone();
+                let initialize_log = conda_outputs_initialize_log.clone();
                 let state = conda_outputs.clone();
 
                 async move {
@@ -130,10 +230,8 @@ impl<T: ProtocolInstantiator> Server<T> {
 
                     let debug_dir = params.work_directory.join(DEBUG_OUTPUT_DIR);
 
-                    if let Some(project_model) = pm.lock().await.take() {
-                        log_project_model(&debug_dir, project_model)
-                            .await
-                            .map_err(convert_error)?;
+                    if let Some(initialize) = initialize_log.lock().await.take() {
+                        initialize.write(&debug_dir).await.map_err(convert_error)?;
                     }
 
                     log_conda_outputs(&debug_dir, &params)
@@ -161,11 +259,11 @@ impl<T: ProtocolInstantiator> Server<T> {
         );
 
         let conda_build_v1 = state.clone();
-        let conda_build_project_model = project_model.clone();
+        let conda_build_initialize_log = initialize_log.clone();
         io.add_method(
             procedures::conda_build_v1::METHOD_NAME,
             move |params: Params| {
-                let pm = conda_build_project_model.clone();
+                let initialize_log = conda_build_initialize_log.clone();
                 let state = conda_build_v1.clone();
 
                 async move {
@@ -175,10 +273,8 @@ impl<T: ProtocolInstantiator> Server<T> {
 
                     let debug_dir = params.work_directory.join(DEBUG_OUTPUT_DIR);
 
-                    if let Some(project_model) = pm.lock().await.take() {
-                        log_project_model(&debug_dir, project_model)
-                            .await
-                            .map_err(convert_error)?;
+                    if let Some(initialize) = initialize_log.lock().await.take() {
+                        initialize.write(&debug_dir).await.map_err(convert_error)?;
                     }
 
                     log_conda_build_v1(&debug_dir, &params)
@@ -209,65 +305,215 @@ impl<T: ProtocolInstantiator> Server<T> {
     }
 }
 
-fn convert_error(err: miette::Report) -> jsonrpc_core::Error {
+/// A Unix domain socket the server listens on. The socket file is removed
+/// when the listener is dropped.
+#[cfg(unix)]
+struct SocketListener {
+    listener: tokio::net::UnixListener,
+    path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl SocketListener {
+    /// Binds to the socket at `path`. A socket of a previous backend process
+    /// that is no longer running is replaced, anything else at `path` is left
+    /// alone.
+    fn bind(path: &Path) -> miette::Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+
+        match fs_err::symlink_metadata(path) {
+            Ok(metadata) if !metadata.file_type().is_socket() => {
+                miette::bail!(
+                    "cannot listen on {}, the path exists and is not a socket",
+                    path.display()
+                );
+            }
+            Ok(_) => match std::os::unix::net::UnixStream::connect(path) {
+                Ok(_) => miette::bail!(
+                    "cannot listen on {}, another backend is already listening on it",
+                    path.display()
+                ),
+                Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    // Nobody is listening anymore, the socket is stale.
+                    fs_err::remove_file(path).into_diagnostic()?;
+                }
+                Err(err) => {
+                    return Err(err)
+                        .into_diagnostic()
+                        .with_context(|| format!("failed to check the socket {}", path.display()));
+                }
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err).into_diagnostic(),
+        }
+
+        let listener = tokio::net::UnixListener::bind(path)
+            .into_diagnostic()
+            .with_context(|| format!("failed to bind to socket {}", path.display()))?;
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SocketListener {
+    fn drop(&mut self) {
+        if let Err(err) = fs_err::remove_file(&self.path) {
+            tracing::debug!("failed to remove the socket: {err}");
+        }
+    }
+}
+
+/// Serves the requests that are read from `reader` and writes the responses
+/// and notifications to `output`, until `reader` is closed.
+///
+/// Every line is a request and every response is written on a separate line.
+/// Requests are handled concurrently, otherwise a `$/cancelRequest`
+/// notification would only be read after the request it tries to cancel has
+/// finished. The `initialize` request is the exception, it is handled before
+/// the next line is read so that the requests that follow it see an
+/// initialized server.
+async fn serve_lines(
+    io: MetaIoHandler<(), RequestCancellation>,
+    reader: impl AsyncRead + Unpin,
+    mut output: impl AsyncWrite + Unpin + Send + 'static,
+    notifications: &NotificationSink,
+) -> miette::Result<()> {
+    let io = Arc::new(io);
+
+    let (response_tx, mut response_rx) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        while let Some(response) = response_rx.recv().await {
+            output.write_all(response.as_bytes()).await?;
+            output.write_all(b"\n").await?;
+            output.flush().await?;
+        }
+        Ok::<_, std::io::Error>(())
+    });
+    notifications.connect(response_tx.clone());
+
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await.into_diagnostic()? {
+        let initialize = is_initialize_request(&line);
+        let io = io.clone();
+        let response_tx = response_tx.clone();
+        let handle = async move {
+            if let Some(response) = io.handle_request(&line, ()).await {
+                // The writer only stops when all senders are dropped.
+                let _ = response_tx.send(response);
+            }
+        };
+        if initialize {
+            handle.await;
+        } else {
+            tokio::spawn(handle);
+        }
+    }
+
+    // Wait for the in-flight requests to write their responses.
+    drop(response_tx);
+    notifications.disconnect();
+    writer
+        .await
+        .into_diagnostic()?
+        .into_diagnostic()
+        .context("failed to write response")
+}
+
+/// Returns whether the line is an `initialize` request.
+fn is_initialize_request(line: &str) -> bool {
+    matches!(
+        serde_json::from_str(line),
+        Ok(Call::MethodCall(call)) if call.method == procedures::initialize::METHOD_NAME
+    )
+}
+
+pub(crate) fn convert_error(err: miette::Report) -> jsonrpc_core::Error {
     let rendered = JSONReportHandler::new();
     let mut json_str = String::new();
     rendered
         .render_report(&mut json_str, err.as_ref())
         .expect("failed to convert error to json");
-    let data = serde_json::from_str(&json_str).expect("failed to parse json error");
+    let mut data: serde_json::Value =
+        serde_json::from_str(&json_str).expect("failed to parse json error");
+
+    let code = if let Some(test_error) = err.downcast_ref::<PackageTestError>() {
+        if let Some(data) = data.as_object_mut() {
+            data.insert("package".into(), test_error.package.clone().into());
+            data.insert(
+                "package_file".into(),
+                test_error.package_file.display().to_string().into(),
+            );
+        }
+        PACKAGE_TEST_FAILED_ERROR_CODE
+    } else {
+        SERVER_ERROR_CODE
+    };
+
     jsonrpc_core::Error {
-        code: jsonrpc_core::ErrorCode::ServerError(-32000),
+        code: jsonrpc_core::ErrorCode::ServerError(code),
         message: err.to_string(),
         data: Some(data),
     }
 }
 
-async fn log_project_model(
-    debug_dir: &Path,
-    project_model: VersionedProjectModel,
-) -> miette::Result<()> {
-    let project_model = project_model
-        .into_v1()
-        .ok_or_else(|| miette::miette!("project model needs to be v1"))?;
+/// The initialization of the backend, which is written to the debug directory
+/// so that the requests can be replayed.
+struct InitializeLog {
+    params: serde_json::Value,
+    project_model: Option<VersionedProjectModel>,
+}
 
-    write_json_file(debug_dir, "project_model.json", &project_model).await
+impl InitializeLog {
+    async fn write(self, debug_dir: &Path) -> miette::Result<()> {
+        write_json_file(debug_dir, INITIALIZE_PARAMS_FILE, &self.params).await?;
+
+        if let Some(project_model) = self.project_model {
+            let project_model = project_model
+                .into_v1()
+                .ok_or_else(|| miette::miette!("project model needs to be v1"))?;
+            write_json_file(debug_dir, PROJECT_MODEL_FILE, &project_model).await?;
+        }
+        Ok(())
+    }
 }
 
 async fn log_conda_outputs(debug_dir: &Path, params: &CondaOutputsParams) -> miette::Result<()> {
-    write_json_file(debug_dir, "conda_outputs_params.json", params).await
+    write_json_file(debug_dir, CONDA_OUTPUTS_PARAMS_FILE, params).await
 }
 
 async fn log_conda_outputs_response(
     debug_dir: &Path,
     result: &CondaOutputsResult,
 ) -> miette::Result<()> {
-    write_json_file(debug_dir, "conda_outputs_response.json", result).await
+    write_json_file(debug_dir, CONDA_OUTPUTS_RESPONSE_FILE, result).await
 }
 
 async fn log_conda_outputs_error(
     debug_dir: &Path,
     error: &jsonrpc_core::Error,
 ) -> miette::Result<()> {
-    write_json_file(debug_dir, "conda_outputs_error.json", error).await
+    write_json_file(debug_dir, CONDA_OUTPUTS_ERROR_FILE, error).await
 }
 
 async fn log_conda_build_v1(debug_dir: &Path, params: &CondaBuildV1Params) -> miette::Result<()> {
-    write_json_file(debug_dir, "conda_build_v1_params.json", params).await
+    write_json_file(debug_dir, CONDA_BUILD_V1_PARAMS_FILE, params).await
 }
 
 async fn log_conda_build_v1_response(
     debug_dir: &Path,
     result: &CondaBuildV1Result,
 ) -> miette::Result<()> {
-    write_json_file(debug_dir, "conda_build_v1_response.json", result).await
+    write_json_file(debug_dir, CONDA_BUILD_V1_RESPONSE_FILE, result).await
 }
 
 async fn log_conda_build_v1_error(
     debug_dir: &Path,
     error: &jsonrpc_core::Error,
 ) -> miette::Result<()> {
-    write_json_file(debug_dir, "conda_build_v1_error.json", error).await
+    write_json_file(debug_dir, CONDA_BUILD_V1_ERROR_FILE, error).await
 }
 
 async fn write_json_file<T: Serialize>(
@@ -291,3 +537,332 @@ async fn write_json_file<T: Serialize>(
         .context("failed to write JSON to file")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use itertools::Itertools;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[test]
+    fn test_convert_package_test_error() {
+        let err = miette::Report::from(PackageTestError {
+            package: String::from("foo"),
+            package_file: PathBuf::from("foo-1.0.0-h123_0.conda"),
+            source: "test script exited with code 1".into(),
+        });
+
+        let json_error = convert_error(err);
+        assert_eq!(
+            json_error.code,
+            jsonrpc_core::ErrorCode::ServerError(PACKAGE_TEST_FAILED_ERROR_CODE)
+        );
+
+        let data = json_error.data.unwrap();
+        assert_eq!(data["package"], "foo");
+        assert_eq!(data["package_file"], "foo-1.0.0-h123_0.conda");
+    }
+
+    /// An instantiator for tests that only exercise the transport.
+    struct UnreachableInstantiator;
+
+    #[async_trait::async_trait]
+    impl ProtocolInstantiator for UnreachableInstantiator {
+        async fn negotiate_capabilities(
+            _params: NegotiateCapabilitiesParams,
+        ) -> miette::Result<procedures::negotiate_capabilities::NegotiateCapabilitiesResult>
+        {
+            unreachable!()
+        }
+
+        async fn initialize(
+            &self,
+            _params: InitializeParams,
+        ) -> miette::Result<(
+            Box<dyn Protocol + Send + Sync + 'static>,
+            procedures::initialize::InitializeResult,
+        )> {
+            unreachable!()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_serves_consecutive_clients() {
+        use tokio::net::UnixStream;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("backend.sock");
+
+        // The socket accepts connections as soon as it is bound.
+        let listener = SocketListener::bind(&socket).unwrap();
+        let server = Server::new(UnreachableInstantiator);
+        let serving = tokio::spawn(async move { server.serve_socket(&listener).await });
+
+        for id in 1..=2 {
+            let mut stream = UnixStream::connect(&socket).await.unwrap();
+            let request = format!(
+                r#"{{"jsonrpc":"2.0","method":"{}","params":{{}},"id":{id}}}"#,
+                procedures::conda_outputs::METHOD_NAME
+            );
+            stream.write_all(request.as_bytes()).await.unwrap();
+            stream.write_all(b"\n").await.unwrap();
+
+            let mut lines = BufReader::new(stream).lines();
+            let response: serde_json::Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            assert_eq!(response["id"], id);
+            assert!(response["error"].is_object());
+        }
+
+        // A second backend cannot take over the socket of a running one.
+        assert!(SocketListener::bind(&socket).is_err());
+
+        // Stopping the server removes the socket.
+        serving.abort();
+        let _ = serving.await;
+        assert!(!socket.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_replaces_only_stale_sockets() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A regular file is not removed.
+        let file = dir.path().join("backend.sock");
+        fs_err::write(&file, "not a socket").unwrap();
+        assert!(SocketListener::bind(&file).is_err());
+        assert!(file.exists());
+
+        // A socket that nobody listens on is replaced.
+        let stale = dir.path().join("stale.sock");
+        drop(std::os::unix::net::UnixListener::bind(&stale).unwrap());
+        assert!(stale.exists());
+        let listener = SocketListener::bind(&stale).unwrap();
+        drop(listener);
+        assert!(!stale.exists());
+    }
+
+    /// An instantiator whose outputs are derived from the manifest, and which
+    /// writes an artifact to the debug directory like the real backends do.
+    struct ManifestInstantiator;
+
+    struct ManifestProtocol {
+        manifest_path: PathBuf,
+    }
+
+    #[async_trait::async_trait]
+    impl ProtocolInstantiator for ManifestInstantiator {
+        async fn negotiate_capabilities(
+            _params: NegotiateCapabilitiesParams,
+        ) -> miette::Result<procedures::negotiate_capabilities::NegotiateCapabilitiesResult>
+        {
+            unreachable!()
+        }
+
+        async fn initialize(
+            &self,
+            params: InitializeParams,
+        ) -> miette::Result<(
+            Box<dyn Protocol + Send + Sync + 'static>,
+            procedures::initialize::InitializeResult,
+        )> {
+            Ok((
+                Box::new(ManifestProtocol {
+                    manifest_path: params.manifest_path,
+                }),
+                procedures::initialize::InitializeResult {},
+            ))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Protocol for ManifestProtocol {
+        async fn conda_outputs(
+            &self,
+            params: CondaOutputsParams,
+        ) -> miette::Result<CondaOutputsResult> {
+            write_json_file(
+                &params.work_directory.join(DEBUG_OUTPUT_DIR),
+                "recipe.json",
+                &params.work_directory,
+            )
+            .await?;
+            Ok(CondaOutputsResult {
+                outputs: Vec::new(),
+                input_globs: [self.manifest_path.display().to_string()]
+                    .into_iter()
+                    .collect(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        use rattler_conda_types::Platform;
+
+        use crate::replay::{self, Recording};
+
+        let dir = tempfile::tempdir().unwrap();
+        let work_directory = dir.path().join("work");
+        let debug_dir = work_directory.join(DEBUG_OUTPUT_DIR);
+
+        // Record the requests.
+        let io = Server::new(ManifestInstantiator).setup_io();
+        let initialize = InitializeParams {
+            workspace_root: None,
+            source_dir: None,
+            manifest_path: PathBuf::from("pixi.toml"),
+            project_model: None,
+            configuration: None,
+            target_configuration: None,
+            cache_directory: None,
+        };
+        let outputs = CondaOutputsParams {
+            channels: vec![],
+            host_platform: Platform::current(),
+            build_platform: Platform::current(),
+            variant_configuration: None,
+            variant_files: None,
+            work_directory: work_directory.clone(),
+        };
+        for (id, (method, params)) in [
+            (
+                procedures::initialize::METHOD_NAME,
+                to_value(&initialize).unwrap(),
+            ),
+            (
+                procedures::conda_outputs::METHOD_NAME,
+                to_value(&outputs).unwrap(),
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": method,
+                "params": params,
+                "id": id,
+            });
+            let response = io.handle_request(&request.to_string(), ()).await.unwrap();
+            let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+            assert!(response["result"].is_object(), "{response}");
+        }
+
+        let recorded_files = fs_err::read_dir(&debug_dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let contents = fs_err::read_to_string(&path).unwrap();
+                (path, contents)
+            })
+            .collect::<Vec<_>>();
+
+        // Replay them in another work directory.
+        let recording = Recording::load(&debug_dir, None, None).unwrap();
+        let procedure = recording.procedure.kind();
+        let (protocol, _) = ManifestInstantiator
+            .initialize(recording.initialize)
+            .await
+            .unwrap();
+        let replay_directory = dir.path().join("replay");
+        let replayed = recording
+            .procedure
+            .replay(protocol.as_ref(), &replay_directory)
+            .await;
+
+        let recorded = replay::recorded_response(&debug_dir, procedure).unwrap();
+        let replayed = replay::response_to_json(replayed);
+        let differences = replay::diff_json(&recorded, &replayed);
+        assert!(differences.is_empty(), "{differences:?}");
+
+        // The recording is left untouched.
+        assert!(
+            replay_directory
+                .join(DEBUG_OUTPUT_DIR)
+                .join("recipe.json")
+                .is_file()
+        );
+        for (path, contents) in recorded_files {
+            assert_eq!(fs_err::read_to_string(&path).unwrap(), contents);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_is_handled_before_the_next_line() {
+        use rattler_conda_types::Platform;
+
+        let dir = tempfile::tempdir().unwrap();
+        let initialize = InitializeParams {
+            workspace_root: None,
+            source_dir: None,
+            manifest_path: PathBuf::from("pixi.toml"),
+            project_model: None,
+            configuration: None,
+            target_configuration: None,
+            cache_directory: None,
+        };
+        let outputs = CondaOutputsParams {
+            channels: vec![],
+            host_platform: Platform::current(),
+            build_platform: Platform::current(),
+            variant_configuration: None,
+            variant_files: None,
+            work_directory: dir.path().to_path_buf(),
+        };
+        let requests = [
+            (procedures::initialize::METHOD_NAME, to_value(&initialize)),
+            (procedures::conda_outputs::METHOD_NAME, to_value(&outputs)),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(id, (method, params))| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": method,
+                "params": params.unwrap(),
+                "id": id,
+            })
+            .to_string()
+        })
+        .join("\n");
+
+        // Both requests are read at once, the second one must not be handled
+        // before the server is initialized.
+        let server = Server::new(ManifestInstantiator);
+        let (output, mut client) = tokio::io::duplex(64 * 1024);
+        serve_lines(
+            server.setup_io(),
+            requests.as_bytes(),
+            output,
+            &server.notifications,
+        )
+        .await
+        .unwrap();
+
+        let mut responses = String::new();
+        client.read_to_string(&mut responses).await.unwrap();
+        let responses = responses
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(responses.len(), 2);
+        for response in responses {
+            assert!(response["result"].is_object(), "{response}");
+        }
+    }
+
+    #[test]
+    fn test_convert_generic_error() {
+        let json_error = convert_error(miette::miette!("something went wrong"));
+        assert_eq!(
+            json_error.code,
+            jsonrpc_core::ErrorCode::ServerError(SERVER_ERROR_CODE)
+        );
+    }
+}
@@ -67,7 +67,7 @@ pub fn from_targets_v1_to_conditional_requirements(targets: &TargetsV1) -> Condi
     let mut build_items = ConditionalList::new();
     let mut host_items = ConditionalList::new();
     let mut run_items = ConditionalList::new();
-    let run_constraints_items = ConditionalList::new();
+    let mut run_constraints_items = ConditionalList::new();
 
     // Add default target
     if let Some(default_target) = &targets.default_target {
@@ -98,6 +98,14 @@ pub fn from_targets_v1_to_conditional_requirements(targets: &TargetsV1) -> Condi
                 .map(|spec| spec.1)
                 .map(Item::from),
         );
+
+        run_constraints_items.extend(
+            package_requirements
+                .run_constraints
+                .into_iter()
+                .map(|spec| spec.1)
+                .map(Item::from),
+        );
     }
 
     // Add specific targets
@@ -149,6 +157,20 @@ pub fn from_targets_v1_to_conditional_requirements(targets: &TargetsV1) -> Condi
                         .into()
                     }),
             );
+            run_constraints_items.extend(
+                package_requirements
+                    .run_constraints
+                    .into_iter()
+                    .map(|spec| spec.1)
+                    .map(|spec| {
+                        Conditional {
+                            condition: to_rattler_build_selector(selector, PlatformKind::Host),
+                            then: ListOrItem(vec![spec]),
+                            else_value: ListOrItem::default(),
+                        }
+                        .into()
+                    }),
+            );
         }
     }
 
@@ -259,6 +281,12 @@ pub fn target_to_package_spec(target: &TargetV1) -> PackageSpecDependencies<Pack
         .map(|deps| package_specs_to_package_dependency(deps).unwrap())
         .unwrap_or_default();
 
+    let run_constraints = target
+        .clone()
+        .run_constraints
+        .map(|deps| package_specs_to_package_dependency(deps).unwrap())
+        .unwrap_or_default();
+
     let mut bin_reqs = PackageSpecDependencies::default();
 
     for spec in build_reqs.iter() {
@@ -273,6 +301,12 @@ pub fn target_to_package_spec(target: &TargetV1) -> PackageSpecDependencies<Pack
         bin_reqs.run.insert(spec.package_name(), spec.clone());
     }
 
+    for spec in run_constraints.iter() {
+        bin_reqs
+            .run_constraints
+            .insert(spec.package_name(), spec.clone());
+    }
+
     bin_reqs
 }
 
@@ -377,6 +411,40 @@ pub fn from_build_v1_args_to_finalized_dependencies(
 mod test {
     use super::*;
 
+    #[test]
+    fn test_run_constraints_from_targets() {
+        let targets: TargetsV1 = serde_json::from_value(serde_json::json!({
+            "defaultTarget": {
+                "runConstraints": {
+                    "openssl": { "binary": { "version": ">=3,<4" } }
+                }
+            },
+            "targets": {
+                "linux-64": {
+                    "runConstraints": {
+                        "libzlib": { "binary": { "version": ">=1.3" } }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let requirements = from_targets_v1_to_conditional_requirements(&targets);
+        let run_constraints = requirements
+            .run_constraints
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(run_constraints.len(), 2);
+        assert_eq!(run_constraints[0], "openssl >=3,<4");
+        assert!(
+            matches!(&requirements.run_constraints[1], Item::Conditional(conditional) if conditional.condition == "host_platform == 'linux-64'"),
+            "expected a conditional constraint, got {}",
+            run_constraints[1]
+        );
+        assert!(requirements.run.is_empty());
+    }
+
     #[test]
     fn test_binary_package_conversion() {
         let name = PackageName::new_unchecked("foobar");
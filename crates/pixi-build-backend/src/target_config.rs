@@ -0,0 +1,220 @@
+//! Merging of the target-specific backend configurations.
+//!
+//! Every `[package.build.target.<selector>.config]` table whose selector
+//! matches the platform is merged into the base configuration, from the least
+//! to the most specific selector. A `[target.linux-64]` configuration therefore
+//! takes precedence over a `[target.unix]` configuration, regardless of the
+//! order in which they are defined.
+
+use std::{collections::BTreeMap, path::Path};
+
+use miette::{Context, IntoDiagnostic};
+use ordermap::OrderMap;
+use pixi_build_types::TargetSelectorV1;
+use rattler_conda_types::Platform;
+use serde::Serialize;
+
+use crate::{TargetSelector, generated_recipe::BackendConfig};
+
+/// The name of the file in the debug directory that records how the
+/// configuration was merged.
+pub const CONFIG_MERGE_FILE_NAME: &str = "target_config_merge.json";
+
+/// A record of how the configuration for a platform was constructed.
+#[derive(Debug, Default, Serialize)]
+pub struct ConfigMergeRecord {
+    /// The platform for which the configuration was merged.
+    pub platform: String,
+    /// The target configurations that were merged, in order.
+    pub steps: Vec<ConfigMergeStep>,
+    /// For every configuration key, the selector of the configuration that
+    /// set it last, or `base` for the base configuration.
+    pub provenance: BTreeMap<String, String>,
+}
+
+/// A single target configuration that was merged into the configuration.
+#[derive(Debug, Serialize)]
+pub struct ConfigMergeStep {
+    pub selector: String,
+    pub config: serde_json::Value,
+}
+
+/// The configuration of a backend together with its target-specific
+/// configurations.
+#[derive(Debug, Clone)]
+pub struct TargetConfigs<C> {
+    base: C,
+    raw_base: serde_json::Value,
+    targets: OrderMap<TargetSelectorV1, (C, serde_json::Value)>,
+}
+
+impl<C: BackendConfig> TargetConfigs<C> {
+    pub fn new(
+        base: C,
+        raw_base: serde_json::Value,
+        targets: OrderMap<TargetSelectorV1, (C, serde_json::Value)>,
+    ) -> Self {
+        Self {
+            base,
+            raw_base,
+            targets,
+        }
+    }
+
+    /// Returns the base configuration.
+    pub fn base(&self) -> &C {
+        &self.base
+    }
+
+    /// Returns the configuration for the given platform together with a record
+    /// of how it was merged.
+    pub fn for_platform(&self, platform: Platform) -> miette::Result<(C, ConfigMergeRecord)> {
+        let mut matching = self
+            .targets
+            .iter()
+            .filter(|(selector, _)| selector.matches(platform))
+            .collect::<Vec<_>>();
+
+        // The sort is stable, so selectors that are equally specific are merged
+        // in the order in which they are defined.
+        matching.sort_by_key(|(selector, _)| selector.specificity());
+
+        let mut record = ConfigMergeRecord {
+            platform: platform.to_string(),
+            ..ConfigMergeRecord::default()
+        };
+        record_provenance(&mut record.provenance, &self.raw_base, "base");
+
+        let mut config = self.base.clone();
+        for (selector, (target_config, raw_target_config)) in matching {
+            config = config
+                .merge_with_target_config(target_config)
+                .wrap_err_with(|| {
+                    format!("failed to merge the target configuration for {selector}")
+                })?;
+
+            let selector = selector.to_string();
+            record_provenance(&mut record.provenance, raw_target_config, &selector);
+            record.steps.push(ConfigMergeStep {
+                selector,
+                config: raw_target_config.clone(),
+            });
+        }
+
+        Ok((config, record))
+    }
+}
+
+fn record_provenance(
+    provenance: &mut BTreeMap<String, String>,
+    raw_config: &serde_json::Value,
+    source: &str,
+) {
+    if let Some(table) = raw_config.as_object() {
+        for key in table.keys() {
+            provenance.insert(key.clone(), source.to_string());
+        }
+    }
+}
+
+impl ConfigMergeRecord {
+    /// Writes the record to the debug directory.
+    pub async fn write(&self, debug_dir: &Path) -> miette::Result<()> {
+        fs_err::tokio::create_dir_all(debug_dir)
+            .await
+            .into_diagnostic()?;
+        let json = serde_json::to_string_pretty(self).into_diagnostic()?;
+        fs_err::tokio::write(debug_dir.join(CONFIG_MERGE_FILE_NAME), json)
+            .await
+            .into_diagnostic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Default, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Config {
+        extra_args: Option<Vec<String>>,
+        noarch: Option<bool>,
+    }
+
+    impl BackendConfig for Config {
+        fn debug_dir(&self) -> Option<&Path> {
+            None
+        }
+
+        fn merge_with_target_config(&self, target_config: &Self) -> miette::Result<Self> {
+            Ok(Self {
+                extra_args: target_config
+                    .extra_args
+                    .clone()
+                    .or_else(|| self.extra_args.clone()),
+                noarch: target_config.noarch.or(self.noarch),
+            })
+        }
+    }
+
+    fn target_configs(
+        base: serde_json::Value,
+        targets: Vec<(TargetSelectorV1, serde_json::Value)>,
+    ) -> TargetConfigs<Config> {
+        TargetConfigs::new(
+            serde_json::from_value(base.clone()).unwrap(),
+            base,
+            targets
+                .into_iter()
+                .map(|(selector, raw)| {
+                    (
+                        selector,
+                        (serde_json::from_value(raw.clone()).unwrap(), raw),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_merge_by_specificity() {
+        // The platform selector is defined first, but it is the most specific.
+        let configs = target_configs(
+            json!({ "extra-args": ["base"], "noarch": true }),
+            vec![
+                (
+                    TargetSelectorV1::Platform(String::from("linux-64")),
+                    json!({ "extra-args": ["linux-64"] }),
+                ),
+                (
+                    TargetSelectorV1::Unix,
+                    json!({ "extra-args": ["unix"], "noarch": false }),
+                ),
+                (TargetSelectorV1::Win, json!({ "extra-args": ["win"] })),
+            ],
+        );
+
+        let (config, record) = configs.for_platform(Platform::Linux64).unwrap();
+        assert_eq!(config.extra_args, Some(vec![String::from("linux-64")]));
+        assert_eq!(config.noarch, Some(false));
+
+        let selectors = record
+            .steps
+            .iter()
+            .map(|step| step.selector.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(selectors, ["unix", "linux-64"]);
+        assert_eq!(record.provenance["extra-args"], "linux-64");
+        assert_eq!(record.provenance["noarch"], "unix");
+
+        let (config, record) = configs.for_platform(Platform::Win64).unwrap();
+        assert_eq!(config.extra_args, Some(vec![String::from("win")]));
+        assert_eq!(config.noarch, Some(true));
+        assert_eq!(record.provenance["noarch"], "base");
+    }
+}
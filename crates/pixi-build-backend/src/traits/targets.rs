@@ -17,6 +17,10 @@ use pixi_build_types::{self as pbt};
 pub trait TargetSelector {
     /// Does the target selector match the platform?
     fn matches(&self, platform: Platform) -> bool;
+
+    /// How specific the selector is. When multiple selectors match a
+    /// platform, the more specific one takes precedence.
+    fn specificity(&self) -> u8;
 }
 
 #[derive(Debug)]
@@ -142,6 +146,16 @@ impl TargetSelector for pbt::TargetSelectorV1 {
             pbt::TargetSelectorV1::MacOs => platform.is_osx(),
         }
     }
+
+    fn specificity(&self) -> u8 {
+        match self {
+            pbt::TargetSelectorV1::Unix => 0,
+            pbt::TargetSelectorV1::Linux
+            | pbt::TargetSelectorV1::Win
+            | pbt::TargetSelectorV1::MacOs => 1,
+            pbt::TargetSelectorV1::Platform(_) => 2,
+        }
+    }
 }
 
 impl Targets for pbt::TargetsV1 {
@@ -0,0 +1,54 @@
+/// This file contains a helper to instantiate an intermediate backend for a
+/// test project model in a temporary directory.
+use std::{path::Path, sync::Arc};
+
+use pixi_build_backend::{
+    generated_recipe::GenerateRecipe, intermediate_backend::IntermediateBackend,
+};
+use rattler_build::console_utils::LoggingOutputHandler;
+use serde_json::json;
+use tempfile::TempDir;
+
+use super::model::{
+    TestProjectModel, convert_test_model_to_project_model_v1, load_project_model_from_json,
+};
+
+/// An intermediate backend for a project model whose manifest is written to a
+/// temporary directory. The directory is removed when this is dropped.
+pub struct TestBackend<T: GenerateRecipe> {
+    pub backend: IntermediateBackend<T>,
+    tmp_dir: TempDir,
+}
+
+impl<T: GenerateRecipe + Default> TestBackend<T> {
+    /// Instantiates the backend for the project model of the JSON fixture
+    /// `filename`, after `modify` has been applied to it.
+    pub fn from_fixture(filename: &str, modify: impl FnOnce(&mut TestProjectModel)) -> Self {
+        let tmp_dir = TempDir::new().unwrap();
+        let pixi_manifest = tmp_dir.path().join("pixi.toml");
+
+        let mut original_model = load_project_model_from_json(filename);
+        modify(&mut original_model);
+        let project_model_v1 = convert_test_model_to_project_model_v1(original_model);
+        fs_err::write(&pixi_manifest, toml::to_string(&project_model_v1).unwrap()).unwrap();
+
+        let backend = IntermediateBackend::new(
+            pixi_manifest,
+            Some(tmp_dir.path().to_path_buf()),
+            project_model_v1,
+            Arc::new(T::default()),
+            json!({}),
+            Default::default(),
+            LoggingOutputHandler::default(),
+            None,
+        )
+        .unwrap();
+
+        Self { backend, tmp_dir }
+    }
+
+    /// Returns the temporary directory that contains the manifest.
+    pub fn path(&self) -> &Path {
+        self.tmp_dir.path()
+    }
+}
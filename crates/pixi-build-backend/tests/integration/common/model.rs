@@ -36,6 +36,8 @@ pub struct Target {
     pub host_dependencies: HashMap<String, PackageSpec>,
     pub build_dependencies: HashMap<String, PackageSpec>,
     pub run_dependencies: HashMap<String, PackageSpec>,
+    #[serde(default)]
+    pub run_constraints: HashMap<String, PackageSpec>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -159,6 +161,13 @@ fn convert_target_to_v1(target: &Target) -> TargetV1 {
                 .map(|(name, spec)| (name.clone(), convert_package_spec_to_v1(spec)))
                 .collect(),
         ),
+        run_constraints: Some(
+            target
+                .run_constraints
+                .iter()
+                .map(|(name, spec)| (name.clone(), convert_package_spec_to_v1(spec)))
+                .collect(),
+        ),
     }
 }
 
@@ -1,13 +1,28 @@
 use std::sync::Arc;
 
-use crate::common::model::{convert_test_model_to_project_model_v1, load_project_model_from_json};
-use imp::TestGenerateRecipe;
-use pixi_build_backend::{intermediate_backend::IntermediateBackend, protocol::Protocol};
-use pixi_build_types::procedures::conda_build_v1::{CondaBuildV1Output, CondaBuildV1Params};
+use crate::common::{
+    backend::TestBackend,
+    model::{convert_test_model_to_project_model_v1, load_project_model_from_json},
+};
+use imp::{MultiOutputGenerateRecipe, TestGenerateRecipe, VirtualPackageGenerateRecipe};
+use pixi_build_backend::{
+    intermediate_backend::IntermediateBackend,
+    notifications::{NotificationSink, PHASE_STARTED_METHOD_NAME},
+    protocol::Protocol,
+};
+use pixi_build_types::{
+    PackageSpecV1, SourcePackageSpecV1,
+    procedures::{
+        conda_build_v1::{CondaBuildV1Output, CondaBuildV1Params},
+        conda_outputs::CondaOutputsParams,
+    },
+};
 use rattler_build::console_utils::LoggingOutputHandler;
 use rattler_conda_types::{ChannelUrl, Platform};
+use rattler_digest::{Sha256, compute_file_digest};
 use serde_json::json;
 use tempfile::TempDir;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use url::Url;
 
 #[cfg(test)]
@@ -16,7 +31,8 @@ mod imp {
     use pixi_build_backend::generated_recipe::{
         BackendConfig, DefaultMetadataProvider, GenerateRecipe, GeneratedRecipe, PythonParams,
     };
-    use rattler_conda_types::ChannelUrl;
+    use rattler_conda_types::{ChannelUrl, MatchSpec, ParseStrictness};
+    use recipe_stage0::recipe::{Item, Output, Source, Value};
     use serde::{Deserialize, Serialize};
     use std::{
         collections::HashSet,
@@ -64,8 +80,106 @@ mod imp {
             _variants: &HashSet<pixi_build_backend::variants::NormalizedKey>,
             _channels: Vec<ChannelUrl>,
         ) -> miette::Result<GeneratedRecipe> {
-            GeneratedRecipe::from_model(model.clone(), &mut DefaultMetadataProvider)
-                .into_diagnostic()
+            let mut generated_recipe =
+                GeneratedRecipe::from_model(model.clone(), &mut DefaultMetadataProvider)
+                    .into_diagnostic()?;
+            generated_recipe.recipe.build.script.content = String::from("echo building");
+            Ok(generated_recipe)
+        }
+    }
+
+    /// A generator that selects a run dependency on the `__glibc` virtual
+    /// package.
+    #[derive(Clone, Default)]
+    pub(crate) struct VirtualPackageGenerateRecipe {}
+
+    impl GenerateRecipe for VirtualPackageGenerateRecipe {
+        type Config = TestBackendConfig;
+
+        fn generate_recipe(
+            &self,
+            model: &pixi_build_types::ProjectModelV1,
+            config: &Self::Config,
+            manifest_path: PathBuf,
+            host_platform: rattler_conda_types::Platform,
+            python_params: Option<PythonParams>,
+            variants: &HashSet<pixi_build_backend::variants::NormalizedKey>,
+            channels: Vec<ChannelUrl>,
+        ) -> miette::Result<GeneratedRecipe> {
+            let mut generated_recipe = TestGenerateRecipe::default().generate_recipe(
+                model,
+                config,
+                manifest_path,
+                host_platform,
+                python_params,
+                variants,
+                channels,
+            )?;
+            generated_recipe
+                .recipe
+                .requirements
+                .run
+                .push(Item::new_from_conditional(
+                    String::from("__glibc is defined"),
+                    vec!["__glibc".parse().unwrap()],
+                    Vec::new(),
+                ));
+            Ok(generated_recipe)
+        }
+    }
+
+    /// A generator that splits the project into a library and a development
+    /// package that pins the library.
+    #[derive(Clone, Default)]
+    pub(crate) struct MultiOutputGenerateRecipe {}
+
+    impl GenerateRecipe for MultiOutputGenerateRecipe {
+        type Config = TestBackendConfig;
+
+        fn generate_recipe(
+            &self,
+            model: &pixi_build_types::ProjectModelV1,
+            _config: &Self::Config,
+            _manifest_path: PathBuf,
+            _host_platform: rattler_conda_types::Platform,
+            _python_params: Option<PythonParams>,
+            _variants: &HashSet<pixi_build_backend::variants::NormalizedKey>,
+            _channels: Vec<ChannelUrl>,
+        ) -> miette::Result<GeneratedRecipe> {
+            let mut generated_recipe =
+                GeneratedRecipe::from_model(model.clone(), &mut DefaultMetadataProvider)
+                    .into_diagnostic()?;
+            let recipe = &mut generated_recipe.recipe;
+
+            let mut lib = Output::new("libfoo");
+            lib.requirements = std::mem::take(&mut recipe.requirements);
+
+            let mut dev = Output::new("libfoo-dev");
+            dev.requirements.run = vec![
+                "${{ pin_subpackage('libfoo', exact=True) }}"
+                    .parse()
+                    .unwrap(),
+            ];
+
+            recipe.outputs = vec![lib, dev];
+
+            // The sources are shared by all outputs and resolved relative to
+            // the recipe.
+            recipe.source = vec![Item::Value(Value::Concrete(Source::path("src".into())))];
+
+            // Only the library is identified by a package url.
+            generated_recipe.purls.insert(
+                String::from("libfoo"),
+                ["pkg:generic/libfoo@0.1.0".parse().unwrap()].into(),
+            );
+
+            // The ABI of the host environment of the library is pinned
+            // through a constraint.
+            generated_recipe.host_constraints.insert(
+                String::from("libfoo"),
+                vec![MatchSpec::from_str("openssl >=3,<4", ParseStrictness::Strict).unwrap()],
+            );
+            Ok(generated_recipe)
         }
     }
 }
@@ -129,11 +243,39 @@ async fn test_conda_build_v1() {
     )
     .unwrap();
 
+    // Record the build phases, the script and packaging phases are spans of
+    // rattler-build that are matched by name.
+    let notifications = NotificationSink::default();
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    notifications.connect(sender);
+    let _ = tracing_subscriber::registry()
+        .with(notifications.layer())
+        .try_init();
+
     let conda_build_result = intermediate_backend
         .conda_build_v1(build_params)
         .await
         .unwrap();
 
+    let mut phases = Vec::new();
+    while let Ok(notification) = receiver.try_recv() {
+        let notification: serde_json::Value = serde_json::from_str(&notification).unwrap();
+        if notification["method"] == PHASE_STARTED_METHOD_NAME {
+            phases.push(
+                notification["params"]["phase"]
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+            );
+        }
+    }
+    for phase in ["resolving-variants", "running-script", "packaging"] {
+        assert!(
+            phases.iter().any(|p| p == phase),
+            "the `{phase}` phase was not reported, did rattler-build rename its span? reported: {phases:?}"
+        );
+    }
+
     insta::assert_yaml_snapshot!(conda_build_result, {
         ".output_file" => "[redacted]",
         ".build" => "[redacted]",
@@ -142,3 +284,262 @@ async fn test_conda_build_v1() {
 
     assert!(build_dir.join("debug").join("recipe.yaml").exists());
 }
+
+#[tokio::test]
+async fn test_multi_output_conda_outputs() {
+    let tmp_dir = TempDir::new().unwrap();
+    let tmp_dir_path = tmp_dir.path().to_path_buf();
+
+    // The manifest lives in a subdirectory of the source directory.
+    let package_dir = tmp_dir_path.join("pkg");
+    fs_err::create_dir_all(package_dir.join("src")).unwrap();
+    let pixi_manifest = package_dir.join("pixi.toml");
+
+    let original_model = load_project_model_from_json("minimal_project_model_for_build.json");
+    let project_model_v1 = convert_test_model_to_project_model_v1(original_model);
+
+    fs_err::write(&pixi_manifest, toml::to_string(&project_model_v1).unwrap()).unwrap();
+
+    let intermediate_backend: IntermediateBackend<MultiOutputGenerateRecipe> =
+        IntermediateBackend::new(
+            pixi_manifest.clone(),
+            Some(tmp_dir_path.clone()),
+            project_model_v1,
+            Arc::default(),
+            json!({}),
+            Default::default(),
+            LoggingOutputHandler::default(),
+            None,
+        )
+        .unwrap();
+
+    let result = intermediate_backend
+        .conda_outputs(CondaOutputsParams {
+            channels: vec![],
+            host_platform: Platform::current(),
+            build_platform: Platform::current(),
+            variant_configuration: None,
+            variant_files: None,
+            work_directory: tmp_dir_path.join("work"),
+        })
+        .await
+        .unwrap();
+
+    let mut names = result
+        .outputs
+        .iter()
+        .map(|output| output.metadata.name.as_normalized())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["libfoo", "libfoo-dev"]);
+
+    // Every output depends on the overlay and on its path sources, relative to
+    // the source directory.
+    for output in &result.outputs {
+        let input_globs = output
+            .input_globs
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            input_globs,
+            ["pkg/overlay.yaml", "pkg/src/**"],
+            "unexpected input globs of {}",
+            output.metadata.name.as_normalized()
+        );
+    }
+
+    // The package urls are only reported for the output they identify.
+    for output in &result.outputs {
+        let purls = output
+            .metadata
+            .purls
+            .iter()
+            .flatten()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        match output.metadata.name.as_normalized() {
+            "libfoo" => assert_eq!(purls, ["pkg:generic/libfoo@0.1.0"]),
+            _ => assert!(purls.is_empty()),
+        }
+    }
+
+    // The constraints are only reported for the output they belong to.
+    for output in &result.outputs {
+        let constraints = output
+            .host_dependencies
+            .as_ref()
+            .unwrap()
+            .constraints
+            .iter()
+            .map(|constraint| constraint.name.as_str())
+            .collect::<Vec<_>>();
+        match output.metadata.name.as_normalized() {
+            "libfoo" => assert_eq!(constraints, ["openssl"]),
+            _ => assert!(constraints.is_empty()),
+        }
+        assert!(
+            output
+                .build_dependencies
+                .as_ref()
+                .unwrap()
+                .constraints
+                .is_empty()
+        );
+    }
+
+    // The pinned sibling output should be built from the same source.
+    let dev = result
+        .outputs
+        .iter()
+        .find(|output| output.metadata.name.as_normalized() == "libfoo-dev")
+        .unwrap();
+    let pin = dev
+        .run_dependencies
+        .depends
+        .iter()
+        .find(|dependency| dependency.name == "libfoo")
+        .expect("libfoo-dev should depend on libfoo");
+    assert!(
+        matches!(&pin.spec, PackageSpecV1::Source(SourcePackageSpecV1::Path(path)) if path.path == "."),
+        "expected a source dependency on libfoo, got {:?}",
+        pin.spec
+    );
+}
+
+#[tokio::test]
+async fn test_conda_outputs_reports_run_constraints_of_the_project_model() {
+    let test_backend = TestBackend::<TestGenerateRecipe>::from_fixture(
+        "minimal_project_model_for_build.json",
+        |model| {
+            model.targets.default_target.run_constraints.insert(
+                String::from("openssl"),
+                serde_json::from_value(json!({ "binary": { "version": ">=3,<4" } })).unwrap(),
+            );
+        },
+    );
+
+    let result = test_backend
+        .backend
+        .conda_outputs(CondaOutputsParams {
+            channels: vec![],
+            host_platform: Platform::current(),
+            build_platform: Platform::current(),
+            variant_configuration: None,
+            variant_files: None,
+            work_directory: test_backend.path().join("work"),
+        })
+        .await
+        .unwrap();
+
+    let output = result.outputs.first().expect("expected a single output");
+    let constraints = &output.run_dependencies.constraints;
+    assert_eq!(constraints.len(), 1);
+    assert_eq!(constraints[0].name, "openssl");
+}
+
+#[tokio::test]
+async fn test_conda_outputs_and_conda_build_v1_agree_on_hash() {
+    // The recipe selects on the `__glibc` virtual package.
+    let test_backend = TestBackend::<VirtualPackageGenerateRecipe>::from_fixture(
+        "minimal_project_model_for_build.json",
+        |_| {},
+    );
+    let intermediate_backend = &test_backend.backend;
+
+    let channel_url = ChannelUrl::from(Url::parse("https://prefix.dev/conda-forge").unwrap());
+
+    let outputs = intermediate_backend
+        .conda_outputs(CondaOutputsParams {
+            channels: vec![channel_url.clone()],
+            host_platform: Platform::current(),
+            build_platform: Platform::current(),
+            variant_configuration: None,
+            variant_files: None,
+            work_directory: test_backend.path().join("outputs"),
+        })
+        .await
+        .unwrap();
+    let output = outputs.outputs.first().expect("expected a single output");
+
+    // The detected virtual package is part of the variant of the output.
+    #[cfg(target_os = "linux")]
+    assert!(
+        output.metadata.variant.contains_key("__glibc"),
+        "expected `__glibc` in the variant, got {:?}",
+        output.metadata.variant
+    );
+
+    // Request the output without its variant, so that `conda/build_v1` has
+    // to detect the virtual packages itself to find the same output.
+    let build_result = intermediate_backend
+        .conda_build_v1(CondaBuildV1Params {
+            channels: vec![channel_url],
+            build_prefix: None,
+            host_prefix: None,
+            run_constraints: None,
+            run_dependencies: None,
+            run_exports: None,
+            output: CondaBuildV1Output {
+                name: output.metadata.name.clone(),
+                version: Some(output.metadata.version.clone()),
+                build: Some(output.metadata.build.clone()),
+                subdir: output.metadata.subdir,
+                variant: Default::default(),
+            },
+            work_directory: test_backend.path().join("build"),
+            output_directory: None,
+            editable: None,
+        })
+        .await
+        .unwrap();
+
+    // Both procedures must see the same virtual packages, otherwise the
+    // build string of the built package differs from the one that was
+    // reported.
+    assert_eq!(build_result.build, output.metadata.build);
+}
+
+#[tokio::test]
+async fn test_conda_build_v1_is_reproducible() {
+    let test_backend = TestBackend::<TestGenerateRecipe>::from_fixture(
+        "minimal_project_model_for_build.json",
+        |_| {},
+    );
+
+    let channel_url = ChannelUrl::from(Url::parse("https://prefix.dev/conda-forge").unwrap());
+
+    // Build the same package twice in separate work directories.
+    let mut digests = Vec::new();
+    for work_directory in ["build-1", "build-2"] {
+        let build_result = test_backend
+            .backend
+            .conda_build_v1(CondaBuildV1Params {
+                channels: vec![channel_url.clone()],
+                build_prefix: None,
+                host_prefix: None,
+                run_constraints: None,
+                run_dependencies: None,
+                run_exports: None,
+                output: CondaBuildV1Output {
+                    name: "minimal-package".parse().unwrap(),
+                    version: None,
+                    build: None,
+                    subdir: Platform::current(),
+                    variant: Default::default(),
+                },
+                work_directory: test_backend.path().join(work_directory),
+                output_directory: None,
+                editable: None,
+            })
+            .await
+            .unwrap();
+
+        digests.push(compute_file_digest::<Sha256>(&build_result.output_file).unwrap());
+    }
+
+    // The timestamp is derived from the sources, so both archives are
+    // byte-identical.
+    assert_eq!(digests[0], digests[1]);
+}
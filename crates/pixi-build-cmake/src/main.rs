@@ -5,7 +5,8 @@ use build_script::{BuildPlatform, BuildScriptContext};
 use config::CMakeBackendConfig;
 use miette::IntoDiagnostic;
 use pixi_build_backend::{
-    generated_recipe::{DefaultMetadataProvider, GenerateRecipe, GeneratedRecipe, PythonParams},
+    generated_recipe::{GenerateRecipe, GeneratedRecipe, MetadataProvider, PythonParams},
+    git_metadata::GitMetadataProvider,
     intermediate_backend::IntermediateBackendInstantiator,
     traits::ProjectModel,
 };
@@ -53,9 +54,18 @@ impl GenerateRecipe for CMakeGenerator {
             manifest_path.clone()
         };
 
-        let mut generated_recipe =
-            GeneratedRecipe::from_model(model.clone(), &mut DefaultMetadataProvider)
-                .into_diagnostic()?;
+        // Derive the version from the git tags, if the manifest does not define
+        // one.
+        let mut git_metadata = GitMetadataProvider::new(&manifest_root);
+        let mut generated_recipe = GeneratedRecipe::from_model_with_manifest_root(
+            model.clone(),
+            &mut git_metadata,
+            &manifest_root,
+        )
+        .into_diagnostic()?;
+        generated_recipe
+            .metadata_input_globs
+            .extend(git_metadata.input_globs());
 
         // we need to add compilers
 
@@ -101,6 +111,15 @@ impl GenerateRecipe for CMakeGenerator {
             .host
             .contains_key(&SourcePackageName::from("python"));
 
+        // Launch the compilers through the compiler cache, if one is configured
+        let mut extra_args = config.extra_args.clone();
+        let mut secrets = Vec::new();
+        if let Some(compiler_cache) = config.compiler_cache {
+            compiler_cache.add_to_requirements(&mut requirements.build, &model_dependencies)?;
+            extra_args.extend(compiler_cache.cmake_launcher_args(&compilers));
+            secrets = compiler_cache.host_secrets(&config.env)?;
+        }
+
         let build_script = BuildScriptContext {
             build_platform: if Platform::current().is_windows() {
                 BuildPlatform::Windows
@@ -108,7 +127,7 @@ impl GenerateRecipe for CMakeGenerator {
                 BuildPlatform::Unix
             },
             source_dir: manifest_root.display().to_string(),
-            extra_args: config.extra_args.clone(),
+            extra_args,
             has_host_python,
         }
         .render();
@@ -116,7 +135,7 @@ impl GenerateRecipe for CMakeGenerator {
         generated_recipe.recipe.build.script = Script {
             content: build_script,
             env: config.env.clone(),
-            ..Default::default()
+            secrets,
         };
 
         Ok(generated_recipe)
@@ -177,7 +196,8 @@ mod tests {
 
     use indexmap::IndexMap;
     use pixi_build_backend::{
-        protocol::ProtocolInstantiator, utils::test::intermediate_conda_outputs,
+        cache::CompilerCache, protocol::ProtocolInstantiator,
+        utils::test::intermediate_conda_outputs,
     };
     use pixi_build_types::{
         ProjectModelV1,
@@ -335,6 +355,92 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_compiler_cache_is_used() {
+        let project_model = project_fixture!({
+            "name": "foobar",
+            "version": "0.1.0",
+            "targets": {
+                "defaultTarget": {}
+            }
+        });
+
+        let generated_recipe = CMakeGenerator::default()
+            .generate_recipe(
+                &project_model,
+                &CMakeBackendConfig {
+                    compiler_cache: Some(CompilerCache::Ccache),
+                    ..Default::default()
+                },
+                PathBuf::from("."),
+                Platform::Linux64,
+                None,
+                &HashSet::new(),
+                vec![],
+            )
+            .expect("Failed to generate recipe");
+
+        // ccache is added to the build requirements
+        assert!(
+            generated_recipe
+                .recipe
+                .requirements
+                .build
+                .contains(&"ccache".parse().unwrap()),
+            "ccache should be in build requirements"
+        );
+
+        // and the compilers are launched through it
+        let script = &generated_recipe.recipe.build.script.content;
+        assert!(script.contains("-DCMAKE_C_COMPILER_LAUNCHER=ccache"));
+        assert!(script.contains("-DCMAKE_CXX_COMPILER_LAUNCHER=ccache"));
+    }
+
+    #[test]
+    fn test_license_files_are_discovered() {
+        let project_model = project_fixture!({
+            "name": "foobar",
+            "version": "0.1.0",
+            "targets": {
+                "defaultTarget": {}
+            }
+        });
+
+        let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        for file in ["License.txt", "notice.md", "COPYING.LESSER", "README.md"] {
+            fs_err::write(temp_dir.path().join(file), "").unwrap();
+        }
+
+        let generated_recipe = CMakeGenerator::default()
+            .generate_recipe(
+                &project_model,
+                &CMakeBackendConfig::default(),
+                temp_dir.path().to_path_buf(),
+                Platform::Linux64,
+                None,
+                &HashSet::new(),
+                vec![],
+            )
+            .expect("Failed to generate recipe");
+
+        let about = generated_recipe
+            .recipe
+            .about
+            .expect("the license files should be discovered");
+        assert_eq!(
+            about
+                .license_files()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["COPYING.LESSER", "License.txt", "notice.md"]
+        );
+        assert!(
+            generated_recipe
+                .metadata_input_globs
+                .contains("[lL][iI][cC][eE][nN][sS][eE]*")
+        );
+    }
+
     #[test]
     fn test_cxx_is_not_added_if_gcc_is_already_present() {
         let project_model = project_fixture!({
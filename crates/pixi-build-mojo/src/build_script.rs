@@ -10,6 +10,8 @@ pub struct BuildScriptContext {
     pub bins: Option<Vec<MojoBinConfig>>,
     /// Any packages to create.
     pub pkg: Option<MojoPkgConfig>,
+    /// The compiler cache that the C and C++ compilers are launched through.
+    pub compiler_cache: Option<&'static str>,
 }
 
 impl BuildScriptContext {
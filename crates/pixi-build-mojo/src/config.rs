@@ -5,16 +5,27 @@ use std::{
 
 use indexmap::IndexMap;
 use miette::Error;
-use pixi_build_backend::generated_recipe::BackendConfig;
+use pixi_build_backend::{
+    cache::CompilerCache,
+    config_schema::config_schema,
+    generated_recipe::BackendConfig,
+    packaging::{PackageArchiveType, PackageCompressionLevel, PackagingOptions},
+    sandbox::SandboxConfig,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Top level config struct for the Mojo backend.
-#[derive(Debug, Default, Deserialize, Clone)]
+#[derive(Debug, Default, Deserialize, Clone, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub struct MojoBackendConfig {
     /// Environment Variables
     #[serde(default)]
     pub env: IndexMap<String, String>,
+    /// Host environment variables that are forwarded to the build script as
+    /// secrets, either names or glob patterns like `CCACHE_*`
+    #[serde(default)]
+    pub env_passthrough: Vec<String>,
 
     /// Dir that can be specified for outputting pixi debug state.
     #[serde(alias = "debug_dir")]
@@ -33,6 +44,32 @@ pub struct MojoBackendConfig {
     /// List of compilers to use (e.g., ["mojo", "c", "cxx"])
     /// If not specified, defaults to ["mojo"]
     pub compilers: Option<Vec<String>>,
+    /// Launch the compilers through a compiler cache, `sccache` or `ccache`,
+    /// which is added to the build requirements. The environment variables
+    /// that configure the cache are forwarded to the build script.
+    pub compiler_cache: Option<CompilerCache>,
+
+    /// Run the tests of the generated recipe against the built package.
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub run_tests: Option<bool>,
+    /// The archive format of the built package, `conda` or `tar.bz2`. Defaults
+    /// to `conda`.
+    pub archive_type: Option<PackageArchiveType>,
+    /// The compression level of the built package: `fast`, `default`, `max`
+    /// or a number. Defaults to `fast`.
+    pub compression_level: Option<PackageCompressionLevel>,
+    /// Store the rendered recipe inside the built package. Defaults to
+    /// `false`.
+    #[serde(default)]
+    pub store_recipe: Option<bool>,
+    /// Run the build script in the sandbox of rattler-build, which restricts
+    /// the paths it can access and blocks network access unless allowed.
+    pub sandbox: Option<SandboxConfig>,
+    /// A YAML file, relative to the manifest, that is deep-merged into the
+    /// generated recipe. Defaults to `overlay.yaml` next to the manifest if
+    /// it exists.
+    pub overlay: Option<PathBuf>,
 }
 
 impl BackendConfig for MojoBackendConfig {
@@ -40,16 +77,47 @@ impl BackendConfig for MojoBackendConfig {
         self.debug_dir.as_deref()
     }
 
+    fn json_schema() -> Option<serde_json::Value> {
+        Some(config_schema::<Self>())
+    }
+
+    fn run_tests(&self) -> bool {
+        self.run_tests.unwrap_or(false)
+    }
+
+    fn packaging(&self) -> PackagingOptions {
+        PackagingOptions::from_config(self.archive_type, self.compression_level, self.store_recipe)
+    }
+
+    fn sandbox(&self) -> Option<&SandboxConfig> {
+        self.sandbox.as_ref()
+    }
+
+    fn env_passthrough(&self) -> &[String] {
+        &self.env_passthrough
+    }
+
+    fn overlay(&self) -> Option<&Path> {
+        self.overlay.as_deref()
+    }
+
     /// Merge this configuration with a target-specific configuration.
     /// Target-specific values override base values using the following rules:
     ///
     /// - env: Platform env vars override base, others merge
+    /// - env_passthrough: Platform-specific entries are added to base
     /// - debug_dir: Not allowed to have target specific value
     /// - extra_input_globs: Platform-specific completely replaces base
     /// - bins: Any bins with matching not-None names will be merged,
     ///   Any set-settings on the platform specific pkg override base
     ///   Any bins found only in target_config will be kept
     /// - pkg: Any set-settings on the platform specific pkg override base
+    /// - compiler_cache: Platform-specific takes precedence
+    /// - run_tests: Platform-specific takes precedence
+    /// - archive_type, compression_level, store_recipe: Platform-specific takes
+    ///   precedence
+    /// - sandbox: Platform-specific completely replaces base
+    /// - overlay: Platform-specific takes precedence
     fn merge_with_target_config(&self, target_config: &Self) -> miette::Result<Self> {
         if target_config.debug_dir.is_some() {
             miette::bail!("`debug_dir` cannot have a target specific value");
@@ -116,6 +184,17 @@ impl BackendConfig for MojoBackendConfig {
                 merged_env.extend(target_config.env.clone());
                 merged_env
             },
+            env_passthrough: {
+                let mut merged_env_passthrough = self.env_passthrough.clone();
+                merged_env_passthrough.extend(
+                    target_config
+                        .env_passthrough
+                        .iter()
+                        .filter(|name| !self.env_passthrough.contains(name))
+                        .cloned(),
+                );
+                merged_env_passthrough
+            },
             debug_dir: self.debug_dir.clone(),
             extra_input_globs: if target_config.extra_input_globs.is_empty() {
                 self.extra_input_globs.clone()
@@ -128,6 +207,19 @@ impl BackendConfig for MojoBackendConfig {
                 .compilers
                 .clone()
                 .or_else(|| self.compilers.clone()),
+            compiler_cache: target_config.compiler_cache.or(self.compiler_cache),
+            run_tests: target_config.run_tests.or(self.run_tests),
+            archive_type: target_config.archive_type.or(self.archive_type),
+            compression_level: target_config.compression_level.or(self.compression_level),
+            store_recipe: target_config.store_recipe.or(self.store_recipe),
+            sandbox: target_config
+                .sandbox
+                .clone()
+                .or_else(|| self.sandbox.clone()),
+            overlay: target_config
+                .overlay
+                .clone()
+                .or_else(|| self.overlay.clone()),
         })
     }
 }
@@ -178,7 +270,7 @@ impl MojoBackendConfig {
 }
 
 /// Config object for a Mojo binary.
-#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub struct MojoBinConfig {
     /// Name of the binary.
@@ -319,7 +411,7 @@ impl MojoBinConfig {
 }
 
 /// Config object for a Mojo package.
-#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub struct MojoPkgConfig {
     /// Name to give the mojo package (.mojopkg suffix will be added).
@@ -445,12 +537,18 @@ pub fn clean_project_name(s: &str) -> String {
 
 #[cfg(test)]
 mod tests {
+    use pixi_build_backend::config_schema::assert_schema_up_to_date;
     use rstest::rstest;
     use serde_json::json;
     use tempfile::TempDir;
 
     use super::*;
 
+    #[test]
+    fn test_config_schema_is_up_to_date() {
+        assert_schema_up_to_date::<MojoBackendConfig>("pixi-build-mojo");
+    }
+
     #[test]
     fn test_ensure_deseralize_from_empty() {
         let json_data = json!({});
@@ -4,9 +4,10 @@ mod config;
 use build_script::BuildScriptContext;
 use config::{MojoBackendConfig, clean_project_name};
 use miette::{Error, IntoDiagnostic};
-use pixi_build_backend::generated_recipe::DefaultMetadataProvider;
 use pixi_build_backend::{
-    generated_recipe::{GenerateRecipe, GeneratedRecipe, PythonParams},
+    cache::CompilerCache,
+    generated_recipe::{GenerateRecipe, GeneratedRecipe, MetadataProvider, PythonParams},
+    git_metadata::GitMetadataProvider,
     intermediate_backend::IntermediateBackendInstantiator,
     traits::ProjectModel,
 };
@@ -50,9 +51,18 @@ impl GenerateRecipe for MojoGenerator {
             manifest_path.clone()
         };
 
-        let mut generated_recipe =
-            GeneratedRecipe::from_model(model.clone(), &mut DefaultMetadataProvider)
-                .into_diagnostic()?;
+        // Derive the version from the git tags, if the manifest does not define
+        // one.
+        let mut git_metadata = GitMetadataProvider::new(&manifest_root);
+        let mut generated_recipe = GeneratedRecipe::from_model_with_manifest_root(
+            model.clone(),
+            &mut git_metadata,
+            &manifest_root,
+        )
+        .into_diagnostic()?;
+        generated_recipe
+            .metadata_input_globs
+            .extend(git_metadata.input_globs());
 
         let cleaned_project_name = clean_project_name(
             generated_recipe
@@ -117,17 +127,28 @@ impl GenerateRecipe for MojoGenerator {
             variants,
         );
 
+        // Launch the compilers through the compiler cache, if one is configured.
+        // The mojo compiler itself is not cached, so this requires additional
+        // compilers.
+        let compiler_cache = config.compiler_cache.filter(|_| !compilers.is_empty());
+        let mut secrets = Vec::new();
+        if let Some(compiler_cache) = compiler_cache {
+            compiler_cache.add_to_requirements(&mut requirements.build, &model_dependencies)?;
+            secrets = compiler_cache.host_secrets(&config.env)?;
+        }
+
         let build_script = BuildScriptContext {
             source_dir: manifest_root.display().to_string(),
             bins,
             pkg,
+            compiler_cache: compiler_cache.map(CompilerCache::tool),
         }
         .render();
 
         generated_recipe.recipe.build.script = Script {
             content: build_script,
             env: config.env.clone(),
-            ..Default::default()
+            secrets,
         };
 
         generated_recipe.build_input_globs = Self::globs().collect::<BTreeSet<_>>();
@@ -11,6 +11,11 @@ pub struct BuildScriptContext {
     pub editable: bool,
     pub extra_args: Vec<String>,
     pub manifest_root: PathBuf,
+    /// The compiler cache that the C and C++ compilers are launched through.
+    pub compiler_cache: Option<&'static str>,
+    /// The wrapper that `rustc` is launched through, if the package is built
+    /// with a Rust compiler and the compiler cache supports Rust.
+    pub rustc_wrapper: Option<&'static str>,
 }
 
 #[derive(Default, Serialize)]
@@ -1,9 +1,16 @@
 use indexmap::IndexMap;
-use pixi_build_backend::generated_recipe::BackendConfig;
+use pixi_build_backend::{
+    cache::CompilerCache,
+    config_schema::config_schema,
+    generated_recipe::BackendConfig,
+    packaging::{PackageArchiveType, PackageCompressionLevel, PackagingOptions},
+    sandbox::SandboxConfig,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone, JsonSchema)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct PythonBackendConfig {
     /// True if the package should be build as a python noarch package. Defaults
@@ -16,6 +23,10 @@ pub struct PythonBackendConfig {
     /// Environment Variables
     #[serde(default)]
     pub env: IndexMap<String, String>,
+    /// Host environment variables that are forwarded to the build script as
+    /// secrets, either names or glob patterns like `CCACHE_*`
+    #[serde(default)]
+    pub env_passthrough: Vec<String>,
     /// Deprecated. Setting this has no effect; debug data is always written to
     /// the `debug` subdirectory of the work directory.
     #[serde(alias = "debug_dir")]
@@ -26,9 +37,34 @@ pub struct PythonBackendConfig {
     /// List of compilers to use (e.g., ["c", "cxx", "rust"])
     /// If not specified, no compilers are added (since most Python packages are pure Python)
     pub compilers: Option<Vec<String>>,
+    /// Launch the compilers through a compiler cache, `sccache` or `ccache`,
+    /// which is added to the build requirements. The environment variables
+    /// that configure the cache are forwarded to the build script.
+    pub compiler_cache: Option<CompilerCache>,
     /// Ignore the pyproject.toml manifest and rely only on the project model.
     #[serde(default)]
     pub ignore_pyproject_manifest: Option<bool>,
+    /// Run the tests of the generated recipe, e.g. imports of the Python
+    /// modules of the package, after the build. Defaults to `false`.
+    #[serde(default)]
+    pub run_tests: Option<bool>,
+    /// The archive format of the built package, `conda` or `tar.bz2`. Defaults
+    /// to `conda`.
+    pub archive_type: Option<PackageArchiveType>,
+    /// The compression level of the built package: `fast`, `default`, `max`
+    /// or a number. Defaults to `fast`.
+    pub compression_level: Option<PackageCompressionLevel>,
+    /// Store the rendered recipe inside the built package. Defaults to
+    /// `false`.
+    #[serde(default)]
+    pub store_recipe: Option<bool>,
+    /// Run the build script in the sandbox of rattler-build, which restricts
+    /// the paths it can access and blocks network access unless allowed.
+    pub sandbox: Option<SandboxConfig>,
+    /// A YAML file, relative to the manifest, that is deep-merged into the
+    /// generated recipe. Defaults to `overlay.yaml` next to the manifest if
+    /// it exists.
+    pub overlay: Option<PathBuf>,
 }
 
 impl PythonBackendConfig {
@@ -53,13 +89,44 @@ impl BackendConfig for PythonBackendConfig {
         self.debug_dir.as_deref()
     }
 
+    fn json_schema() -> Option<serde_json::Value> {
+        Some(config_schema::<Self>())
+    }
+
+    fn run_tests(&self) -> bool {
+        self.run_tests.unwrap_or(false)
+    }
+
+    fn packaging(&self) -> PackagingOptions {
+        PackagingOptions::from_config(self.archive_type, self.compression_level, self.store_recipe)
+    }
+
+    fn sandbox(&self) -> Option<&SandboxConfig> {
+        self.sandbox.as_ref()
+    }
+
+    fn env_passthrough(&self) -> &[String] {
+        &self.env_passthrough
+    }
+
+    fn overlay(&self) -> Option<&Path> {
+        self.overlay.as_deref()
+    }
+
     /// Merge this configuration with a target-specific configuration.
     /// Target-specific values override base values using the following rules:
     /// - noarch: Platform-specific takes precedence (critical for cross-platform)
     /// - env: Platform env vars override base, others merge
+    /// - env_passthrough: Platform-specific entries are added to base
     /// - extra_args: Platform-specific completely replaces base
     /// - debug_dir: Not allowed to have target specific value
     /// - extra_input_globs: Platform-specific completely replaces base
+    /// - compiler_cache: Platform-specific takes precedence
+    /// - run_tests: Platform-specific takes precedence
+    /// - archive_type, compression_level, store_recipe: Platform-specific takes
+    ///   precedence
+    /// - sandbox: Platform-specific completely replaces base
+    /// - overlay: Platform-specific takes precedence
     fn merge_with_target_config(&self, target_config: &Self) -> miette::Result<Self> {
         if target_config.debug_dir.is_some() {
             miette::bail!("`debug_dir` cannot have a target specific value");
@@ -72,6 +139,17 @@ impl BackendConfig for PythonBackendConfig {
                 merged_env.extend(target_config.env.clone());
                 merged_env
             },
+            env_passthrough: {
+                let mut merged_env_passthrough = self.env_passthrough.clone();
+                merged_env_passthrough.extend(
+                    target_config
+                        .env_passthrough
+                        .iter()
+                        .filter(|name| !self.env_passthrough.contains(name))
+                        .cloned(),
+                );
+                merged_env_passthrough
+            },
             debug_dir: self.debug_dir.clone(),
             extra_args: if target_config.extra_args.is_empty() {
                 self.extra_args.clone()
@@ -87,9 +165,22 @@ impl BackendConfig for PythonBackendConfig {
                 .compilers
                 .clone()
                 .or_else(|| self.compilers.clone()),
+            compiler_cache: target_config.compiler_cache.or(self.compiler_cache),
             ignore_pyproject_manifest: target_config
                 .ignore_pyproject_manifest
                 .or(self.ignore_pyproject_manifest),
+            run_tests: target_config.run_tests.or(self.run_tests),
+            archive_type: target_config.archive_type.or(self.archive_type),
+            compression_level: target_config.compression_level.or(self.compression_level),
+            store_recipe: target_config.store_recipe.or(self.store_recipe),
+            sandbox: target_config
+                .sandbox
+                .clone()
+                .or_else(|| self.sandbox.clone()),
+            overlay: target_config
+                .overlay
+                .clone()
+                .or_else(|| self.overlay.clone()),
         })
     }
 }
@@ -97,10 +188,17 @@ impl BackendConfig for PythonBackendConfig {
 #[cfg(test)]
 mod tests {
     use super::PythonBackendConfig;
-    use pixi_build_backend::generated_recipe::BackendConfig;
+    use pixi_build_backend::{
+        config_schema::assert_schema_up_to_date, generated_recipe::BackendConfig,
+    };
     use serde_json::json;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_config_schema_is_up_to_date() {
+        assert_schema_up_to_date::<PythonBackendConfig>("pixi-build-python");
+    }
+
     #[test]
     fn test_ensure_deseralize_from_empty() {
         let json_data = json!({});
@@ -116,11 +214,19 @@ mod tests {
         let base_config = PythonBackendConfig {
             noarch: Some(true),
             env: base_env,
+            env_passthrough: Vec::new(),
             debug_dir: Some(PathBuf::from("/base/debug")),
             extra_args: vec!["-Cbuilddir=mybuilddir".into()],
             extra_input_globs: vec!["*.base".to_string()],
             compilers: Some(vec!["c".to_string()]),
+            compiler_cache: None,
             ignore_pyproject_manifest: Some(true),
+            run_tests: Some(true),
+            archive_type: None,
+            compression_level: None,
+            store_recipe: None,
+            sandbox: None,
+            overlay: None,
         };
 
         let mut target_env = indexmap::IndexMap::new();
@@ -130,11 +236,19 @@ mod tests {
         let target_config = PythonBackendConfig {
             noarch: Some(false),
             env: target_env,
+            env_passthrough: Vec::new(),
             debug_dir: None,
             extra_args: vec![],
             extra_input_globs: vec!["*.target".to_string()],
             compilers: Some(vec!["cxx".to_string(), "rust".to_string()]),
+            compiler_cache: None,
             ignore_pyproject_manifest: Some(false),
+            run_tests: Some(false),
+            archive_type: None,
+            compression_level: None,
+            store_recipe: None,
+            sandbox: None,
+            overlay: None,
         };
 
         let merged = base_config
@@ -168,6 +282,9 @@ mod tests {
         );
         // ignore_pyproject_manifest should use target value
         assert_eq!(merged.ignore_pyproject_manifest, Some(false));
+
+        // run_tests should use the target value
+        assert!(!merged.run_tests());
     }
 
     #[test]
@@ -178,11 +295,19 @@ mod tests {
         let base_config = PythonBackendConfig {
             noarch: Some(true),
             env: base_env,
+            env_passthrough: Vec::new(),
             debug_dir: Some(PathBuf::from("/base/debug")),
             extra_args: vec!["-Cbuilddir=mybuilddir".into()],
             extra_input_globs: vec!["*.base".to_string()],
             compilers: None,
+            compiler_cache: None,
             ignore_pyproject_manifest: Some(true),
+            run_tests: Some(true),
+            archive_type: None,
+            compression_level: None,
+            store_recipe: None,
+            sandbox: None,
+            overlay: None,
         };
 
         let empty_target_config = PythonBackendConfig::default();
@@ -198,6 +323,7 @@ mod tests {
         assert_eq!(merged.extra_input_globs, vec!["*.base".to_string()]);
         assert_eq!(merged.compilers, None);
         assert_eq!(merged.ignore_pyproject_manifest, Some(true));
+        assert!(merged.run_tests());
     }
 
     #[test]
@@ -7,7 +7,9 @@ use config::PythonBackendConfig;
 use miette::IntoDiagnostic;
 use pixi_build_backend::variants::NormalizedKey;
 use pixi_build_backend::{
-    generated_recipe::{GenerateRecipe, GeneratedRecipe, PythonParams},
+    cache::CompilerCache,
+    generated_recipe::{GenerateRecipe, GeneratedRecipe, MetadataProvider, PythonParams},
+    git_metadata::GitMetadataProvider,
     intermediate_backend::IntermediateBackendInstantiator,
     traits::ProjectModel,
 };
@@ -80,16 +82,23 @@ impl GenerateRecipe for PythonGenerator {
             manifest_path.clone()
         };
 
-        let mut pyproject_metadata_provider = PyprojectMetadataProvider::new(
+        // Read the metadata from the pyproject.toml, and derive the version from
+        // the git tags if it is not defined there, e.g. because it is dynamic.
+        let mut metadata_provider = PyprojectMetadataProvider::new(
             &manifest_root,
             config
                 .ignore_pyproject_manifest
                 .is_some_and(|ignore| ignore),
-        );
+        )
+        .or(GitMetadataProvider::new(&manifest_root));
 
-        let mut generated_recipe =
-            GeneratedRecipe::from_model(model.clone(), &mut pyproject_metadata_provider)
-                .into_diagnostic()?;
+        let mut generated_recipe = GeneratedRecipe::from_model_with_manifest_root(
+            model.clone(),
+            &mut metadata_provider,
+            &manifest_root,
+        )
+        .into_diagnostic()?;
+        let pyproject_metadata_provider = metadata_provider.first();
 
         let requirements = &mut generated_recipe.recipe.requirements;
 
@@ -150,6 +159,15 @@ impl GenerateRecipe for PythonGenerator {
             variants,
         );
 
+        // Launch the compilers through the compiler cache, if one is configured.
+        // Without compilers there is nothing to cache.
+        let compiler_cache = config.compiler_cache.filter(|_| !compilers.is_empty());
+        let mut secrets = Vec::new();
+        if let Some(compiler_cache) = compiler_cache {
+            compiler_cache.add_to_requirements(&mut requirements.build, &model_dependencies)?;
+            secrets = compiler_cache.host_secrets(&config.env)?;
+        }
+
         let build_platform = Platform::current();
 
         // TODO: remove this env var override as soon as we have profiles
@@ -167,6 +185,10 @@ impl GenerateRecipe for PythonGenerator {
             editable,
             extra_args: config.extra_args.clone(),
             manifest_root: manifest_root.clone(),
+            compiler_cache: compiler_cache.map(CompilerCache::tool),
+            rustc_wrapper: compiler_cache
+                .filter(|_| compilers.iter().any(|compiler| compiler == "rust"))
+                .and_then(CompilerCache::rustc_wrapper),
         }
         .render();
 
@@ -210,13 +232,26 @@ impl GenerateRecipe for PythonGenerator {
         generated_recipe.recipe.build.script = Script {
             content: build_script,
             env: config.env.clone(),
-            ..Script::default()
+            secrets,
         };
 
         // Add the metadata input globs from the MetadataProvider
         generated_recipe
             .metadata_input_globs
-            .extend(pyproject_metadata_provider.input_globs());
+            .extend(metadata_provider.input_globs());
+
+        // Identify the package as the PyPI package it was built from.
+        let version = generated_recipe.recipe.package.version.to_string();
+        if let Some(purl) = pyproject_metadata_provider
+            .purl(&version)
+            .into_diagnostic()?
+        {
+            generated_recipe
+                .purls
+                .entry(generated_recipe.recipe.package.name.to_string())
+                .or_default()
+                .insert(purl);
+        }
 
         Ok(generated_recipe)
     }
@@ -640,6 +675,50 @@ version = "0.1.0"
         );
     }
 
+    #[test]
+    fn test_compiler_cache_wraps_rustc() {
+        let project_model = project_fixture!({
+            "name": "foobar",
+            "version": "0.1.0",
+            "targets": {
+                "defaultTarget": {}
+            }
+        });
+
+        let generate = |compilers: &[&str]| {
+            PythonGenerator::default()
+                .generate_recipe(
+                    &project_model,
+                    &PythonBackendConfig {
+                        compilers: Some(compilers.iter().map(ToString::to_string).collect()),
+                        compiler_cache: Some(CompilerCache::Sccache),
+                        ignore_pyproject_manifest: Some(true),
+                        ..Default::default()
+                    },
+                    PathBuf::from("."),
+                    Platform::Linux64,
+                    None,
+                    &HashSet::new(),
+                    vec![],
+                )
+                .expect("Failed to generate recipe")
+                .recipe
+                .build
+                .script
+                .content
+        };
+
+        let script = generate(&["c", "rust"]);
+        assert!(script.contains("RUSTC_WRAPPER=sccache"));
+        if !Platform::current().is_windows() {
+            // The compilers are only wrapped if they are set.
+            assert!(script.contains(r#"if [ -n "${CC:+set}" ]; then export CC="sccache $CC"; fi"#));
+        }
+
+        let script = generate(&["c"]);
+        assert!(!script.contains("RUSTC_WRAPPER"));
+    }
+
     #[test]
     fn test_default_no_compilers_when_not_specified() {
         let project_model = project_fixture!({
@@ -4,7 +4,7 @@ use miette::Diagnostic;
 use once_cell::unsync::OnceCell;
 use pixi_build_backend::generated_recipe::MetadataProvider;
 use pyproject_toml::PyProjectToml;
-use rattler_conda_types::{ParseVersionError, Version};
+use rattler_conda_types::{PackageUrl, ParseVersionError, Version};
 
 #[derive(Debug, thiserror::Error, Diagnostic)]
 pub enum MetadataError {
@@ -54,29 +54,54 @@ impl PyprojectMetadataProvider {
         })
     }
 
-    /// Returns the set of globs that match files that influence the metadata of
-    /// this package.
-    ///
-    /// This includes the package's own `pyproject.toml` file. These globs
-    /// can be used for incremental builds to determine when metadata might
-    /// have changed.
-    ///
-    /// # Returns
+    /// Returns the package URL (purl) of the PyPI package that is defined in
+    /// the pyproject.toml manifest, e.g. `pkg:pypi/my-package@1.0.0`.
     ///
-    /// A `BTreeSet` of glob patterns as strings. Common patterns include:
-    /// - `"pyproject.toml"` - The package's manifest file
-    pub fn input_globs(&self) -> BTreeSet<String> {
-        let mut input_globs = BTreeSet::new();
-
-        let Some(_) = self.pyproject_manifest.get() else {
-            return input_globs;
+    /// The version from the manifest is used if it is defined, otherwise the
+    /// `fallback_version`, e.g. when the version is dynamic. Returns `None` if
+    /// there is no manifest, if it is ignored or if the package url is
+    /// invalid.
+    pub fn purl(&self, fallback_version: &str) -> Result<Option<PackageUrl>, MetadataError> {
+        if self.ignore_pyproject_manifest || !self.manifest_root.join("pyproject.toml").is_file() {
+            return Ok(None);
+        }
+        let Some(project) = self.ensure_manifest_project()? else {
+            return Ok(None);
         };
 
-        // Add the pyproject.toml manifest file itself.
-        input_globs.insert(String::from("pyproject.toml"));
+        // PyPI purls use the normalized name of the package.
+        let version = project
+            .version
+            .as_ref()
+            .map_or_else(|| fallback_version.to_string(), |v| v.to_string());
+        match PackageUrl::builder(String::from("pypi"), normalize_pypi_name(&project.name))
+            .with_version(version)
+            .build()
+        {
+            Ok(purl) => Ok(Some(purl)),
+            Err(err) => {
+                tracing::warn!("ignoring the package url of `{}`: {err}", project.name);
+                Ok(None)
+            }
+        }
+    }
+}
 
-        input_globs
+/// Normalizes the name of a Python package as defined in PEP 503, i.e. the
+/// name is lowercased and runs of `-`, `_` and `.` are replaced by a single
+/// `-`.
+fn normalize_pypi_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.extend(c.to_lowercase());
+        }
     }
+    normalized
 }
 
 impl MetadataProvider for PyprojectMetadataProvider {
@@ -223,6 +248,35 @@ impl MetadataProvider for PyprojectMetadataProvider {
                     .cloned()
             }))
     }
+
+    /// Returns the set of globs that match files that influence the metadata of
+    /// this package.
+    ///
+    /// This includes the package's own `pyproject.toml` file. These globs
+    /// can be used for incremental builds to determine when metadata might
+    /// have changed.
+    ///
+    /// # Returns
+    ///
+    /// A `BTreeSet` of glob patterns as strings. Common patterns include:
+    /// - `"pyproject.toml"` - The package's manifest file
+    fn input_globs(&self) -> BTreeSet<String> {
+        let mut input_globs = BTreeSet::new();
+
+        let Some(_) = self.pyproject_manifest.get() else {
+            return input_globs;
+        };
+
+        // Add the pyproject.toml manifest file itself.
+        input_globs.insert(String::from("pyproject.toml"));
+
+        input_globs
+    }
+
+    /// All values are read from the `pyproject.toml` file.
+    fn value_source(&self, _field: &str) -> Option<String> {
+        Some(String::from("pyproject.toml"))
+    }
 }
 
 impl PyprojectMetadataProvider {
@@ -307,6 +361,39 @@ Documentation = "https://docs.example.com"
         );
     }
 
+    #[test]
+    fn test_purl() {
+        let temp_dir = create_temp_pyproject_project(
+            r#"
+[project]
+name = "My__Package.Name"
+version = "1.0.0+local"
+"#,
+        );
+        let provider = create_metadata_provider(temp_dir.path());
+        let purl = provider.purl("0.1.0").unwrap().unwrap();
+        assert_eq!(purl.package_type(), "pypi");
+        assert_eq!(purl.name(), "my-package-name");
+        assert_eq!(purl.version(), Some("1.0.0+local"));
+
+        // The fallback version is used for dynamic versions.
+        let temp_dir = create_temp_pyproject_project(
+            r#"
+[project]
+name = "my-package"
+dynamic = ["version"]
+"#,
+        );
+        let provider = create_metadata_provider(temp_dir.path());
+        assert_eq!(
+            provider.purl("0.1.0").unwrap().map(|purl| purl.to_string()),
+            Some(String::from("pkg:pypi/my-package@0.1.0"))
+        );
+
+        let ignoring_provider = PyprojectMetadataProvider::new(temp_dir.path(), true);
+        assert_eq!(ignoring_provider.purl("0.1.0").unwrap(), None);
+    }
+
     #[test]
     fn test_license_from_file() {
         let pyproject_toml_content = r#"
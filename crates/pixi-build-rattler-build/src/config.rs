@@ -1,8 +1,14 @@
-use pixi_build_backend::generated_recipe::BackendConfig;
+use pixi_build_backend::{
+    config_schema::config_schema,
+    generated_recipe::BackendConfig,
+    packaging::{PackageArchiveType, PackageCompressionLevel, PackagingOptions},
+    sandbox::SandboxConfig,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone, JsonSchema)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct RattlerBuildBackendConfig {
     /// Deprecated. Setting this has no effect; debug data is always written to
@@ -12,6 +18,19 @@ pub struct RattlerBuildBackendConfig {
     /// Extra input globs to include in addition to the default ones
     #[serde(default)]
     pub extra_input_globs: Vec<String>,
+    /// The archive format of the built package, `conda` or `tar.bz2`. Defaults
+    /// to `conda`.
+    pub archive_type: Option<PackageArchiveType>,
+    /// The compression level of the built package: `fast`, `default`, `max`
+    /// or a number. Defaults to `fast`.
+    pub compression_level: Option<PackageCompressionLevel>,
+    /// Store the rendered recipe inside the built package. Defaults to
+    /// `false`.
+    #[serde(default)]
+    pub store_recipe: Option<bool>,
+    /// Run the build script in the sandbox of rattler-build, which restricts
+    /// the paths it can access and blocks network access unless allowed.
+    pub sandbox: Option<SandboxConfig>,
 }
 
 impl BackendConfig for RattlerBuildBackendConfig {
@@ -19,10 +38,25 @@ impl BackendConfig for RattlerBuildBackendConfig {
         self.debug_dir.as_deref()
     }
 
+    fn json_schema() -> Option<serde_json::Value> {
+        Some(config_schema::<Self>())
+    }
+
+    fn packaging(&self) -> PackagingOptions {
+        PackagingOptions::from_config(self.archive_type, self.compression_level, self.store_recipe)
+    }
+
+    fn sandbox(&self) -> Option<&SandboxConfig> {
+        self.sandbox.as_ref()
+    }
+
     /// Merge this configuration with a target-specific configuration.
     /// Target-specific values override base values using the following rules:
     /// - debug_dir: Not allowed to have target specific value
     /// - extra_input_globs: Platform-specific completely replaces base
+    /// - archive_type, compression_level, store_recipe: Platform-specific takes
+    ///   precedence
+    /// - sandbox: Platform-specific completely replaces base
     fn merge_with_target_config(&self, target_config: &Self) -> miette::Result<Self> {
         if target_config.debug_dir.is_some() {
             miette::bail!("`debug_dir` cannot have a target specific value");
@@ -35,6 +69,13 @@ impl BackendConfig for RattlerBuildBackendConfig {
             } else {
                 target_config.extra_input_globs.clone()
             },
+            archive_type: target_config.archive_type.or(self.archive_type),
+            compression_level: target_config.compression_level.or(self.compression_level),
+            store_recipe: target_config.store_recipe.or(self.store_recipe),
+            sandbox: target_config
+                .sandbox
+                .clone()
+                .or_else(|| self.sandbox.clone()),
         })
     }
 }
@@ -42,10 +83,17 @@ impl BackendConfig for RattlerBuildBackendConfig {
 #[cfg(test)]
 mod tests {
     use super::RattlerBuildBackendConfig;
-    use pixi_build_backend::generated_recipe::BackendConfig;
+    use pixi_build_backend::{
+        config_schema::assert_schema_up_to_date, generated_recipe::BackendConfig,
+    };
     use serde_json::json;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_config_schema_is_up_to_date() {
+        assert_schema_up_to_date::<RattlerBuildBackendConfig>("pixi-build-rattler-build");
+    }
+
     #[test]
     fn test_ensure_deseralize_from_empty() {
         let json_data = json!({});
@@ -57,11 +105,19 @@ mod tests {
         let base_config = RattlerBuildBackendConfig {
             debug_dir: Some(PathBuf::from("/base/debug")),
             extra_input_globs: vec!["*.base".to_string()],
+            archive_type: None,
+            compression_level: None,
+            store_recipe: None,
+            sandbox: None,
         };
 
         let target_config = RattlerBuildBackendConfig {
             debug_dir: None,
             extra_input_globs: vec!["*.target".to_string()],
+            archive_type: None,
+            compression_level: None,
+            store_recipe: None,
+            sandbox: None,
         };
 
         let merged = base_config
@@ -80,6 +136,10 @@ mod tests {
         let base_config = RattlerBuildBackendConfig {
             debug_dir: Some(PathBuf::from("/base/debug")),
             extra_input_globs: vec!["*.base".to_string()],
+            archive_type: None,
+            compression_level: None,
+            store_recipe: None,
+            sandbox: None,
         };
 
         let empty_target_config = RattlerBuildBackendConfig::default();
@@ -5,12 +5,18 @@ use std::{
 };
 
 use crate::{config::RattlerBuildBackendConfig, rattler_build::RattlerBuildBackend};
-use miette::{Context, IntoDiagnostic};
 use pixi_build_backend::specs_conversion::from_build_v1_args_to_finalized_dependencies;
 use pixi_build_backend::{
-    dependencies::{convert_binary_dependencies, convert_dependencies},
+    build_timestamp::BuildTimestamp,
+    config_diagnostics::{ConfigSection, parse_config},
+    dependencies::{
+        convert_binary_dependencies, convert_dependencies, pin_compatible_to_host_prefix,
+        resolve_pin_compatible_dependencies,
+    },
+    generated_recipe::BackendConfig,
     intermediate_backend::{conda_build_v1_directories, find_matching_output},
     protocol::{Protocol, ProtocolInstantiator},
+    sandbox::{SandboxConfig, run_sandboxed},
     tools::LoadedVariantConfig,
 };
 use pixi_build_types::{
@@ -33,10 +39,10 @@ use rattler_build::{
     recipe::{Jinja, ParsingError, Recipe, parser::find_outputs_from_src, variable::Variable},
     selectors::SelectorConfig,
     tool_configuration::Configuration,
-    types::{PackageIdentifier, PackagingSettings},
+    types::PackageIdentifier,
     variant_config::{ParseErrors, VariantConfig},
 };
-use rattler_conda_types::{Platform, compression_level::CompressionLevel, package::ArchiveType};
+use rattler_conda_types::Platform;
 use tracing::warn;
 pub struct RattlerBuildBackendInstantiator {
     logging_output_handler: LoggingOutputHandler,
@@ -139,6 +145,14 @@ impl Protocol for RattlerBuildBackend {
                 continue;
             }
 
+            // Every output is invalidated by its own sources and the recipe.
+            let input_globs = build_input_globs(
+                &self.manifest_root,
+                &self.recipe_source.path,
+                Some(path_sources(recipe.sources())),
+                self.config.extra_input_globs.clone(),
+            )?;
+
             let jinja = Jinja::new(selector_config);
             let build_number = recipe.build().number;
             let build_string = recipe.build().string().resolve(&hash, build_number, &jinja);
@@ -189,13 +203,21 @@ impl Protocol for RattlerBuildBackend {
                 }),
                 run_dependencies: CondaOutputDependencies {
                     depends: convert_dependencies(
-                        recipe.requirements.run,
+                        resolve_pin_compatible_dependencies(
+                            recipe.requirements.run,
+                            &HashMap::new(),
+                            &variant,
+                        )?,
                         &BTreeMap::default(), // Variants are not applied to run dependencies
                         &subpackages,
                         &local_source_packages,
                     )?,
                     constraints: convert_binary_dependencies(
-                        recipe.requirements.run_constraints,
+                        resolve_pin_compatible_dependencies(
+                            recipe.requirements.run_constraints,
+                            &HashMap::new(),
+                            &variant,
+                        )?,
                         &BTreeMap::default(), // Variants are not applied to run constraints
                         &subpackages,
                     )?,
@@ -245,9 +267,7 @@ impl Protocol for RattlerBuildBackend {
                     )?,
                 },
 
-                // The input globs are the same for all outputs
-                input_globs: None,
-                // TODO: Implement caching
+                input_globs: Some(input_globs),
             });
         }
 
@@ -332,6 +352,34 @@ impl Protocol for RattlerBuildBackend {
             .with_environments_externally_managed(true)
             .finish();
 
+        // Derive the timestamp from the sources so that rebuilding the same
+        // sources produces an identical package.
+        let timestamp = BuildTimestamp::determine(
+            &self.manifest_root,
+            &self.recipe_source.path,
+            &build_input_globs(
+                &self.manifest_root,
+                &self.recipe_source.path,
+                Some(path_sources(discovered_output.recipe.sources())),
+                self.config.extra_input_globs.clone(),
+            )?,
+        )?;
+
+        let packaging = self.config.packaging();
+        // The `pin_compatible` run dependencies were pinned to the variant when
+        // the outputs were determined, now the host prefix is known.
+        let requirements = &discovered_output.recipe.requirements;
+        let run_dependencies = pin_compatible_to_host_prefix(
+            &requirements.run,
+            params.host_prefix.as_ref(),
+            params.run_dependencies,
+        )?;
+        let run_constraints = pin_compatible_to_host_prefix(
+            &requirements.run_constraints,
+            params.host_prefix.as_ref(),
+            params.run_constraints,
+        )?;
+
         let output = Output {
             recipe: discovered_output.recipe,
             build_configuration: BuildConfiguration {
@@ -350,23 +398,24 @@ impl Protocol for RattlerBuildBackend {
                 channels: vec![],
                 channel_priority: Default::default(),
                 solve_strategy: Default::default(),
-                timestamp: chrono::Utc::now(),
+                timestamp: timestamp.timestamp,
                 subpackages: BTreeMap::new(),
-                packaging_settings: PackagingSettings::from_args(
-                    ArchiveType::Conda,
-                    CompressionLevel::default(),
-                ),
-                store_recipe: false,
+                packaging_settings: packaging.packaging_settings(),
+                store_recipe: packaging.store_recipe,
                 force_colors: true,
-                sandbox_config: None,
+                sandbox_config: self
+                    .config
+                    .sandbox()
+                    .map(SandboxConfig::configuration)
+                    .transpose()?,
                 debug: Debug::new(false),
                 exclude_newer: None,
             },
             finalized_dependencies: Some(from_build_v1_args_to_finalized_dependencies(
                 params.build_prefix,
                 params.host_prefix,
-                params.run_dependencies,
-                params.run_constraints,
+                run_dependencies,
+                run_constraints,
                 params.run_exports,
             )),
             finalized_sources: None,
@@ -377,9 +426,14 @@ impl Protocol for RattlerBuildBackend {
             extra_meta: None,
         };
 
-        let (output, output_path) =
-            // rattler-build requires a clean work dir
-            run_build(output, &tool_config, WorkingDirectoryBehavior::Cleanup).await?;
+        let package = output.name().as_normalized().to_string();
+        // rattler-build requires a clean work dir
+        let build = run_build(output, &tool_config, WorkingDirectoryBehavior::Cleanup);
+        let (output, output_path) = if self.config.sandbox().is_some() {
+            run_sandboxed(&package, build).await?
+        } else {
+            build.await?
+        };
 
         Ok(CondaBuildV1Result {
             output_file: output_path,
@@ -400,18 +454,24 @@ impl Protocol for RattlerBuildBackend {
 /// Extracts the package sources from an `Output` object that are mutable and
 /// should be watched for changes.
 fn extract_mutable_package_sources(output: &Output) -> Option<Vec<PathBuf>> {
-    output.finalized_sources.as_ref().map(|package_sources| {
-        package_sources
-            .iter()
-            .filter_map(|source| {
-                if let rattler_build::recipe::parser::Source::Path(path_source) = source {
-                    Some(path_source.path.clone())
-                } else {
-                    None
-                }
-            })
-            .collect()
-    })
+    output
+        .finalized_sources
+        .as_ref()
+        .map(|package_sources| path_sources(package_sources.as_slice()))
+}
+
+/// Returns the paths of the local path sources.
+fn path_sources(sources: &[rattler_build::recipe::parser::Source]) -> Vec<PathBuf> {
+    sources
+        .iter()
+        .filter_map(|source| {
+            if let rattler_build::recipe::parser::Source::Path(path_source) = source {
+                Some(path_source.path.clone())
+            } else {
+                None
+            }
+        })
+        .collect()
 }
 
 /// Returns the relative path from `base` to `input`, joined by "/".
@@ -502,9 +562,11 @@ impl ProtocolInstantiator for RattlerBuildBackendInstantiator {
         params: InitializeParams,
     ) -> miette::Result<(Box<dyn Protocol + Send + Sync + 'static>, InitializeResult)> {
         let config = if let Some(config) = params.configuration {
-            serde_json::from_value(config)
-                .into_diagnostic()
-                .context("failed to parse configuration")?
+            parse_config::<RattlerBuildBackendConfig>(
+                &config,
+                ConfigSection::Base,
+                &params.manifest_path,
+            )?
         } else {
             RattlerBuildBackendConfig::default()
         };
@@ -587,6 +649,10 @@ impl ProtocolInstantiator for RattlerBuildBackendInstantiator {
             capabilities: default_capabilities(),
         })
     }
+
+    fn config_schema() -> Option<serde_json::Value> {
+        RattlerBuildBackendConfig::json_schema()
+    }
 }
 
 pub(crate) fn default_capabilities() -> BackendCapabilities {
@@ -650,6 +716,77 @@ mod tests {
         });
     }
 
+    #[tokio::test]
+    async fn test_unresolvable_pin_compatible_is_an_error() {
+        let cases = [
+            // The build string of the host package is not known yet.
+            (
+                "${{ pin_compatible('libfoo', exact=true) }}",
+                "only known when the package is built",
+            ),
+            // There is no version to pin to.
+            (
+                "${{ pin_compatible('libfoo') }}",
+                "is not part of the variant",
+            ),
+        ];
+        for (pin, expected_error) in cases {
+            let temp_dir = tempdir().unwrap();
+            let recipe_path = temp_dir.path().join("recipe.yaml");
+            tokio::fs::write(
+                &recipe_path,
+                format!(
+                    r#"
+package:
+  name: pin-compatible
+  version: 0.1.0
+
+build:
+  noarch: generic
+
+requirements:
+  host:
+    - libfoo
+  run:
+    - {pin}
+"#
+                ),
+            )
+            .await
+            .unwrap();
+
+            let factory = RattlerBuildBackendInstantiator::new(LoggingOutputHandler::default())
+                .initialize(InitializeParams {
+                    workspace_root: None,
+                    source_dir: None,
+                    manifest_path: recipe_path,
+                    project_model: None,
+                    configuration: None,
+                    target_configuration: None,
+                    cache_directory: None,
+                })
+                .await
+                .unwrap();
+
+            let err = factory
+                .0
+                .conda_outputs(CondaOutputsParams {
+                    channels: vec![],
+                    host_platform: Platform::Linux64,
+                    build_platform: Platform::Linux64,
+                    variant_configuration: None,
+                    variant_files: None,
+                    work_directory: temp_dir.path().to_path_buf(),
+                })
+                .await
+                .unwrap_err();
+            assert!(
+                format!("{err:?}").contains(expected_error),
+                "unexpected error for `{pin}`: {err:?}"
+            );
+        }
+    }
+
     const VARIANT_RECIPE: &str = r#"
     package:
       name: variant-test
@@ -1,10 +1,17 @@
 use indexmap::IndexMap;
-use pixi_build_backend::generated_recipe::BackendConfig;
+use pixi_build_backend::{
+    cache::CompilerCache,
+    config_schema::config_schema,
+    generated_recipe::BackendConfig,
+    packaging::{PackageArchiveType, PackageCompressionLevel, PackagingOptions},
+    sandbox::SandboxConfig,
+};
 use std::path::{Path, PathBuf};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct RustBackendConfig {
     /// Extra args to pass for cargo
@@ -13,6 +20,10 @@ pub struct RustBackendConfig {
     /// Environment Variables
     #[serde(default)]
     pub env: IndexMap<String, String>,
+    /// Host environment variables that are forwarded to the build script as
+    /// secrets, either names or glob patterns like `CCACHE_*`
+    #[serde(default)]
+    pub env_passthrough: Vec<String>,
     /// Deprecated. Setting this has no effect; debug data is always written to
     /// the `debug` subdirectory of the work directory.
     #[serde(alias = "debug_dir")]
@@ -26,6 +37,31 @@ pub struct RustBackendConfig {
     /// List of compilers to use (e.g., ["rust", "c", "cxx"])
     /// If not specified, a default will be used
     pub compilers: Option<Vec<String>>,
+    /// Launch the compilers through a compiler cache, `sccache` or `ccache`,
+    /// which is added to the build requirements. The environment variables
+    /// that configure the cache are forwarded to the build script.
+    pub compiler_cache: Option<CompilerCache>,
+    /// Run the tests of the generated recipe, e.g. commands that invoke the
+    /// installed binaries, after the build. Defaults to `false`.
+    #[serde(default)]
+    pub run_tests: Option<bool>,
+    /// The archive format of the built package, `conda` or `tar.bz2`. Defaults
+    /// to `conda`.
+    pub archive_type: Option<PackageArchiveType>,
+    /// The compression level of the built package: `fast`, `default`, `max`
+    /// or a number. Defaults to `fast`.
+    pub compression_level: Option<PackageCompressionLevel>,
+    /// Store the rendered recipe inside the built package. Defaults to
+    /// `false`.
+    #[serde(default)]
+    pub store_recipe: Option<bool>,
+    /// Run the build script in the sandbox of rattler-build, which restricts
+    /// the paths it can access and blocks network access unless allowed.
+    pub sandbox: Option<SandboxConfig>,
+    /// A YAML file, relative to the manifest, that is deep-merged into the
+    /// generated recipe. Defaults to `overlay.yaml` next to the manifest if
+    /// it exists.
+    pub overlay: Option<PathBuf>,
 }
 
 impl RustBackendConfig {
@@ -45,12 +81,43 @@ impl BackendConfig for RustBackendConfig {
         self.debug_dir.as_deref()
     }
 
+    fn json_schema() -> Option<serde_json::Value> {
+        Some(config_schema::<Self>())
+    }
+
+    fn run_tests(&self) -> bool {
+        self.run_tests.unwrap_or(false)
+    }
+
+    fn packaging(&self) -> PackagingOptions {
+        PackagingOptions::from_config(self.archive_type, self.compression_level, self.store_recipe)
+    }
+
+    fn sandbox(&self) -> Option<&SandboxConfig> {
+        self.sandbox.as_ref()
+    }
+
+    fn env_passthrough(&self) -> &[String] {
+        &self.env_passthrough
+    }
+
+    fn overlay(&self) -> Option<&Path> {
+        self.overlay.as_deref()
+    }
+
     /// Merge this configuration with a target-specific configuration.
     /// Target-specific values override base values using the following rules:
     /// - extra_args: Platform-specific completely replaces base
     /// - env: Platform env vars override base, others merge
+    /// - env_passthrough: Platform-specific entries are added to base
     /// - debug_dir: Not allowed to have target specific value
     /// - extra_input_globs: Platform-specific completely replaces base
+    /// - compiler_cache: Platform-specific takes precedence
+    /// - run_tests: Platform-specific takes precedence
+    /// - archive_type, compression_level, store_recipe: Platform-specific takes
+    ///   precedence
+    /// - sandbox: Platform-specific completely replaces base
+    /// - overlay: Platform-specific takes precedence
     fn merge_with_target_config(&self, target_config: &Self) -> miette::Result<Self> {
         if target_config.debug_dir.is_some() {
             miette::bail!("`debug_dir` cannot have a target specific value");
@@ -67,6 +134,17 @@ impl BackendConfig for RustBackendConfig {
                 merged_env.extend(target_config.env.clone());
                 merged_env
             },
+            env_passthrough: {
+                let mut merged_env_passthrough = self.env_passthrough.clone();
+                merged_env_passthrough.extend(
+                    target_config
+                        .env_passthrough
+                        .iter()
+                        .filter(|name| !self.env_passthrough.contains(name))
+                        .cloned(),
+                );
+                merged_env_passthrough
+            },
             debug_dir: self.debug_dir.clone(),
             extra_input_globs: if target_config.extra_input_globs.is_empty() {
                 self.extra_input_globs.clone()
@@ -80,6 +158,19 @@ impl BackendConfig for RustBackendConfig {
                 .compilers
                 .clone()
                 .or_else(|| self.compilers.clone()),
+            compiler_cache: target_config.compiler_cache.or(self.compiler_cache),
+            run_tests: target_config.run_tests.or(self.run_tests),
+            archive_type: target_config.archive_type.or(self.archive_type),
+            compression_level: target_config.compression_level.or(self.compression_level),
+            store_recipe: target_config.store_recipe.or(self.store_recipe),
+            sandbox: target_config
+                .sandbox
+                .clone()
+                .or_else(|| self.sandbox.clone()),
+            overlay: target_config
+                .overlay
+                .clone()
+                .or_else(|| self.overlay.clone()),
         })
     }
 }
@@ -87,10 +178,17 @@ impl BackendConfig for RustBackendConfig {
 #[cfg(test)]
 mod tests {
     use super::RustBackendConfig;
-    use pixi_build_backend::generated_recipe::BackendConfig;
+    use pixi_build_backend::{
+        config_schema::assert_schema_up_to_date, generated_recipe::BackendConfig,
+    };
     use serde_json::json;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_config_schema_is_up_to_date() {
+        assert_schema_up_to_date::<RustBackendConfig>("pixi-build-rust");
+    }
+
     #[test]
     fn test_ensure_deseralize_from_empty() {
         let json_data = json!({});
@@ -123,10 +221,18 @@ mod tests {
         let base_config = RustBackendConfig {
             extra_args: vec!["--base-arg".to_string()],
             env: base_env,
+            env_passthrough: Vec::new(),
             debug_dir: Some(PathBuf::from("/base/debug")),
             extra_input_globs: vec!["*.base".to_string()],
             ignore_cargo_manifest: None,
             compilers: Some(vec!["rust".to_string()]),
+            compiler_cache: None,
+            run_tests: Some(true),
+            archive_type: None,
+            compression_level: None,
+            store_recipe: None,
+            sandbox: None,
+            overlay: None,
         };
 
         let mut target_env = indexmap::IndexMap::new();
@@ -136,10 +242,18 @@ mod tests {
         let target_config = RustBackendConfig {
             extra_args: vec!["--target-arg".to_string()],
             env: target_env,
+            env_passthrough: Vec::new(),
             debug_dir: None,
             extra_input_globs: vec!["*.target".to_string()],
             ignore_cargo_manifest: Some(true),
             compilers: Some(vec!["c".to_string(), "rust".to_string()]),
+            compiler_cache: None,
+            run_tests: Some(false),
+            archive_type: None,
+            compression_level: None,
+            store_recipe: None,
+            sandbox: None,
+            overlay: None,
         };
 
         let merged = base_config
@@ -171,6 +285,9 @@ mod tests {
             merged.compilers,
             Some(vec!["c".to_string(), "rust".to_string()])
         );
+
+        // run_tests should use the target value
+        assert!(!merged.run_tests());
     }
 
     #[test]
@@ -181,10 +298,18 @@ mod tests {
         let base_config = RustBackendConfig {
             extra_args: vec!["--base-arg".to_string()],
             env: base_env,
+            env_passthrough: Vec::new(),
             debug_dir: Some(PathBuf::from("/base/debug")),
             extra_input_globs: vec!["*.base".to_string()],
             ignore_cargo_manifest: None,
             compilers: Some(vec!["rust".to_string()]),
+            compiler_cache: None,
+            run_tests: Some(true),
+            archive_type: None,
+            compression_level: None,
+            store_recipe: None,
+            sandbox: None,
+            overlay: None,
         };
 
         let empty_target_config = RustBackendConfig::default();
@@ -199,6 +324,7 @@ mod tests {
         assert_eq!(merged.debug_dir, Some(PathBuf::from("/base/debug")));
         assert_eq!(merged.extra_input_globs, vec!["*.base".to_string()]);
         assert_eq!(merged.compilers, Some(vec!["rust".to_string()]));
+        assert!(merged.run_tests());
     }
 
     #[test]
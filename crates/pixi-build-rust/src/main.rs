@@ -8,17 +8,15 @@ use metadata::CargoMetadataProvider;
 use miette::IntoDiagnostic;
 use pixi_build_backend::variants::NormalizedKey;
 use pixi_build_backend::{
-    cache::{sccache_envs, sccache_tools},
-    generated_recipe::{GenerateRecipe, GeneratedRecipe, PythonParams},
+    cache::{CompilerCache, sccache_envs},
+    generated_recipe::{GenerateRecipe, GeneratedRecipe, MetadataProvider, PythonParams},
+    git_metadata::GitMetadataProvider,
     intermediate_backend::IntermediateBackendInstantiator,
     traits::ProjectModel,
 };
 use pixi_build_types::ProjectModelV1;
 use rattler_conda_types::{ChannelUrl, Platform};
-use recipe_stage0::{
-    matchspec::PackageDependency,
-    recipe::{Item, Script},
-};
+use recipe_stage0::recipe::Script;
 use std::collections::HashSet;
 use std::{
     collections::{BTreeSet, HashMap},
@@ -60,14 +58,21 @@ impl GenerateRecipe for RustGenerator {
             manifest_path.clone()
         };
 
-        let mut cargo_metadata = CargoMetadataProvider::new(
+        // The version is derived from the git tags if it is not defined in the
+        // Cargo.toml.
+        let mut metadata_provider = CargoMetadataProvider::new(
             &manifest_root,
             config.ignore_cargo_manifest.is_some_and(|ignore| ignore),
-        );
+        )
+        .or(GitMetadataProvider::new(&manifest_root));
 
         // Create the recipe
-        let mut generated_recipe =
-            GeneratedRecipe::from_model(model.clone(), &mut cargo_metadata).into_diagnostic()?;
+        let mut generated_recipe = GeneratedRecipe::from_model_with_manifest_root(
+            model.clone(),
+            &mut metadata_provider,
+            &manifest_root,
+        )
+        .into_diagnostic()?;
 
         // we need to add compilers
         let requirements = &mut generated_recipe.recipe.requirements;
@@ -103,56 +108,34 @@ impl GenerateRecipe for RustGenerator {
             .host
             .contains_key(&pixi_build_types::SourcePackageName::from("openssl"));
 
-        let mut has_sccache = false;
-
         let config_env = config.env.clone();
 
-        let system_env_vars = std::env::vars().collect::<HashMap<String, String>>();
-
-        let all_env_vars = config_env
-            .clone()
-            .into_iter()
-            .chain(system_env_vars.clone())
-            .collect();
-
-        let mut sccache_secrets = Vec::default();
-
-        // Verify if user has set any sccache environment variables
-        if sccache_envs(&all_env_vars).is_some() {
-            // check if we set some sccache in system env vars
-            if let Some(system_sccache_keys) = sccache_envs(&system_env_vars) {
-                // If sccache_envs are used in the system environment variables,
-                // we need to set them as secrets
-                let system_sccache_keys = system_env_vars
-                    .keys()
-                    // we set only those keys that are present in the system environment variables
-                    // and not in the config env
-                    .filter(|key| {
-                        system_sccache_keys.contains(&key.as_str())
-                            && !config_env.contains_key(*key)
-                    })
-                    .cloned()
-                    .collect();
-
-                sccache_secrets = system_sccache_keys;
-            };
-
-            let sccache_dep: Vec<Item<PackageDependency>> = sccache_tools()
-                .iter()
-                .map(|tool| tool.parse().into_diagnostic())
-                .collect::<miette::Result<Vec<_>>>()?;
-
-            // Add sccache tools to the build requirements
-            // only if they are not already present
-            let existing_reqs: Vec<_> = requirements.build.clone().into_iter().collect();
-
-            requirements.build.extend(
-                sccache_dep
+        // sccache is used when it is configured explicitly or when any of its
+        // environment variables is set. ccache can not cache `rustc`.
+        let has_sccache = match config.compiler_cache {
+            Some(CompilerCache::Sccache) => true,
+            Some(CompilerCache::Ccache) => miette::bail!(
+                help = "use `compiler-cache = \"sccache\"` instead",
+                "`ccache` cannot be used as the compiler cache of a Rust package"
+            ),
+            None => {
+                let system_env_vars = std::env::vars().collect::<HashMap<String, String>>();
+                let all_env_vars = config_env
+                    .clone()
                     .into_iter()
-                    .filter(|dep| !existing_reqs.contains(dep)),
-            );
+                    .chain(system_env_vars)
+                    .collect();
+                sccache_envs(&all_env_vars).is_some()
+            }
+        };
 
-            has_sccache = true;
+        // Add sccache to the build requirements and forward the sccache
+        // environment variables of the system that are not set in the config
+        let mut sccache_secrets = Vec::default();
+        if has_sccache {
+            CompilerCache::Sccache
+                .add_to_requirements(&mut requirements.build, &model_dependencies)?;
+            sccache_secrets = CompilerCache::Sccache.host_secrets(&config_env)?;
         }
 
         let build_script = BuildScriptContext {
@@ -170,10 +153,24 @@ impl GenerateRecipe for RustGenerator {
             secrets: sccache_secrets,
         };
 
-        // Add the input globs from the Cargo metadata provider
+        // Add the input globs from the metadata providers
         generated_recipe
             .metadata_input_globs
-            .extend(cargo_metadata.input_globs());
+            .extend(metadata_provider.input_globs());
+
+        // Identify the package as the crate it was built from.
+        let version = generated_recipe.recipe.package.version.to_string();
+        if let Some(purl) = metadata_provider
+            .first_mut()
+            .purl(&version)
+            .into_diagnostic()?
+        {
+            generated_recipe
+                .purls
+                .entry(generated_recipe.recipe.package.name.to_string())
+                .or_default()
+                .insert(purl);
+        }
 
         Ok(generated_recipe)
     }
@@ -511,7 +508,10 @@ mod tests {
         insta::assert_yaml_snapshot!(&generated_recipe.metadata_input_globs, @r###"
         - "../../Cargo.toml"
         - "../Cargo.toml"
+        - COPYING*
         - Cargo.toml
+        - LICENSE*
+        - NOTICE*
         "###);
     }
 
@@ -7,7 +7,7 @@ use cargo_toml::{
 use miette::Diagnostic;
 use once_cell::unsync::OnceCell;
 use pixi_build_backend::generated_recipe::MetadataProvider;
-use rattler_conda_types::{ParseVersionError, Version};
+use rattler_conda_types::{PackageUrl, ParseVersionError, Version};
 
 #[derive(Debug, thiserror::Error, Diagnostic)]
 pub enum MetadataError {
@@ -80,64 +80,32 @@ impl CargoMetadataProvider {
         Ok(manifest.workspace.as_ref().and_then(|w| w.package.as_ref()))
     }
 
-    /// Returns the set of globs that match files that influence the metadata of
-    /// this package.
-    ///
-    /// This includes the package's own `Cargo.toml` file and any workspace
-    /// `Cargo.toml` files if workspace inheritance is detected. These globs
-    /// can be used for incremental builds to determine when metadata might
-    /// have changed.
-    ///
-    /// # Returns
+    /// Returns the package URL (purl) of the crate that is defined in the
+    /// Cargo.toml manifest, e.g. `pkg:cargo/my-crate@1.0.0`.
     ///
-    /// A `BTreeSet` of glob patterns as strings. Common patterns include:
-    /// - `"Cargo.toml"` - The package's manifest file
-    /// - `"../../**/Cargo.toml"` - Workspace manifest files (when workspace
-    ///   inheritance is used)
-    pub fn input_globs(&self) -> BTreeSet<String> {
-        let mut input_globs = BTreeSet::new();
-
-        let Some(_) = self.cargo_manifest.get() else {
-            return input_globs;
+    /// The version from the manifest is used if it is defined, otherwise the
+    /// `fallback_version`. Returns `None` if there is no manifest, if it is
+    /// ignored or if the package url is invalid.
+    pub fn purl(&mut self, fallback_version: &str) -> Result<Option<PackageUrl>, MetadataError> {
+        if self.ignore_cargo_manifest || !self.manifest_root.join("Cargo.toml").is_file() {
+            return Ok(None);
+        }
+        let Some(name) = self.name()? else {
+            return Ok(None);
         };
-
-        // Add the Cargo.toml manifest file itself.
-        input_globs.insert(String::from("Cargo.toml"));
-
-        // If the manifest has workspace inheritance, include that as well.
-        if let Some((_, workspace_path)) = self.workspace_manifest.get() {
-            // If the workspace is defined in the package we just include the path to the
-            // workspace itself.
-            let workspace_selected = self
-                .cargo_manifest
-                .get()
-                .and_then(|p| p.package.as_ref())
-                .is_some_and(|p| p.workspace.is_some());
-
-            if let Some(path) = pathdiff::diff_paths(
-                workspace_path
-                    .parent()
-                    .expect("the workspace path is a file so it must have a parent"),
-                &self.manifest_root,
-            ) {
-                if workspace_selected {
-                    input_globs.insert(format!(
-                        "{}/Cargo.toml",
-                        path.display().to_string().replace("\\", "/")
-                    ));
-                } else {
-                    // Otherwise we assume the file is located in a parent directory of the package.
-                    input_globs.extend(
-                        path.components()
-                            .take_while(|p| matches!(p, std::path::Component::ParentDir))
-                            .enumerate()
-                            .map(|(idx, _)| format!("{}Cargo.toml", "../".repeat(idx + 1))),
-                    )
-                }
+        let version = self
+            .version()?
+            .map_or_else(|| fallback_version.to_string(), |v| v.to_string());
+        match PackageUrl::builder(String::from("cargo"), name.clone())
+            .with_version(version)
+            .build()
+        {
+            Ok(purl) => Ok(Some(purl)),
+            Err(err) => {
+                tracing::warn!("ignoring the package url of `{name}`: {err}");
+                Ok(None)
             }
         }
-
-        input_globs
     }
 }
 
@@ -356,6 +324,72 @@ impl MetadataProvider for CargoMetadataProvider {
         };
         Ok(Some(repository.clone()))
     }
+
+    /// Returns the set of globs that match files that influence the metadata of
+    /// this package.
+    ///
+    /// This includes the package's own `Cargo.toml` file and any workspace
+    /// `Cargo.toml` files if workspace inheritance is detected. These globs
+    /// can be used for incremental builds to determine when metadata might
+    /// have changed.
+    ///
+    /// # Returns
+    ///
+    /// A `BTreeSet` of glob patterns as strings. Common patterns include:
+    /// - `"Cargo.toml"` - The package's manifest file
+    /// - `"../../**/Cargo.toml"` - Workspace manifest files (when workspace
+    ///   inheritance is used)
+    fn input_globs(&self) -> BTreeSet<String> {
+        let mut input_globs = BTreeSet::new();
+
+        let Some(_) = self.cargo_manifest.get() else {
+            return input_globs;
+        };
+
+        // Add the Cargo.toml manifest file itself.
+        input_globs.insert(String::from("Cargo.toml"));
+
+        // If the manifest has workspace inheritance, include that as well.
+        if let Some((_, workspace_path)) = self.workspace_manifest.get() {
+            // If the workspace is defined in the package we just include the path to the
+            // workspace itself.
+            let workspace_selected = self
+                .cargo_manifest
+                .get()
+                .and_then(|p| p.package.as_ref())
+                .is_some_and(|p| p.workspace.is_some());
+
+            if let Some(path) = pathdiff::diff_paths(
+                workspace_path
+                    .parent()
+                    .expect("the workspace path is a file so it must have a parent"),
+                &self.manifest_root,
+            ) {
+                if workspace_selected {
+                    input_globs.insert(format!(
+                        "{}/Cargo.toml",
+                        path.display().to_string().replace("\\", "/")
+                    ));
+                } else {
+                    // Otherwise we assume the file is located in a parent directory of the package.
+                    input_globs.extend(
+                        path.components()
+                            .take_while(|p| matches!(p, std::path::Component::ParentDir))
+                            .enumerate()
+                            .map(|(idx, _)| format!("{}Cargo.toml", "../".repeat(idx + 1))),
+                    )
+                }
+            }
+        }
+
+        input_globs
+    }
+
+    /// All values are read from the `Cargo.toml` file, including the values
+    /// that are inherited from the workspace.
+    fn value_source(&self, _field: &str) -> Option<String> {
+        Some(String::from("Cargo.toml"))
+    }
 }
 
 #[cfg(test)]
@@ -467,6 +501,28 @@ documentation.workspace = true
         );
     }
 
+    #[test]
+    fn test_purl_with_inherited_version() {
+        let cargo_toml_content = r#"
+[workspace.package]
+version = "1.2.3"
+
+[package]
+name = "test-package"
+version.workspace = true
+"#;
+
+        let temp_dir = create_temp_cargo_project(cargo_toml_content);
+        let mut provider = create_metadata_provider(temp_dir.path());
+        assert_eq!(
+            provider.purl("0.1.0").unwrap().map(|purl| purl.to_string()),
+            Some(String::from("pkg:cargo/test-package@1.2.3"))
+        );
+
+        let mut ignoring_provider = CargoMetadataProvider::new(temp_dir.path(), true);
+        assert_eq!(ignoring_provider.purl("0.1.0").unwrap(), None);
+    }
+
     #[test]
     fn test_inheritance_without_workspace_version() {
         let cargo_toml_content = r#"
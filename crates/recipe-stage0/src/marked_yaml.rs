@@ -6,7 +6,8 @@ pub type MappingHash = LinkedHashMap<MarkedScalarNode, MarkedNode>;
 
 use crate::recipe::{
     About, Build, Conditional, ConditionalList, ConditionalRequirements, Extra, IntermediateRecipe,
-    Item, ListOrItem, Package, PackageContents, Source, Test, Value,
+    Item, ListOrItem, Output, OutputPackage, Package, PackageContents, PythonTest, Source, Test,
+    TestRequirements, Value,
 };
 
 // Trait for converting to marked YAML nodes
@@ -213,6 +214,72 @@ impl ToMarkedYaml for Test {
             );
         }
 
+        if let Some(ref script) = self.script {
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "script"),
+                script.to_marked_yaml(),
+            );
+        }
+
+        if let Some(ref requirements) = self.requirements {
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "requirements"),
+                requirements.to_marked_yaml(),
+            );
+        }
+
+        if let Some(ref python) = self.python {
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "python"),
+                python.to_marked_yaml(),
+            );
+        }
+
+        MarkedNode::Mapping(MarkedMappingNode::new(Span::new_blank(), mapping))
+    }
+}
+
+impl ToMarkedYaml for TestRequirements {
+    fn to_marked_yaml(&self) -> MarkedNode {
+        let mut mapping = MappingHash::new();
+
+        if !self.run.is_empty() {
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "run"),
+                self.run.to_marked_yaml(),
+            );
+        }
+
+        if !self.build.is_empty() {
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "build"),
+                self.build.to_marked_yaml(),
+            );
+        }
+
+        MarkedNode::Mapping(MarkedMappingNode::new(Span::new_blank(), mapping))
+    }
+}
+
+impl ToMarkedYaml for PythonTest {
+    fn to_marked_yaml(&self) -> MarkedNode {
+        let mut mapping = MappingHash::new();
+
+        mapping.insert(
+            MarkedScalarNode::new(Span::new_blank(), "imports"),
+            self.imports.to_marked_yaml(),
+        );
+
+        if let Some(pip_check) = self.pip_check {
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "pip_check"),
+                MarkedNode::Scalar(MarkedScalarNode::new(
+                    Span::new_blank(),
+                    pip_check.to_string(),
+                )),
+            );
+        }
+
         MarkedNode::Mapping(MarkedMappingNode::new(Span::new_blank(), mapping))
     }
 }
@@ -235,10 +302,11 @@ impl ToMarkedYaml for About {
             );
         }
 
-        if let Some(ref license_file) = self.license_file {
+        let license_files: Vec<_> = self.license_files().collect();
+        if !license_files.is_empty() {
             mapping.insert(
                 MarkedScalarNode::new(Span::new_blank(), "license_file"),
-                license_file.to_marked_yaml(),
+                ListOrItem::new(license_files).to_marked_yaml(),
             );
         }
 
@@ -289,6 +357,70 @@ impl ToMarkedYaml for Extra {
     }
 }
 
+impl ToMarkedYaml for OutputPackage {
+    fn to_marked_yaml(&self) -> MarkedNode {
+        let mut mapping = MappingHash::new();
+
+        mapping.insert(
+            MarkedScalarNode::new(Span::new_blank(), "name"),
+            self.name.to_marked_yaml(),
+        );
+
+        if let Some(ref version) = self.version {
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "version"),
+                version.to_marked_yaml(),
+            );
+        }
+
+        MarkedNode::Mapping(MarkedMappingNode::new(Span::new_blank(), mapping))
+    }
+}
+
+impl ToMarkedYaml for Output {
+    fn to_marked_yaml(&self) -> MarkedNode {
+        let mut mapping = MappingHash::new();
+
+        mapping.insert(
+            MarkedScalarNode::new(Span::new_blank(), "package"),
+            self.package.to_marked_yaml(),
+        );
+
+        if let Some(ref build) = self.build {
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "build"),
+                build.to_marked_yaml(),
+            );
+        }
+
+        mapping.insert(
+            MarkedScalarNode::new(Span::new_blank(), "requirements"),
+            self.requirements.to_marked_yaml(),
+        );
+
+        if !self.tests.is_empty() {
+            let test_nodes: Vec<MarkedNode> = self
+                .tests
+                .iter()
+                .map(|test| test.to_marked_yaml())
+                .collect();
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "tests"),
+                MarkedNode::Sequence(MarkedSequenceNode::new(Span::new_blank(), test_nodes)),
+            );
+        }
+
+        if let Some(ref about) = self.about {
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "about"),
+                about.to_marked_yaml(),
+            );
+        }
+
+        MarkedNode::Mapping(MarkedMappingNode::new(Span::new_blank(), mapping))
+    }
+}
+
 impl ToMarkedYaml for IntermediateRecipe {
     fn to_marked_yaml(&self) -> MarkedNode {
         let mut mapping = MappingHash::new();
@@ -308,9 +440,16 @@ impl ToMarkedYaml for IntermediateRecipe {
             );
         }
 
-        // Add package
+        // Add package, a multi-output recipe names this section `recipe`
         mapping.insert(
-            MarkedScalarNode::new(Span::new_blank(), "package"),
+            MarkedScalarNode::new(
+                Span::new_blank(),
+                if self.is_multi_output() {
+                    "recipe"
+                } else {
+                    "package"
+                },
+            ),
             self.package.to_marked_yaml(),
         );
 
@@ -326,21 +465,25 @@ impl ToMarkedYaml for IntermediateRecipe {
             self.build.to_marked_yaml(),
         );
 
-        mapping.insert(
-            MarkedScalarNode::new(Span::new_blank(), "requirements"),
-            self.requirements.to_marked_yaml(),
-        );
-
-        if !self.tests.is_empty() {
-            let test_nodes: Vec<MarkedNode> = self
-                .tests
-                .iter()
-                .map(|test| test.to_marked_yaml())
-                .collect();
+        // Requirements and tests of a multi-output recipe live in its outputs,
+        // `IntermediateRecipe::validate` rejects top-level ones
+        if !self.is_multi_output() {
             mapping.insert(
-                MarkedScalarNode::new(Span::new_blank(), "tests"),
-                MarkedNode::Sequence(MarkedSequenceNode::new(Span::new_blank(), test_nodes)),
+                MarkedScalarNode::new(Span::new_blank(), "requirements"),
+                self.requirements.to_marked_yaml(),
             );
+
+            if !self.tests.is_empty() {
+                let test_nodes: Vec<MarkedNode> = self
+                    .tests
+                    .iter()
+                    .map(|test| test.to_marked_yaml())
+                    .collect();
+                mapping.insert(
+                    MarkedScalarNode::new(Span::new_blank(), "tests"),
+                    MarkedNode::Sequence(MarkedSequenceNode::new(Span::new_blank(), test_nodes)),
+                );
+            }
         }
 
         if let Some(ref about) = self.about {
@@ -357,6 +500,18 @@ impl ToMarkedYaml for IntermediateRecipe {
             );
         }
 
+        if self.is_multi_output() {
+            let output_nodes: Vec<MarkedNode> = self
+                .outputs
+                .iter()
+                .map(|output| output.to_marked_yaml())
+                .collect();
+            mapping.insert(
+                MarkedScalarNode::new(Span::new_blank(), "outputs"),
+                MarkedNode::Sequence(MarkedSequenceNode::new(Span::new_blank(), output_nodes)),
+            );
+        }
+
         MarkedNode::Mapping(MarkedMappingNode::new(Span::new_blank(), mapping))
     }
 }
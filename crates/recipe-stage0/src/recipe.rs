@@ -336,7 +336,9 @@ pub type ConditionalList<T> = Vec<Item<T>>;
 pub struct IntermediateRecipe {
     #[serde(default)]
     pub context: IndexMap<String, Value<String>>,
-    #[serde(default)]
+    /// The package of a single-output recipe. For a multi-output recipe this is
+    /// rendered as the top-level `recipe` section instead.
+    #[serde(default, alias = "recipe")]
     pub package: Package,
     #[serde(default)]
     pub source: ConditionalList<Source>,
@@ -350,6 +352,10 @@ pub struct IntermediateRecipe {
     pub about: Option<About>,
     #[serde(default)]
     pub extra: Option<Extra>,
+    /// The outputs of a multi-output recipe. If this is empty the recipe
+    /// describes a single package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<Output>,
 }
 
 pub struct EvaluatedDependencies {
@@ -380,6 +386,58 @@ impl Display for Package {
     }
 }
 
+/// The package section of an output in a multi-output recipe. If the version
+/// is not set, it is inherited from the top-level `recipe` section.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutputPackage {
+    pub name: Value<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<Value<String>>,
+}
+
+impl Display for OutputPackage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{}-{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// A single output of a multi-output recipe.
+///
+/// Sections that are not set are inherited from the top-level recipe by
+/// rattler-build, so an output only has to describe what is different.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Output {
+    pub package: OutputPackage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<Build>,
+    #[serde(default)]
+    pub requirements: ConditionalRequirements,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tests: Vec<Test>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub about: Option<About>,
+}
+
+impl Output {
+    /// Creates a new output with the given name that inherits everything else
+    /// from the top-level recipe.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            package: OutputPackage {
+                name: Value::Concrete(name.into()),
+                version: None,
+            },
+            build: None,
+            requirements: ConditionalRequirements::default(),
+            tests: Vec::new(),
+            about: None,
+        }
+    }
+}
+
 /// Source information.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(untagged)]
@@ -583,6 +641,14 @@ pub struct ConditionalRequirements {
 }
 
 impl ConditionalRequirements {
+    /// Returns true if there are no requirements in any of the sections.
+    pub fn is_empty(&self) -> bool {
+        self.build.is_empty()
+            && self.host.is_empty()
+            && self.run.is_empty()
+            && self.run_constraints.is_empty()
+    }
+
     /// Resolves the conditional requirements for a given platform.
     pub fn resolve(
         build: &ConditionalList<PackageDependency>,
@@ -667,17 +733,104 @@ impl Display for ConditionalRequirements {
 //     pub run_constraints: Vec<SerializableMatchSpec>,
 // }
 
+/// A test that is executed against the built package. A single test should
+/// only describe one kind of test, e.g. either a script or a python test.
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Test {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub package_contents: Option<PackageContents>,
+    /// The commands of a script test. Each command is executed in an
+    /// environment that contains the package and the test requirements.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<ConditionalList<String>>,
+    /// Additional requirements of a script test.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requirements: Option<TestRequirements>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<PythonTest>,
+}
+
+impl Test {
+    /// Creates a script test that executes the given commands, e.g. to check
+    /// that an executable of the package can be invoked.
+    pub fn script(commands: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            script: Some(
+                commands
+                    .into_iter()
+                    .map(|command| Item::Value(Value::Concrete(command.into())))
+                    .collect(),
+            ),
+            ..Self::default()
+        }
+    }
+
+    /// Creates a python test that imports the given modules.
+    pub fn python_imports(imports: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            python: Some(PythonTest {
+                imports: imports
+                    .into_iter()
+                    .map(|import| Item::Value(Value::Concrete(import.into())))
+                    .collect(),
+                pip_check: None,
+            }),
+            ..Self::default()
+        }
+    }
 }
 
 impl Display for Test {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Test {{ package_contents: {} }}",
-            self.package_contents.as_ref().into_iter().format("")
+            "Test {{ package_contents: {}, script: {}, requirements: {}, python: {} }}",
+            self.package_contents.as_ref().into_iter().format(""),
+            self.script.as_ref().into_iter().flatten().format(", "),
+            self.requirements.as_ref().into_iter().format(""),
+            self.python.as_ref().into_iter().format(""),
+        )
+    }
+}
+
+/// The requirements of a script test.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TestRequirements {
+    /// Packages that are installed alongside the package under test.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub run: ConditionalList<PackageDependency>,
+    /// Packages that are installed in a separate build environment.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub build: ConditionalList<PackageDependency>,
+}
+
+impl Display for TestRequirements {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TestRequirements {{ run: {}, build: {} }}",
+            self.run.iter().format(", "),
+            self.build.iter().format(", "),
+        )
+    }
+}
+
+/// A test that imports python modules from the package.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PythonTest {
+    pub imports: ConditionalList<String>,
+    /// Whether to run `pip check`, rattler-build defaults to `true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pip_check: Option<bool>,
+}
+
+impl Display for PythonTest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PythonTest {{ imports: {}, pip_check: {} }}",
+            self.imports.iter().format(", "),
+            self.pip_check.as_ref().into_iter().format(""),
         )
     }
 }
@@ -700,16 +853,74 @@ impl Display for PackageContents {
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(from = "AboutRepr", into = "AboutRepr")]
 pub struct About {
     pub homepage: Option<Value<String>>,
     pub license: Option<Value<String>>,
     pub license_file: Option<Value<String>>,
+    /// License files in addition to the `license_file`. They are rendered
+    /// together with it as the `license_file` list of the recipe.
+    pub additional_license_files: Vec<Value<String>>,
     pub summary: Option<Value<String>>,
     pub description: Option<Value<String>>,
     pub documentation: Option<Value<String>>,
     pub repository: Option<Value<String>>,
 }
 
+impl About {
+    /// Returns the `license_file` followed by the additional license files.
+    pub fn license_files(&self) -> impl Iterator<Item = &Value<String>> {
+        self.license_file
+            .iter()
+            .chain(&self.additional_license_files)
+    }
+}
+
+/// The serialized form of [`About`], in which all license files are a single
+/// `license_file` list.
+#[derive(Serialize, Deserialize)]
+struct AboutRepr {
+    homepage: Option<Value<String>>,
+    license: Option<Value<String>>,
+    license_file: Option<ListOrItem<Value<String>>>,
+    summary: Option<Value<String>>,
+    description: Option<Value<String>>,
+    documentation: Option<Value<String>>,
+    repository: Option<Value<String>>,
+}
+
+impl From<About> for AboutRepr {
+    fn from(about: About) -> Self {
+        let license_files: Vec<_> = about.license_files().cloned().collect();
+        Self {
+            homepage: about.homepage,
+            license: about.license,
+            license_file: (!license_files.is_empty()).then(|| ListOrItem::new(license_files)),
+            summary: about.summary,
+            description: about.description,
+            documentation: about.documentation,
+            repository: about.repository,
+        }
+    }
+}
+
+impl From<AboutRepr> for About {
+    fn from(about: AboutRepr) -> Self {
+        let mut license_files = about.license_file.map(|files| files.0).unwrap_or_default();
+        let license_file = (!license_files.is_empty()).then(|| license_files.remove(0));
+        Self {
+            homepage: about.homepage,
+            license: about.license,
+            license_file,
+            additional_license_files: license_files,
+            summary: about.summary,
+            description: about.description,
+            documentation: about.documentation,
+            repository: about.repository,
+        }
+    }
+}
+
 impl Display for About {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -717,7 +928,7 @@ impl Display for About {
             "About {{ homepage: {}, license: {}, license_file: {}, summary: {}, description: {}, documentation: {}, repository: {} }}",
             self.homepage.as_ref().into_iter().format(", "),
             self.license.as_ref().into_iter().format(", "),
-            self.license_file.as_ref().into_iter().format(", "),
+            self.license_files().format(", "),
             self.summary.as_ref().into_iter().format(", "),
             self.description.as_ref().into_iter().format(", "),
             self.documentation.as_ref().into_iter().format(", "),
@@ -744,9 +955,63 @@ impl Display for Extra {
 
 // Implementation for Recipe
 impl IntermediateRecipe {
+    /// Returns true if the recipe describes multiple outputs.
+    pub fn is_multi_output(&self) -> bool {
+        !self.outputs.is_empty()
+    }
+
     /// Converts the recipe to YAML string
     pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
-        serde_yaml::to_string(self)
+        if self.is_multi_output() {
+            serde_yaml::to_string(&self.to_multi_output_value()?)
+        } else {
+            serde_yaml::to_string(self)
+        }
+    }
+
+    /// Returns an error if a multi-output recipe has top-level requirements or
+    /// tests, these would be dropped because they only live in the outputs.
+    /// [`IntermediateRecipe::to_yaml`] fails for such a recipe, but
+    /// `to_marked_yaml` cannot fail and leaves them out, so call this first.
+    pub fn validate(&self) -> Result<(), serde_yaml::Error> {
+        use serde::ser::Error;
+
+        if !self.is_multi_output() {
+            return Ok(());
+        }
+        if !self.requirements.is_empty() {
+            return Err(serde_yaml::Error::custom(
+                "the top-level requirements of a multi-output recipe must be moved into its outputs",
+            ));
+        }
+        if !self.tests.is_empty() {
+            return Err(serde_yaml::Error::custom(
+                "the top-level tests of a multi-output recipe must be moved into its outputs",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Converts a multi-output recipe into the shape rattler-build expects:
+    /// the `package` section becomes the top-level `recipe` section and
+    /// requirements and tests only live in the individual outputs.
+    fn to_multi_output_value(&self) -> Result<serde_yaml::Value, serde_yaml::Error> {
+        self.validate()?;
+
+        let serde_yaml::Value::Mapping(mapping) = serde_yaml::to_value(self)? else {
+            unreachable!("a recipe always serializes to a mapping");
+        };
+
+        Ok(serde_yaml::Value::Mapping(
+            mapping
+                .into_iter()
+                .filter_map(|(key, value)| match key.as_str() {
+                    Some("package") => Some((serde_yaml::Value::from("recipe"), value)),
+                    Some("requirements") | Some("tests") => None,
+                    _ => Some((key, value)),
+                })
+                .collect(),
+        ))
     }
 
     /// Converts the recipe to pretty-formatted YAML string
@@ -844,6 +1109,7 @@ mod tests {
                 )),
                 license: Some("BSD-3-Clause".parse().unwrap()),
                 license_file: Some("LICENSE".parse().unwrap()),
+                additional_license_files: Vec::new(),
                 summary: Some("The C++ tensor algebra library".parse().unwrap()),
                 description: Some(
                     "Multi dimensional arrays with broadcasting and lazy computing"
@@ -861,4 +1127,137 @@ mod tests {
 
         insta::assert_yaml_snapshot!(recipe)
     }
+    #[test]
+    fn test_multi_output_recipe_to_yaml() {
+        let mut libfoo = Output::new("libfoo");
+        libfoo.requirements.run = vec!["zlib".parse().unwrap()];
+
+        let mut libfoo_dev = Output::new("libfoo-dev");
+        libfoo_dev.requirements.run = vec![
+            "${{ pin_subpackage('libfoo', exact=True) }}"
+                .parse()
+                .unwrap(),
+        ];
+
+        let recipe = IntermediateRecipe {
+            package: Package {
+                name: Value::Concrete("foo".to_string()),
+                version: Value::Concrete("1.0.0".to_string()),
+            },
+            outputs: vec![libfoo, libfoo_dev],
+            ..Default::default()
+        };
+        assert!(recipe.is_multi_output());
+
+        let yaml = recipe.to_yaml().unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert!(value.get("package").is_none());
+        assert!(value.get("requirements").is_none());
+        assert_eq!(value["recipe"]["name"].as_str(), Some("foo"));
+        assert_eq!(
+            value["outputs"][0]["package"]["name"].as_str(),
+            Some("libfoo")
+        );
+        assert_eq!(
+            value["outputs"][1]["requirements"]["run"][0].as_str(),
+            Some("${{ pin_subpackage('libfoo', exact=True) }}")
+        );
+
+        // The rendered recipe can be read back in.
+        let parsed = IntermediateRecipe::from_yaml(&yaml).unwrap();
+        assert_eq!(parsed.package.name.to_string(), "foo");
+        assert_eq!(parsed.outputs.len(), 2);
+    }
+
+    #[test]
+    fn test_multi_output_recipe_rejects_top_level_requirements() {
+        let recipe = IntermediateRecipe {
+            requirements: ConditionalRequirements {
+                run: vec!["zlib".parse().unwrap()],
+                ..Default::default()
+            },
+            outputs: vec![Output::new("libfoo")],
+            ..Default::default()
+        };
+        assert!(recipe.to_yaml().is_err());
+        assert!(recipe.validate().is_err());
+    }
+
+    #[test]
+    fn test_tests_to_yaml() {
+        let mut script_test = Test::script(["foo --version"]);
+        script_test.requirements = Some(TestRequirements {
+            run: vec!["pytest".parse().unwrap()],
+            ..Default::default()
+        });
+
+        let recipe = IntermediateRecipe {
+            tests: vec![script_test, Test::python_imports(["foo"])],
+            ..Default::default()
+        };
+
+        let yaml = recipe.to_yaml().unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let tests = value["tests"].as_sequence().unwrap();
+
+        // Every test only contains the keys of its own kind.
+        assert_eq!(tests[0].as_mapping().unwrap().len(), 2);
+        assert_eq!(tests[0]["script"][0].as_str(), Some("foo --version"));
+        assert_eq!(tests[0]["requirements"]["run"][0].as_str(), Some("pytest"));
+        assert_eq!(tests[1].as_mapping().unwrap().len(), 1);
+        assert_eq!(tests[1]["python"]["imports"][0].as_str(), Some("foo"));
+    }
+
+    #[test]
+    fn test_command_test_to_yaml() {
+        // A command test checks that the executables of the package can be
+        // invoked. A plain command needs no further keys.
+        let recipe = IntermediateRecipe {
+            tests: vec![Test::script(["foo --version"])],
+            ..Default::default()
+        };
+
+        let yaml = recipe.to_yaml().unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let test = &value["tests"][0];
+        assert_eq!(test.as_mapping().unwrap().len(), 1);
+        assert_eq!(test["script"].as_sequence().unwrap().len(), 1);
+        assert_eq!(test["script"][0].as_str(), Some("foo --version"));
+
+        let parsed = IntermediateRecipe::from_yaml(&yaml).unwrap();
+        let script = parsed.tests[0].script.as_ref().unwrap();
+        assert!(matches!(
+            &script[..],
+            [Item::Value(Value::Concrete(command))] if command == "foo --version"
+        ));
+
+        // The command may differ per platform.
+        let mut command_test = Test::script(["foo --version"]);
+        command_test
+            .script
+            .as_mut()
+            .unwrap()
+            .push(Item::Conditional(Conditional {
+                condition: "win".to_string(),
+                then: ListOrItem::single("foo.exe --help".to_string()),
+                else_value: ListOrItem::single("foo --help".to_string()),
+            }));
+
+        let recipe = IntermediateRecipe {
+            tests: vec![command_test],
+            ..Default::default()
+        };
+
+        let yaml = recipe.to_yaml().unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let script = value["tests"][0]["script"].as_sequence().unwrap();
+        assert_eq!(script[0].as_str(), Some("foo --version"));
+        assert_eq!(script[1]["if"].as_str(), Some("win"));
+        assert_eq!(script[1]["then"].as_str(), Some("foo.exe --help"));
+        assert_eq!(script[1]["else"].as_str(), Some("foo --help"));
+
+        // The test survives a round trip through the recipe format.
+        let parsed = IntermediateRecipe::from_yaml(&yaml).unwrap();
+        assert_eq!(parsed.tests[0].script.as_ref().unwrap().len(), 2);
+    }
 }
@@ -56,7 +56,7 @@ class PythonGenerator(GenerateRecipeProtocol):
         manifest_root = Path(manifest_path).parent
 
         # Create base recipe from model
-        generated_recipe = GeneratedRecipe.from_model(model)
+        generated_recipe = GeneratedRecipe.from_model(model, manifest_root=manifest_root)
 
         # Get recipe components
         recipe = generated_recipe.recipe
@@ -18,14 +18,24 @@ class GeneratedRecipe:
 
     @classmethod
     def from_model(
-        cls, model: ProjectModelV1, metadata_provider: Optional[MetadataProvider] = None
+        cls,
+        model: ProjectModelV1,
+        metadata_provider: Optional[MetadataProvider] = None,
+        manifest_root: Optional[Path] = None,
     ) -> "GeneratedRecipe":
-        """Create a GeneratedRecipe from a ProjectModelV1."""
+        """Create a GeneratedRecipe from a ProjectModelV1.
+
+        If the model does not specify a license file, the `LICENSE*`, `COPYING*`
+        and `NOTICE*` files in `manifest_root` are used. No license files are
+        discovered without a `manifest_root`.
+        """
         instance = cls()
         if metadata_provider is not None:
-            instance._inner = PyGeneratedRecipe().from_model_with_provider(model._inner, metadata_provider)
+            instance._inner = PyGeneratedRecipe().from_model_with_provider(
+                model._inner, metadata_provider, manifest_root
+            )
         else:
-            instance._inner = PyGeneratedRecipe().from_model(model._inner)
+            instance._inner = PyGeneratedRecipe().from_model(model._inner, manifest_root)
         return instance
 
     @property
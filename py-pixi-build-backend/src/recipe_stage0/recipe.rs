@@ -277,6 +277,7 @@ impl PyIntermediateRecipe {
             tests,
             about,
             extra,
+            outputs: Vec::new(),
         }
     }
 }
@@ -48,11 +48,23 @@ impl PyGeneratedRecipe {
         })
     }
 
+    /// License files are discovered in `manifest_root`, if it is given.
     #[staticmethod]
-    pub fn from_model(py: Python, model: PyProjectModelV1) -> PyResult<Self> {
-        let generated_recipe =
-            GeneratedRecipe::from_model(model.inner.clone(), &mut DefaultMetadataProvider)
-                .map_err(|e| PyErr::new::<PyValueError, _>(e.to_string()))?;
+    #[pyo3(signature = (model, manifest_root=None))]
+    pub fn from_model(
+        py: Python,
+        model: PyProjectModelV1,
+        manifest_root: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        let generated_recipe = match manifest_root {
+            Some(manifest_root) => GeneratedRecipe::from_model_with_manifest_root(
+                model.inner.clone(),
+                &mut DefaultMetadataProvider,
+                &manifest_root,
+            ),
+            None => GeneratedRecipe::from_model(model.inner.clone(), &mut DefaultMetadataProvider),
+        }
+        .map_err(|e| PyErr::new::<PyValueError, _>(e.to_string()))?;
 
         let py_recipe = Py::new(
             py,
@@ -85,14 +97,23 @@ impl PyGeneratedRecipe {
     }
 
     #[staticmethod]
+    #[pyo3(signature = (model, metadata_provider, manifest_root=None))]
     pub fn from_model_with_provider(
         py: Python,
         model: PyProjectModelV1,
         metadata_provider: Py<PyAny>,
+        manifest_root: Option<PathBuf>,
     ) -> PyResult<Self> {
         let mut provider = PyMetadataProvider::new(metadata_provider.clone());
-        let generated_recipe = GeneratedRecipe::from_model(model.inner.clone(), &mut provider)
-            .map_err(|e| PyErr::new::<PyValueError, _>(e.to_string()))?;
+        let generated_recipe = match manifest_root {
+            Some(manifest_root) => GeneratedRecipe::from_model_with_manifest_root(
+                model.inner.clone(),
+                &mut provider,
+                &manifest_root,
+            ),
+            None => GeneratedRecipe::from_model(model.inner.clone(), &mut provider),
+        }
+        .map_err(|e| PyErr::new::<PyValueError, _>(e.to_string()))?;
 
         // Get additional input globs from the metadata provider if available
         let mut metadata_input_globs = generated_recipe.metadata_input_globs;
@@ -142,6 +163,10 @@ impl PyGeneratedRecipe {
             recipe,
             metadata_input_globs,
             build_input_globs,
+            purls: BTreeMap::new(),
+            metadata_provenance: BTreeMap::new(),
+            build_constraints: BTreeMap::new(),
+            host_constraints: BTreeMap::new(),
         }
     }
 }